@@ -1,7 +1,7 @@
 #!/bin/sh
 
-# Small script to grab the chain specification of a running node.
+# Small script to refresh the checkpoint of a bundled chain specification from a running node.
+#
+# usage: ./generate_chain_spec.sh polkadot.json wss://node-address-here
 
-echo '{"id":53,"jsonrpc":"2.0","method":"sync_state_genSyncSpec","params":[true]}' |
-    websocat -n1 -B 99999999 wss://node-address-here |
-    jq .result > chain_spec.json
+cargo run --quiet -p rpc_provider --bin chain-spec -- refresh "$1" "$2"
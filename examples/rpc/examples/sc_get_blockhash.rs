@@ -6,7 +6,7 @@ use types_support::metadata::v15::polkadot_rpc::PolkadotRpcMethod;
 async fn main() {
 	let chain_spec = include_str!("../../../chain_spec/demo/polkadot.json");
 
-	let provider = ScProvider::new(chain_spec, vec![]);
+	let provider = ScProvider::new(chain_spec, vec![]).unwrap();
 	let method = PolkadotRpcMethod::ChainGetBlockHash.as_string();
 	let output = provider.request::<H256>(&method, rpc_params!(Some(0))).await.unwrap();
 
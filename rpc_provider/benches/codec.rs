@@ -0,0 +1,109 @@
+//! Compares the request/response codec with the previous `Value` based path.
+//!
+//! Run with `cargo bench -p rpc_provider --bench codec`. Every case reports the time and the
+//! number of heap allocations per operation.
+use rpc_provider::{codec, primitives::RpcParams, rpc_params};
+use serde_json::{value::RawValue, Value};
+use std::{
+	alloc::{GlobalAlloc, Layout, System},
+	hint::black_box,
+	sync::atomic::{AtomicUsize, Ordering},
+	time::Instant,
+};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+		ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+		System.alloc(layout)
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		System.dealloc(ptr, layout)
+	}
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const ITERATIONS: usize = 2_000;
+
+fn bench(name: &str, mut f: impl FnMut()) {
+	// Warm up once so lazily initialized state is not accounted.
+	f();
+
+	let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+	let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+	let start = Instant::now();
+	for _ in 0..ITERATIONS {
+		f();
+	}
+	let elapsed = start.elapsed();
+	let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+	let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes;
+
+	println!(
+		"{name:<40} {:>10.2} µs/op {:>10} allocs/op {:>12} bytes/op",
+		elapsed.as_secs_f64() * 1e6 / ITERATIONS as f64,
+		allocations / ITERATIONS,
+		bytes / ITERATIONS,
+	);
+}
+
+/// The request encoding used before `codec::encode_request`.
+#[allow(deprecated)]
+fn legacy_encode_request(method: &str, params: RpcParams) -> String {
+	serde_json::json!({
+		"method": method,
+		"params": params.to_json_value().unwrap(),
+		"jsonrpc": "2.0",
+		"id": "1",
+	})
+	.to_string()
+}
+
+/// A `state_getKeysPaged`-like answer with `count` storage keys.
+fn storage_keys_response(count: usize) -> String {
+	let keys =
+		(0..count)
+			.map(|i| {
+				format!("\"0x26aa394eea5630e07c48ae0c9558cef7b99d880ec681799c0cf30e8886371da9{i:064x}\"")
+			})
+			.collect::<Vec<_>>()
+			.join(",");
+	format!(r#"{{"jsonrpc":"2.0","id":1,"result":[{keys}]}}"#)
+}
+
+fn main() {
+	let prefix = "0x26aa394eea5630e07c48ae0c9558cef7b99d880ec681799c0cf30e8886371da9";
+	let method = "state_getKeysPaged";
+
+	bench("encode request (Value tree)", || {
+		black_box(legacy_encode_request(method, rpc_params!(prefix, 1000, prefix)));
+	});
+	bench("encode request (codec)", || {
+		black_box(codec::encode_request(1, method, rpc_params!(prefix, 1000, prefix)).unwrap());
+	});
+
+	let response = storage_keys_response(1000);
+
+	bench("decode 1000 keys (Value tree)", || {
+		let value: Value = serde_json::from_str(&response).unwrap();
+		let keys: Vec<String> = serde_json::from_value(value["result"].clone()).unwrap();
+		black_box(keys);
+	});
+	bench("decode 1000 keys (codec, owned)", || {
+		black_box(codec::decode_response::<Vec<String>>(response.as_bytes()).unwrap());
+	});
+	bench("decode 1000 keys (codec, borrowed)", || {
+		black_box(codec::decode_response::<Vec<&str>>(response.as_bytes()).unwrap());
+	});
+	bench("decode 1000 keys (codec, raw)", || {
+		black_box(codec::decode_response::<&RawValue>(response.as_bytes()).unwrap());
+	});
+}
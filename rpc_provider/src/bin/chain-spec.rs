@@ -0,0 +1,111 @@
+//! Maintenance of chain specs.
+//!
+//! ```text
+//! chain-spec refresh <spec> <url>
+//! chain-spec lint <spec>...
+//! chain-spec genesis-hash <spec> [v0|v1]
+//! ```
+//!
+//! `refresh` replaces the `lightSyncState` checkpoint of the spec at `<spec>` by a fresh one
+//! generated by the node at `<url>`, over WebSocket (`ws://`, `wss://`) or HTTP (`http://`,
+//! `https://`). The node must serve `sync_state_genSyncSpec`.
+//!
+//! `lint` prints what is wrong with each spec, and fails if any has an error.
+//!
+//! `genesis-hash` prints the hash of the genesis block of a raw spec, computed from its storage
+//! with the given state version, `v0` by default.
+use rpc_provider::{
+	chain_spec::refresh_chain_spec,
+	http::{HttpConfig, HttpProvider},
+	limits::Limits,
+	types::ProviderInterface,
+	ws::{WsConfig, WsProvider},
+	Error, Result,
+};
+use std::{path::Path, process::ExitCode};
+use types_support::chain_spec::{lint, ChainSpec, Genesis, Severity, StateVersion};
+
+const USAGE: &str = "usage: chain-spec refresh <spec> <url>
+       chain-spec lint <spec>...
+       chain-spec genesis-hash <spec> [v0|v1]";
+
+/// Specs with their raw genesis are several megabytes large.
+const MAX_SPEC_SIZE: usize = 64 * 1024 * 1024;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+	let args = std::env::args().skip(1).collect::<Vec<_>>();
+	let result = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+		["refresh", spec, url] => refresh(spec, url).await,
+		["lint", ref specs @ ..] if !specs.is_empty() => return lint_all(specs),
+		["genesis-hash", spec] | ["genesis-hash", spec, "v0"] =>
+			genesis_hash(spec, StateVersion::V0),
+		["genesis-hash", spec, "v1"] => genesis_hash(spec, StateVersion::V1),
+		_ => {
+			eprintln!("{USAGE}");
+			return ExitCode::FAILURE
+		},
+	};
+	match result {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(e) => {
+			eprintln!("error: {e:?}");
+			ExitCode::FAILURE
+		},
+	}
+}
+
+async fn refresh(spec: &str, url: &str) -> Result<()> {
+	let limits = Limits { max_message_size: MAX_SPEC_SIZE, ..Limits::default() };
+	if url.starts_with("ws://") || url.starts_with("wss://") {
+		let config = WsConfig { limits, ..WsConfig::default() };
+		let mut provider = WsProvider::new_with_config(url, config)?;
+		provider.connect().await?;
+		refresh_chain_spec(&provider, spec).await?;
+	} else if url.starts_with("http://") || url.starts_with("https://") {
+		let config = HttpConfig { limits, ..HttpConfig::default() };
+		let mut provider = HttpProvider::new_with_config(url, config)?;
+		provider.connect().await?;
+		refresh_chain_spec(&provider, spec).await?;
+	} else {
+		return Err(Error::InvalidUrl(url.into()))
+	}
+	println!("Refreshed the checkpoint of {spec}");
+	Ok(())
+}
+
+fn lint_all(specs: &[&str]) -> ExitCode {
+	let mut failed = false;
+	for path in specs {
+		let spec = match ChainSpec::load(Path::new(path)) {
+			Ok(spec) => spec,
+			Err(e) => {
+				eprintln!("{path}: error: {e:?}");
+				failed = true;
+				continue
+			},
+		};
+		for lint in lint(&spec) {
+			let severity = match lint.severity() {
+				Severity::Error => "error",
+				Severity::Warning => "warning",
+			};
+			println!("{path}: {severity}: {lint}");
+			failed |= lint.severity() == Severity::Error;
+		}
+	}
+	if failed {
+		ExitCode::FAILURE
+	} else {
+		ExitCode::SUCCESS
+	}
+}
+
+fn genesis_hash(spec: &str, version: StateVersion) -> Result<()> {
+	let Genesis::Raw(genesis) = ChainSpec::load(Path::new(spec))?.genesis else {
+		return Err(Error::InvalidChainSpec("the genesis is not raw".into()))
+	};
+	let hash = genesis.block_hash(version)?;
+	println!("0x{}", hash.iter().map(|byte| format!("{byte:02x}")).collect::<String>());
+	Ok(())
+}
@@ -0,0 +1,20 @@
+//! Chain specs fetched from a node, see [`types_support::chain_spec`].
+use crate::{rpc_params, Request, Result};
+use serde_json::value::RawValue;
+use std::path::Path;
+use types_support::chain_spec::{self, GEN_SYNC_SPEC};
+
+/// Fetches the chain spec of the node behind `provider`, with its genesis in raw form and a
+/// `lightSyncState` checkpoint at its finalized block.
+pub async fn fetch_sync_spec<R: Request>(provider: &R) -> Result<Box<RawValue>> {
+	provider.request_raw(GEN_SYNC_SPEC, rpc_params![true]).await
+}
+
+/// Refreshes the checkpoint of the spec at `path` from the node behind `provider`.
+///
+/// The spec is only replaced by one of the same chain, see [`chain_spec::check_refreshed`].
+pub async fn refresh_chain_spec<R: Request>(provider: &R, path: impl AsRef<Path>) -> Result<()> {
+	let fresh = fetch_sync_spec(provider).await?;
+	chain_spec::refresh_chain_spec(path.as_ref(), fresh.get())?;
+	Ok(())
+}
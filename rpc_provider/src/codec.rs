@@ -0,0 +1,210 @@
+//! JSON-RPC 2.0 encoding and decoding without intermediate `Value` trees.
+use crate::{error::RpcError, primitives::RpcParams, Error, Result};
+use serde::{Deserialize, Deserializer};
+use serde_json::value::RawValue;
+
+/// Number of bytes taken by the request envelope, without the method and the parameters.
+const REQUEST_ENVELOPE_BYTES: usize = 64;
+
+/// The parts of a JSON-RPC 2.0 response needed to hand its `result` over.
+///
+/// `R` is deserialized straight from the response bytes, so it may borrow from them
+/// (`&RawValue`, `&str`, ...).
+#[derive(Debug, Deserialize)]
+#[serde(bound(deserialize = "R: Deserialize<'de>"))]
+pub struct JsonRpcResponse<R> {
+	/// A `null` result is kept as `Some`, only a missing field maps to `None`.
+	#[serde(default, deserialize_with = "deserialize_some")]
+	pub result: Option<R>,
+	#[serde(default)]
+	pub error: Option<RpcError>,
+}
+
+impl<R> JsonRpcResponse<R> {
+	pub fn into_result(self) -> Result<R> {
+		match (self.result, self.error) {
+			(_, Some(error)) => Err(Error::JsonRpcError(error)),
+			(Some(result), None) => Ok(result),
+			(None, None) => Err(Error::JsonRpcError(RpcError::default())),
+		}
+	}
+}
+
+pub(crate) fn deserialize_some<'de, D, R>(
+	deserializer: D,
+) -> core::result::Result<Option<R>, D::Error>
+where
+	D: Deserializer<'de>,
+	R: Deserialize<'de>,
+{
+	R::deserialize(deserializer).map(Some)
+}
+
+#[derive(Deserialize)]
+struct ResponseId {
+	#[serde(default)]
+	id: Option<u64>,
+}
+
+/// The parts of a JSON-RPC 2.0 notification needed to route it to its subscription.
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "R: Deserialize<'de>"))]
+struct JsonRpcNotification<R> {
+	params: NotificationParams<R>,
+}
+
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "R: Deserialize<'de>"))]
+struct NotificationParams<R> {
+	result: R,
+}
+
+#[derive(Deserialize)]
+struct NotificationSubscription<'a> {
+	#[serde(borrow)]
+	params: SubscriptionParams<'a>,
+}
+
+#[derive(Deserialize)]
+struct SubscriptionParams<'a> {
+	#[serde(borrow)]
+	subscription: &'a RawValue,
+}
+
+/// Serializes a JSON-RPC 2.0 request straight into its final string.
+///
+/// The parameters were already serialized by [`RpcParams`], so they are copied as they are
+/// instead of being parsed back. Empty parameters are sent as an empty array.
+pub fn encode_request(id: u64, method: &str, params: RpcParams) -> Result<String> {
+	let params = params.build();
+	let params = params.as_deref().unwrap_or("[]");
+
+	let mut bytes = Vec::with_capacity(REQUEST_ENVELOPE_BYTES + method.len() + params.len());
+	bytes.extend_from_slice(br#"{"jsonrpc":"2.0","id":"#);
+	serde_json::to_writer(&mut bytes, &id)?;
+	bytes.extend_from_slice(br#","method":"#);
+	serde_json::to_writer(&mut bytes, method)?;
+	bytes.extend_from_slice(br#","params":"#);
+	bytes.extend_from_slice(params.as_bytes());
+	bytes.push(b'}');
+
+	Ok(String::from_utf8(bytes).expect("the literals above and `serde_json` output are UTF-8"))
+}
+
+/// Decodes a JSON-RPC 2.0 response and deserializes its `result` in a single pass over `bytes`.
+pub fn decode_response<'a, R: Deserialize<'a>>(bytes: &'a [u8]) -> Result<R> {
+	serde_json::from_slice::<JsonRpcResponse<R>>(bytes)?.into_result()
+}
+
+/// Reads the numeric `id` of a JSON-RPC 2.0 response, `None` for notifications.
+pub fn decode_response_id(bytes: &[u8]) -> Option<u64> {
+	serde_json::from_slice::<ResponseId>(bytes).ok()?.id
+}
+
+/// Reads the subscription id of a JSON-RPC 2.0 notification, as the JSON the node sent.
+pub fn decode_notification_subscription(bytes: &[u8]) -> Option<&str> {
+	let notification = serde_json::from_slice::<NotificationSubscription>(bytes).ok()?;
+	Some(notification.params.subscription.get())
+}
+
+/// Deserializes the `result` of a JSON-RPC 2.0 notification.
+pub fn decode_notification<'a, R: Deserialize<'a>>(bytes: &'a [u8]) -> Result<R> {
+	Ok(serde_json::from_slice::<JsonRpcNotification<R>>(bytes)?.params.result)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{no_params, rpc_params};
+	use serde_json::Value;
+
+	#[test]
+	fn encode_request_writes_a_valid_request() {
+		let request = encode_request(7, "chain_getBlockHash", rpc_params!(Some(0))).unwrap();
+		assert_eq!(
+			request,
+			r#"{"jsonrpc":"2.0","id":7,"method":"chain_getBlockHash","params":[0]}"#
+		);
+
+		let value: Value = serde_json::from_str(&request).unwrap();
+		assert_eq!(value["params"], serde_json::json!([0]));
+	}
+
+	#[test]
+	fn encode_request_sends_empty_params_as_empty_array() {
+		let request = encode_request(1, "system_health", no_params!()).unwrap();
+		assert_eq!(request, r#"{"jsonrpc":"2.0","id":1,"method":"system_health","params":[]}"#);
+	}
+
+	#[test]
+	fn to_json_req_uses_a_numeric_id_and_empty_params() {
+		// `to_json_req` used to send `"id":"1"` and `"params":[null]`.
+		let request = crate::to_json_req("system_health", no_params!()).unwrap();
+		let value: Value = serde_json::from_str(&request).unwrap();
+		assert_eq!(value["id"], serde_json::json!(1));
+		assert_eq!(value["params"], serde_json::json!([]));
+
+		let request = crate::to_json_req("state_getStorage", rpc_params!("0x00")).unwrap();
+		assert_eq!(
+			request,
+			r#"{"jsonrpc":"2.0","id":1,"method":"state_getStorage","params":["0x00"]}"#
+		);
+	}
+
+	#[test]
+	fn encode_request_escapes_the_method() {
+		let request = encode_request(1, "weird\"method", no_params!()).unwrap();
+		let value: Value = serde_json::from_str(&request).unwrap();
+		assert_eq!(value["method"], "weird\"method");
+	}
+
+	#[test]
+	fn decode_response_borrows_the_result() {
+		let response = br#"{"jsonrpc":"2.0","id":1,"result":{"specName":"polkadot"}}"#;
+		let result = decode_response::<&RawValue>(response).unwrap();
+		assert_eq!(result.get(), r#"{"specName":"polkadot"}"#);
+
+		let response = br#"{"jsonrpc":"2.0","id":1,"result":["0x01","0x02"]}"#;
+		let keys = decode_response::<Vec<&str>>(response).unwrap();
+		assert_eq!(keys, vec!["0x01", "0x02"]);
+	}
+
+	#[test]
+	fn decode_response_keeps_null_results() {
+		let response = br#"{"jsonrpc":"2.0","id":1,"result":null}"#;
+		assert_eq!(decode_response::<Option<u32>>(response).unwrap(), None);
+		assert_eq!(decode_response::<&RawValue>(response).unwrap().get(), "null");
+	}
+
+	#[test]
+	fn decode_response_id_reads_numeric_ids() {
+		assert_eq!(decode_response_id(br#"{"jsonrpc":"2.0","id":12,"result":[1,2]}"#), Some(12));
+		assert_eq!(decode_response_id(br#"{"jsonrpc":"2.0","method":"a","params":{}}"#), None);
+	}
+
+	#[test]
+	fn notifications_are_routed_by_their_subscription_id() {
+		let notification =
+			br#"{"jsonrpc":"2.0","method":"chain_newHead","params":{"subscription":"a1","result":{"number":"0x2a"}}}"#;
+		assert_eq!(decode_notification_subscription(notification), Some(r#""a1""#));
+		let result = decode_notification::<&RawValue>(notification).unwrap();
+		assert_eq!(result.get(), r#"{"number":"0x2a"}"#);
+
+		assert_eq!(
+			decode_notification_subscription(br#"{"jsonrpc":"2.0","id":1,"result":1}"#),
+			None
+		);
+	}
+
+	#[test]
+	fn decode_response_returns_rpc_error() {
+		let response = br#"{"jsonrpc":"2.0","id":1,"error":{"code":-32601,"message":"Not found"}}"#;
+		match decode_response::<&RawValue>(response) {
+			Err(Error::JsonRpcError(error)) => {
+				assert_eq!(error.code(), -32601);
+				assert_eq!(error.message(), "Not found");
+			},
+			other => panic!("Expected a JSON-RPC error, got {other:?}"),
+		}
+	}
+}
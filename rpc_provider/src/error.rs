@@ -1,13 +1,30 @@
+use crate::{limits::Limit, sync::SyncStatus};
 use core::fmt::Debug;
+use std::sync::Arc;
+use types_support::chain_spec::ChainSpecError;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RpcError {
 	code: i128,
 	message: String,
 }
 
+impl RpcError {
+	pub fn new(code: i128, message: String) -> Self {
+		Self { code, message }
+	}
+
+	pub fn code(&self) -> i128 {
+		self.code
+	}
+
+	pub fn message(&self) -> &str {
+		&self.message
+	}
+}
+
 impl Default for RpcError {
 	fn default() -> Self {
 		return Self { code: i128::default(), message: "No matched rpc error".to_string() };
@@ -24,18 +41,99 @@ pub enum Error {
 	InvalidChainSpec(String),
 	RecvError(String),
 	Io(String),
+	/// Invalid TLS configuration or failed TLS handshake.
+	Tls(String),
+	/// Invalid proxy configuration, or the proxy refused to connect to the node.
+	Proxy(String),
+	/// Call of a node-admin method refused by the provider, see
+	/// [`types_support::metadata::v15::polkadot_rpc::PolkadotRpcMethod::is_unsafe`].
+	UnsafeMethod(String),
+	/// Request to a light client chain added without JSON-RPC, see
+	/// [`crate::light_client::JsonRpc::Disabled`].
+	JsonRpcDisabled,
 	MaxConnectionAttemptsExceeded,
 	ConnectionClosed,
+	/// The node broke one of the [`crate::limits::Limits`] of the provider.
+	LimitExceeded(Limit),
+	/// The node was still syncing when [`crate::sync::wait_until_synced`] gave up, with its last
+	/// known status.
+	SyncTimeout(SyncStatus),
+	/// The consumer of a subscription fell behind and this many notifications were dropped.
+	SubscriptionLagged(u64),
+	/// Error of a request answered once for several callers, see
+	/// [`crate::coalesce::Coalescing`].
+	Shared(Arc<Error>),
 	Client(Box<dyn Debug + Send + Sync + 'static>),
 }
 
+impl Error {
+	/// Takes an error back out of an `Arc`, cloning it when possible.
+	pub(crate) fn from_shared(error: Arc<Error>) -> Self {
+		Arc::try_unwrap(error).unwrap_or_else(|error| match &*error {
+			Self::JsonRpcError(e) => Self::JsonRpcError(e.clone()),
+			_ => Self::Shared(error),
+		})
+	}
+}
+
 impl From<serde_json::error::Error> for Error {
 	fn from(error: serde_json::error::Error) -> Self {
 		Self::SerdeJson(error)
 	}
 }
 
+impl From<ChainSpecError> for Error {
+	fn from(error: ChainSpecError) -> Self {
+		match error {
+			ChainSpecError::Json(error) => Self::SerdeJson(error),
+			ChainSpecError::Io(error) => Self::Io(format!("{error:?}")),
+			error => Self::InvalidChainSpec(format!("{error:?}")),
+		}
+	}
+}
+
 use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "jsonrpsee-client")]
+impl From<jsonrpsee::core::client::Error> for Error {
+	fn from(error: jsonrpsee::core::client::Error) -> Self {
+		match error {
+			jsonrpsee::core::client::Error::Call(err) =>
+				Self::JsonRpcError(RpcError::new(err.code().into(), err.message().to_string())),
+			jsonrpsee::core::client::Error::MaxSlotsExceeded =>
+				Self::LimitExceeded(Limit::PendingResponses),
+			err => match exceeded_limit(&err) {
+				Some(limit) => Self::LimitExceeded(limit),
+				None => Self::Client(Box::new(err)),
+			},
+		}
+	}
+}
+
+/// Finds the limit behind a transport error, or behind the error which closed the connection.
+#[cfg(feature = "jsonrpsee-client")]
+fn exceeded_limit(error: &jsonrpsee::core::client::Error) -> Option<Limit> {
+	use jsonrpsee::core::{client::Error as ClientError, http_helpers::HttpError};
+	use jsonrpsee_http_client::transport::Error as HttpTransportError;
+
+	match error {
+		ClientError::RestartNeeded(reason) => exceeded_limit(reason),
+		ClientError::Transport(error) => {
+			if let Some(error) = error.downcast_ref::<crate::ws::ReceiveError>() {
+				return error.limit()
+			}
+			match error.downcast_ref::<HttpTransportError>() {
+				Some(HttpTransportError::Http(HttpError::TooLarge)) => Some(Limit::MessageSize),
+				_ => None,
+			}
+		},
+		// `serde_json` refuses to nest deeper than 128 levels on its own.
+		ClientError::ParseError(error) if error.to_string().starts_with("recursion limit") =>
+			Some(Limit::NestingDepth),
+		_ => None,
+	}
+}
+
 #[cfg(feature = "std")]
 #[allow(unused_imports)]
 pub use std_only::*;
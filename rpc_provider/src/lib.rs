@@ -2,7 +2,10 @@ use std::fmt::Debug;
 
 use primitives::RpcParams;
 use serde::de::DeserializeOwned;
+use serde_json::value::RawValue;
 
+pub mod chain_spec;
+pub mod codec;
 pub mod defaults;
 pub mod error;
 pub mod mac;
@@ -12,8 +15,24 @@ pub use error::{Error, Result};
 
 pub use providers::*;
 
+/// `Send` with the `send` feature, implemented by every type otherwise.
+#[cfg(feature = "send")]
+pub trait MaybeSend: Send {}
+#[cfg(feature = "send")]
+impl<T: Send> MaybeSend for T {}
+
+/// `Send` with the `send` feature, implemented by every type otherwise.
+#[cfg(not(feature = "send"))]
+pub trait MaybeSend {}
+#[cfg(not(feature = "send"))]
+impl<T> MaybeSend for T {}
+
 /// Trait to be implemented by the ws-client for sending rpc requests and extrinsic.
-#[maybe_async::maybe_async(?Send)]
+///
+/// With the `send` feature, enabled by default, the returned futures are `Send` so requests can
+/// be moved into `tokio::spawn`. Without it they are not, for single-threaded runtimes and wasm.
+#[cfg_attr(feature = "send", maybe_async::maybe_async)]
+#[cfg_attr(not(feature = "send"), maybe_async::maybe_async(?Send))]
 pub trait Request {
 	/// Sends a RPC request to the substrate node and returns the answer as deserializable struct (see serde::de::DeserializeOwned).
 	async fn request<R: DeserializeOwned + Debug>(
@@ -21,18 +40,24 @@ pub trait Request {
 		method: &str,
 		params: RpcParams,
 	) -> Result<R>;
-	/// Sends a RPC request to the substrate node and returns the answer as JSON string
-	async fn request_raw(&self, method: &str, params: RpcParams) -> Result<String>;
+	/// Sends a RPC request to the substrate node and returns the `result` of the answer as raw JSON.
+	///
+	/// Every provider follows the same contract: the returned value is the JSON of the `result`
+	/// field exactly as the node sent it, whatever its type (object, array, number, string or
+	/// `null`). The JSON-RPC envelope is never included, and an error answer is returned as
+	/// [`Error::JsonRpcError`].
+	async fn request_raw(&self, method: &str, params: RpcParams) -> Result<Box<RawValue>>;
 }
 
 /// Trait to be implemented by the ws-client for subscribing to the substrate node.
-#[maybe_async::maybe_async(?Send)]
+#[cfg_attr(feature = "send", maybe_async::maybe_async)]
+#[cfg_attr(not(feature = "send"), maybe_async::maybe_async(?Send))]
 pub trait Subscribe {
 	type Subscription<Notification>: HandleSubscription<Notification>
 	where
-		Notification: DeserializeOwned;
+		Notification: DeserializeOwned + MaybeSend;
 
-	async fn subscribe<Notification: DeserializeOwned>(
+	async fn subscribe<Notification: DeserializeOwned + MaybeSend>(
 		&self,
 		sub: &str,
 		params: RpcParams,
@@ -40,8 +65,9 @@ pub trait Subscribe {
 	) -> Result<Self::Subscription<Notification>>;
 }
 
-#[maybe_async::maybe_async(?Send)]
-pub trait HandleSubscription<Notification: DeserializeOwned> {
+#[cfg_attr(feature = "send", maybe_async::maybe_async)]
+#[cfg_attr(not(feature = "send"), maybe_async::maybe_async(?Send))]
+pub trait HandleSubscription<Notification: DeserializeOwned + MaybeSend> {
 	/// Returns the next notification from the stream.
 	async fn next(&mut self) -> Option<Result<Notification>>;
 
@@ -49,12 +75,7 @@ pub trait HandleSubscription<Notification: DeserializeOwned> {
 	async fn unsubscribe(self) -> Result<()>;
 }
 
+/// Encodes a JSON-RPC 2.0 request with the numeric id `1`, empty params as `[]`.
 pub fn to_json_req(method: &str, params: RpcParams) -> Result<String> {
-	Ok(serde_json::json!({
-		"method": method,
-		"params": params.to_json_value()?,
-		"jsonrpc": "2.0",
-		"id": "1",
-	})
-	.to_string())
+	codec::encode_request(1, method, params)
 }
@@ -1,7 +1,7 @@
 use serde::Serialize;
-use serde_json::{Result, Value};
+use serde_json::{value::RawValue, Result, Value};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RpcParams(ParamsBuilder);
 
 impl RpcParams {
@@ -27,6 +27,17 @@ impl RpcParams {
 		self.0.build()
 	}
 
+	/// Finish the building process and return the parameters as a JSON array, `[]` if none
+	/// were inserted.
+	///
+	/// The built string is checked to be valid JSON but no [`Value`] tree is allocated.
+	pub fn to_raw_value(self) -> Result<Box<RawValue>> {
+		RawValue::from_string(self.build().unwrap_or_else(|| "[]".into()))
+	}
+
+	/// Parse the built parameters back into a [`Value`].
+	#[deprecated(note = "parses the string that was just built, use `RpcParams::build` or \
+		        `RpcParams::to_raw_value` instead")]
 	pub fn to_json_value(self) -> Result<Value> {
 		let params = match self.build() {
 			Some(string) => serde_json::from_str(&string)?,
@@ -52,7 +63,7 @@ const PARAM_BYTES_CAPACITY: usize = 128;
 ///   1. Other serialization methods than `serde_json::to_writer` would internally
 ///      have an extra heap allocation for temporarily holding the value in memory.
 ///   2. `io::Write` is not implemented for `String` required for serialization.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct ParamsBuilder {
 	bytes: Vec<u8>,
 	start: char,
@@ -169,4 +180,13 @@ mod tests {
 		let built_params = params.build().unwrap();
 		assert_eq!(built_params, "[0,0]".to_string());
 	}
+
+	#[test]
+	fn to_raw_value_keeps_the_built_params() {
+		let mut params = RpcParams::new();
+		params.insert("0x00").unwrap();
+		params.insert(1.50).unwrap();
+		assert_eq!(params.to_raw_value().unwrap().get(), r#"["0x00",1.5]"#);
+		assert_eq!(RpcParams::new().to_raw_value().unwrap().get(), "[]");
+	}
 }
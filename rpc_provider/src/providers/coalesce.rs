@@ -0,0 +1,371 @@
+//! Coalescing of identical concurrent requests.
+use crate::{types::ProviderInterface, Error, MaybeSend, Request, Result, RpcParams, Subscribe};
+use serde::de::DeserializeOwned;
+use serde_json::value::RawValue;
+use std::{
+	collections::{HashMap, HashSet},
+	fmt::Debug,
+	sync::{Arc, Mutex},
+};
+use tokio::sync::oneshot;
+
+/// Result of a request, shared between the callers it was coalesced for.
+type SharedResult = core::result::Result<Box<RawValue>, Arc<Error>>;
+
+/// Method and built parameters of a request.
+type RequestKey = (String, Option<String>);
+
+/// Methods only reading the state of the node, coalesced by default.
+///
+/// Requests with side effects, such as `author_submitExtrinsic` or `author_rotateKeys`, must be
+/// sent once per caller and are never listed.
+pub const READ_ONLY_METHODS: &[&str] = &[
+	"chain_getBlock",
+	"chain_getBlockHash",
+	"chain_getFinalisedHead",
+	"chain_getFinalizedHead",
+	"chain_getHeader",
+	"rpc_methods",
+	"state_call",
+	"state_getKeys",
+	"state_getKeysPaged",
+	"state_getMetadata",
+	"state_getReadProof",
+	"state_getRuntimeVersion",
+	"state_getStorage",
+	"state_getStorageHash",
+	"state_getStorageSize",
+	"system_chain",
+	"system_chainType",
+	"system_health",
+	"system_name",
+	"system_properties",
+	"system_syncState",
+	"system_version",
+];
+
+/// Methods whose requests are coalesced by [`Coalescing`]. Defaults to [`READ_ONLY_METHODS`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoalescedMethods {
+	/// Every method, including those with side effects. Only for providers sending reads.
+	All,
+	/// Only the listed methods.
+	Only(HashSet<String>),
+	/// Every method but the listed ones.
+	AllExcept(HashSet<String>),
+}
+
+impl Default for CoalescedMethods {
+	fn default() -> Self {
+		Self::Only(READ_ONLY_METHODS.iter().map(|method| method.to_string()).collect())
+	}
+}
+
+impl CoalescedMethods {
+	pub fn contains(&self, method: &str) -> bool {
+		match self {
+			Self::All => true,
+			Self::Only(methods) => methods.contains(method),
+			Self::AllExcept(methods) => !methods.contains(method),
+		}
+	}
+}
+
+/// Provider wrapper sending identical concurrent requests, same method and parameters, only once.
+///
+/// The first caller sends the request, the callers asking for the same thing while it is in
+/// flight wait for its answer. Errors that can not be cloned are handed over to every caller as
+/// [`Error::Shared`]. Nothing is cached: once answered, the next identical request is sent again.
+///
+/// ```ignore
+/// let provider = Coalescing::new(WsProvider::new("ws://127.0.0.1:9944")?);
+/// // Sent once.
+/// let (a, b) = tokio::join!(
+///     provider.request::<Value>("state_getRuntimeVersion", rpc_params![]),
+///     provider.request::<Value>("state_getRuntimeVersion", rpc_params![]),
+/// );
+/// ```
+pub struct Coalescing<P> {
+	inner: P,
+	methods: CoalescedMethods,
+	in_flight: Arc<Mutex<HashMap<RequestKey, Vec<oneshot::Sender<SharedResult>>>>>,
+}
+
+impl<P> Coalescing<P> {
+	/// Coalesces the requests of the [`READ_ONLY_METHODS`].
+	pub fn new(inner: P) -> Self {
+		Self::with_methods(inner, CoalescedMethods::default())
+	}
+
+	pub fn with_methods(inner: P, methods: CoalescedMethods) -> Self {
+		Self { inner, methods, in_flight: Default::default() }
+	}
+
+	pub fn inner(&self) -> &P {
+		&self.inner
+	}
+
+	pub fn inner_mut(&mut self) -> &mut P {
+		&mut self.inner
+	}
+
+	pub fn into_inner(self) -> P {
+		self.inner
+	}
+}
+
+impl<P: Clone> Clone for Coalescing<P> {
+	/// The clone shares the requests in flight.
+	fn clone(&self) -> Self {
+		Self {
+			inner: self.inner.clone(),
+			methods: self.methods.clone(),
+			in_flight: self.in_flight.clone(),
+		}
+	}
+}
+
+impl<P: Request + Sync> Coalescing<P> {
+	async fn coalesce(&self, method: &str, params: RpcParams) -> Result<Box<RawValue>> {
+		let key = (method.to_string(), params.clone().build());
+		loop {
+			let waiting = {
+				let mut in_flight = self.in_flight.lock().unwrap();
+				match in_flight.get_mut(&key) {
+					Some(followers) => {
+						let (send_back, response) = oneshot::channel();
+						followers.push(send_back);
+						Some(response)
+					},
+					None => {
+						in_flight.insert(key.clone(), Vec::new());
+						None
+					},
+				}
+			};
+			match waiting {
+				Some(response) => match response.await {
+					Ok(result) => return result.map_err(Error::from_shared),
+					// The caller sending the request was cancelled, try again.
+					Err(_) => continue,
+				},
+				None => break,
+			}
+		}
+
+		let mut leader = Leader { in_flight: &self.in_flight, key: Some(key) };
+		let result = self.inner.request_raw(method, params).await;
+		let followers = leader.finish();
+		if followers.is_empty() {
+			return result;
+		}
+
+		let result = result.map_err(Arc::new);
+		for send_back in followers {
+			let _ = send_back.send(result.clone());
+		}
+		result.map_err(Error::from_shared)
+	}
+}
+
+/// The caller sending a coalesced request. If it is dropped before the answer, the waiting
+/// callers are released to send the request themselves.
+struct Leader<'a> {
+	in_flight: &'a Mutex<HashMap<RequestKey, Vec<oneshot::Sender<SharedResult>>>>,
+	key: Option<RequestKey>,
+}
+
+impl Leader<'_> {
+	/// Returns the callers waiting for the answer.
+	fn finish(&mut self) -> Vec<oneshot::Sender<SharedResult>> {
+		let key = self.key.take().expect("finish is called once");
+		self.in_flight.lock().unwrap().remove(&key).unwrap_or_default()
+	}
+}
+
+impl Drop for Leader<'_> {
+	fn drop(&mut self) {
+		if let Some(key) = self.key.take() {
+			self.in_flight.lock().unwrap().remove(&key);
+		}
+	}
+}
+
+#[cfg_attr(feature = "send", maybe_async::async_impl)]
+#[cfg_attr(not(feature = "send"), maybe_async::async_impl(?Send))]
+impl<P: Request + Sync> Request for Coalescing<P> {
+	async fn request_raw(&self, method: &str, params: RpcParams) -> Result<Box<RawValue>> {
+		if !self.methods.contains(method) {
+			return self.inner.request_raw(method, params).await;
+		}
+		self.coalesce(method, params).await
+	}
+
+	async fn request<R: DeserializeOwned + Debug>(
+		&self,
+		method: &str,
+		params: RpcParams,
+	) -> Result<R> {
+		if !self.methods.contains(method) {
+			return self.inner.request(method, params).await;
+		}
+		let result = self.coalesce(method, params).await?;
+		Ok(serde_json::from_str(result.get())?)
+	}
+}
+
+#[cfg_attr(feature = "send", maybe_async::async_impl)]
+#[cfg_attr(not(feature = "send"), maybe_async::async_impl(?Send))]
+impl<P: ProviderInterface + Send + Sync> ProviderInterface for Coalescing<P> {
+	async fn connect(&mut self) -> Result<()> {
+		self.inner.connect().await
+	}
+
+	async fn disconnect(&mut self) -> Result<()> {
+		self.inner.disconnect().await
+	}
+}
+
+#[cfg_attr(feature = "send", maybe_async::async_impl)]
+#[cfg_attr(not(feature = "send"), maybe_async::async_impl(?Send))]
+impl<P: Subscribe + Sync> Subscribe for Coalescing<P> {
+	type Subscription<Notification> = P::Subscription<Notification> where Notification: DeserializeOwned + MaybeSend;
+
+	async fn subscribe<Notification: DeserializeOwned + MaybeSend>(
+		&self,
+		sub: &str,
+		params: RpcParams,
+		unsub: &str,
+	) -> Result<Self::Subscription<Notification>> {
+		self.inner.subscribe(sub, params, unsub).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{error::RpcError, no_params, rpc_params};
+	use std::{
+		sync::atomic::{AtomicUsize, Ordering},
+		time::Duration,
+	};
+
+	/// Answers every request with its parameters after a delay, and counts the requests.
+	#[derive(Default)]
+	struct SlowProvider {
+		requests: AtomicUsize,
+	}
+
+	#[cfg_attr(feature = "send", maybe_async::async_impl)]
+	#[cfg_attr(not(feature = "send"), maybe_async::async_impl(?Send))]
+	impl Request for SlowProvider {
+		async fn request_raw(&self, method: &str, params: RpcParams) -> Result<Box<RawValue>> {
+			self.requests.fetch_add(1, Ordering::SeqCst);
+			tokio::time::sleep(Duration::from_millis(50)).await;
+			if method == "mock_error" {
+				return Err(Error::JsonRpcError(RpcError::new(-32000, "mock failure".into())));
+			}
+			Ok(RawValue::from_string(params.build().unwrap_or_else(|| "null".into()))?)
+		}
+
+		async fn request<R: DeserializeOwned>(&self, method: &str, params: RpcParams) -> Result<R> {
+			let result = self.request_raw(method, params).await?;
+			Ok(serde_json::from_str(result.get())?)
+		}
+	}
+
+	fn coalescing_all() -> Coalescing<SlowProvider> {
+		Coalescing::with_methods(SlowProvider::default(), CoalescedMethods::All)
+	}
+
+	#[tokio::test]
+	async fn identical_concurrent_requests_are_sent_once() {
+		let provider = coalescing_all();
+
+		let results = futures::future::join_all(
+			(0..10).map(|_| provider.request::<Vec<u32>>("mock_echo", rpc_params![1])),
+		)
+		.await;
+
+		assert_eq!(provider.inner().requests.load(Ordering::SeqCst), 1);
+		for result in results {
+			assert_eq!(result.unwrap(), vec![1]);
+		}
+	}
+
+	#[tokio::test]
+	async fn requests_with_different_params_are_sent_separately() {
+		let provider = coalescing_all();
+
+		let (one, two) = tokio::join!(
+			provider.request::<Vec<u32>>("mock_echo", rpc_params![1]),
+			provider.request::<Vec<u32>>("mock_echo", rpc_params![2]),
+		);
+
+		assert_eq!(provider.inner().requests.load(Ordering::SeqCst), 2);
+		assert_eq!((one.unwrap(), two.unwrap()), (vec![1], vec![2]));
+	}
+
+	#[tokio::test]
+	async fn only_configured_methods_are_coalesced() {
+		let methods = CoalescedMethods::AllExcept(HashSet::from(["mock_echo".to_string()]));
+		let provider = Coalescing::with_methods(SlowProvider::default(), methods);
+
+		let _ = tokio::join!(
+			provider.request::<Vec<u32>>("mock_echo", rpc_params![1]),
+			provider.request::<Vec<u32>>("mock_echo", rpc_params![1]),
+		);
+
+		assert_eq!(provider.inner().requests.load(Ordering::SeqCst), 2);
+	}
+
+	#[tokio::test]
+	async fn errors_are_shared() {
+		let provider = coalescing_all();
+
+		let (one, two) = tokio::join!(
+			provider.request_raw("mock_error", no_params!()),
+			provider.request_raw("mock_error", no_params!()),
+		);
+
+		assert_eq!(provider.inner().requests.load(Ordering::SeqCst), 1);
+		for result in [one, two] {
+			assert!(matches!(result, Err(Error::JsonRpcError(e)) if e.code() == -32000));
+		}
+	}
+
+	#[tokio::test]
+	async fn waiting_callers_take_over_a_cancelled_request() {
+		let provider = coalescing_all();
+
+		let cancelled = tokio::time::timeout(
+			Duration::from_millis(10),
+			provider.request_raw("mock_echo", rpc_params![1]),
+		);
+		let (cancelled, result) =
+			tokio::join!(cancelled, provider.request::<Vec<u32>>("mock_echo", rpc_params![1]));
+
+		assert!(cancelled.is_err());
+		assert_eq!(result.unwrap(), vec![1]);
+		assert_eq!(provider.inner().requests.load(Ordering::SeqCst), 2);
+	}
+
+	#[tokio::test]
+	async fn only_read_only_methods_are_coalesced_by_default() {
+		let provider = Coalescing::new(SlowProvider::default());
+
+		let _ = tokio::join!(
+			provider.request_raw("state_getRuntimeVersion", no_params!()),
+			provider.request_raw("state_getRuntimeVersion", no_params!()),
+		);
+		assert_eq!(provider.inner().requests.load(Ordering::SeqCst), 1);
+
+		let _ = tokio::join!(
+			provider.request_raw("author_rotateKeys", no_params!()),
+			provider.request_raw("author_rotateKeys", no_params!()),
+			provider.request_raw("author_submitExtrinsic", rpc_params!["0x00"]),
+			provider.request_raw("author_submitExtrinsic", rpc_params!["0x00"]),
+		);
+		assert_eq!(provider.inner().requests.load(Ordering::SeqCst), 5);
+	}
+}
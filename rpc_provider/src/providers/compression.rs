@@ -0,0 +1,142 @@
+//! Compressed responses of the WebSocket and HTTP providers.
+use std::{
+	io::Read,
+	sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Content encodings accepted by the [`crate::http::HttpProvider`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContentEncoding {
+	Gzip,
+	/// zlib format, as meant by the HTTP specification. Raw deflate streams sent by some servers
+	/// are accepted as well.
+	Deflate,
+	Brotli,
+}
+
+impl ContentEncoding {
+	/// Name of the encoding in the `Accept-Encoding` and `Content-Encoding` headers.
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Gzip => "gzip",
+			Self::Deflate => "deflate",
+			Self::Brotli => "br",
+		}
+	}
+
+	pub(crate) fn from_name(name: &str) -> Option<Self> {
+		match name.trim().to_ascii_lowercase().as_str() {
+			"gzip" | "x-gzip" => Some(Self::Gzip),
+			"deflate" => Some(Self::Deflate),
+			"br" => Some(Self::Brotli),
+			_ => None,
+		}
+	}
+
+	/// Decodes `data`, failing if the result is larger than `limit`.
+	pub(crate) fn decode(&self, data: &[u8], limit: usize) -> Result<Vec<u8>, DecodeError> {
+		match self {
+			Self::Gzip => read_limited(flate2::read::GzDecoder::new(data), limit),
+			Self::Deflate => match read_limited(flate2::read::ZlibDecoder::new(data), limit) {
+				Err(DecodeError::Invalid) =>
+					read_limited(flate2::read::DeflateDecoder::new(data), limit),
+				result => result,
+			},
+			Self::Brotli => read_limited(brotli_decompressor::Decompressor::new(data, 4096), limit),
+		}
+	}
+}
+
+#[derive(Debug)]
+pub(crate) enum DecodeError {
+	TooLarge,
+	Invalid,
+}
+
+fn read_limited(reader: impl Read, limit: usize) -> Result<Vec<u8>, DecodeError> {
+	let mut decoded = Vec::new();
+	reader
+		.take(limit as u64 + 1)
+		.read_to_end(&mut decoded)
+		.map_err(|_| DecodeError::Invalid)?;
+	if decoded.len() > limit {
+		return Err(DecodeError::TooLarge)
+	}
+	Ok(decoded)
+}
+
+/// Bytes received by a provider with compression enabled, before and after decompression.
+///
+/// Received bytes are the HTTP response bodies, or everything read from the WebSocket
+/// connection after the handshake, frame headers included.
+#[derive(Debug, Default)]
+pub struct CompressionStats {
+	received: AtomicU64,
+	decoded: AtomicU64,
+}
+
+impl CompressionStats {
+	pub fn received_bytes(&self) -> u64 {
+		self.received.load(Ordering::Relaxed)
+	}
+
+	pub fn decoded_bytes(&self) -> u64 {
+		self.decoded.load(Ordering::Relaxed)
+	}
+
+	/// Decoded bytes per received byte, `None` until something is received.
+	pub fn ratio(&self) -> Option<f64> {
+		match self.received_bytes() {
+			0 => None,
+			received => Some(self.decoded_bytes() as f64 / received as f64),
+		}
+	}
+
+	pub(crate) fn record(&self, received: usize, decoded: usize) {
+		self.received.fetch_add(received as u64, Ordering::Relaxed);
+		self.decoded.fetch_add(decoded as u64, Ordering::Relaxed);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::Write;
+
+	const PAYLOAD: &[u8] = br#"{"jsonrpc":"2.0","id":1,"result":"0x00000000000000000000000000"}"#;
+
+	fn zlib(data: &[u8]) -> Vec<u8> {
+		let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), Default::default());
+		encoder.write_all(data).unwrap();
+		encoder.finish().unwrap()
+	}
+
+	#[test]
+	fn deflate_accepts_zlib_and_raw_streams() {
+		assert_eq!(ContentEncoding::Deflate.decode(&zlib(PAYLOAD), 1024).unwrap(), PAYLOAD);
+
+		let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), Default::default());
+		encoder.write_all(PAYLOAD).unwrap();
+		let raw = encoder.finish().unwrap();
+		assert_eq!(ContentEncoding::Deflate.decode(&raw, 1024).unwrap(), PAYLOAD);
+	}
+
+	#[test]
+	fn decoded_size_is_limited() {
+		let bomb = zlib(&vec![0; 1024 * 1024]);
+		assert!(ContentEncoding::Deflate.decode(&bomb, 1024 * 1024).is_ok());
+		let error = ContentEncoding::Deflate.decode(&bomb, 1024 * 1024 - 1).unwrap_err();
+		assert!(matches!(error, DecodeError::TooLarge));
+		let error = ContentEncoding::Gzip.decode(&bomb, 1024 * 1024).unwrap_err();
+		assert!(matches!(error, DecodeError::Invalid));
+	}
+
+	#[test]
+	fn ratio_of_received_bytes() {
+		let stats = CompressionStats::default();
+		assert_eq!(stats.ratio(), None);
+		stats.record(100, 400);
+		stats.record(100, 100);
+		assert_eq!(stats.ratio(), Some(2.5));
+	}
+}
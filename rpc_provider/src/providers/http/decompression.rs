@@ -0,0 +1,257 @@
+//! Compressed responses of the [`super::HttpProvider`].
+use crate::{
+	compression::{CompressionStats, ContentEncoding, DecodeError},
+	stats::ProviderStats,
+};
+use futures::future::BoxFuture;
+use hyper::{
+	body::HttpBody,
+	header::{HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH},
+	Body, Request, Response,
+};
+use jsonrpsee::core::http_helpers::HttpError;
+use jsonrpsee_http_client::transport::Error as TransportError;
+use std::{
+	sync::Arc,
+	task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+/// Requests compressed responses and decodes them, when compression is enabled.
+///
+/// Also counts the bytes of the requests and of the decoded responses in the [`ProviderStats`] of
+/// the provider.
+#[derive(Clone, Debug)]
+pub struct Decompression<S> {
+	inner: S,
+	accept: Option<Accept>,
+	counted: Option<Counted>,
+}
+
+#[derive(Clone, Debug)]
+struct Accept {
+	header: HeaderValue,
+	/// Largest decoded body.
+	max_size: usize,
+	stats: Arc<CompressionStats>,
+}
+
+#[derive(Clone, Debug)]
+struct Counted {
+	/// Largest body read to find out its size.
+	max_size: usize,
+	stats: Arc<ProviderStats>,
+}
+
+impl<S> Service<Request<Body>> for Decompression<S>
+where
+	S: Service<Request<Body>, Response = Response<Body>, Error = TransportError>,
+	S::Future: Send + 'static,
+{
+	type Response = Response<Body>;
+	type Error = TransportError;
+	type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, mut request: Request<Body>) -> Self::Future {
+		let (accept, counted) = (self.accept.clone(), self.counted.clone());
+		if accept.is_none() && counted.is_none() {
+			return Box::pin(self.inner.call(request))
+		}
+		if let Some(accept) = &accept {
+			request.headers_mut().insert(ACCEPT_ENCODING, accept.header.clone());
+		}
+		if let (Some(counted), Some(len)) = (&counted, request.body().size_hint().exact()) {
+			counted.stats.record_sent(len as usize);
+		}
+		let response = self.inner.call(request);
+		Box::pin(async move {
+			let mut response = response.await?;
+			if let Some(accept) = accept {
+				response = decode(response, accept.max_size, &accept.stats).await?;
+			}
+			match counted {
+				Some(counted) => count(response, &counted).await,
+				None => Ok(response),
+			}
+		})
+	}
+}
+
+/// Records the size of the body of `response`, reading it when its length is not known upfront.
+async fn count(
+	response: Response<Body>,
+	counted: &Counted,
+) -> Result<Response<Body>, TransportError> {
+	if let Some(len) = response.body().size_hint().exact() {
+		counted.stats.record_received(len as usize);
+		return Ok(response)
+	}
+	let (parts, body) = response.into_parts();
+	let bytes = read_limited(body, counted.max_size).await?;
+	counted.stats.record_received(bytes.len());
+	Ok(Response::from_parts(parts, Body::from(bytes)))
+}
+
+/// Reads `body`, failing as soon as it is larger than `max_size`.
+async fn read_limited(mut body: Body, max_size: usize) -> Result<Vec<u8>, TransportError> {
+	if body.size_hint().lower() > max_size as u64 {
+		return Err(TransportError::Http(HttpError::TooLarge))
+	}
+	let mut bytes = Vec::new();
+	while let Some(chunk) = body.data().await {
+		let chunk = chunk.map_err(|e| TransportError::Http(e.into()))?;
+		if bytes.len() + chunk.len() > max_size {
+			return Err(TransportError::Http(HttpError::TooLarge))
+		}
+		bytes.extend_from_slice(&chunk);
+	}
+	Ok(bytes)
+}
+
+/// Decodes the body of `response` according to its `Content-Encoding`.
+async fn decode(
+	response: Response<Body>,
+	max_size: usize,
+	stats: &CompressionStats,
+) -> Result<Response<Body>, TransportError> {
+	let (mut parts, body) = response.into_parts();
+	let encodings = match parts.headers.remove(CONTENT_ENCODING) {
+		Some(value) => value
+			.to_str()
+			.unwrap_or_default()
+			.split(',')
+			.filter(|name| !name.trim().eq_ignore_ascii_case("identity"))
+			.map(|name| {
+				ContentEncoding::from_name(name).ok_or(TransportError::Http(HttpError::Malformed))
+			})
+			.collect::<Result<Vec<_>, _>>()?,
+		None => Vec::new(),
+	};
+
+	// Compressed or not, a body larger than `max_size` could not be decoded within it.
+	let received = read_limited(body, max_size).await?;
+	let received_len = received.len();
+	let mut decoded = received;
+	// Encodings are listed in the order they were applied, each stops decoding past `max_size`.
+	for encoding in encodings.iter().rev() {
+		decoded = encoding.decode(&decoded, max_size).map_err(|e| match e {
+			DecodeError::TooLarge => TransportError::Http(HttpError::TooLarge),
+			DecodeError::Invalid => TransportError::Http(HttpError::Malformed),
+		})?;
+	}
+	stats.record(received_len, decoded.len());
+
+	parts.headers.insert(CONTENT_LENGTH, decoded.len().into());
+	Ok(Response::from_parts(parts, Body::from(decoded)))
+}
+
+/// Builds the [`Decompression`] service, see [`super::HttpConfig::compression`].
+#[derive(Clone, Debug, Default)]
+pub struct DecompressionLayer {
+	accept: Option<Accept>,
+	counted: Option<Counted>,
+}
+
+impl DecompressionLayer {
+	/// Accepts `encodings`, by order of preference, decoding bodies of up to `max_size` bytes.
+	/// Compression is disabled when `encodings` is empty, the bytes are counted in `stats`
+	/// either way. Repeated encodings are only accepted once.
+	pub(crate) fn new(
+		encodings: &[ContentEncoding],
+		max_size: usize,
+		compression_stats: Arc<CompressionStats>,
+		stats: Arc<ProviderStats>,
+	) -> Self {
+		let counted = Some(Counted { max_size, stats });
+		if encodings.is_empty() {
+			return Self { accept: None, counted }
+		}
+		// Without repetitions there are fewer encodings than quality values above `identity`'s.
+		let mut accepted = Vec::new();
+		for encoding in encodings {
+			if !accepted.contains(encoding) {
+				accepted.push(*encoding);
+			}
+		}
+		let header = accepted
+			.iter()
+			.enumerate()
+			// Lower the quality value of each encoding by preference, `identity` comes last.
+			.map(|(i, encoding)| format!("{};q={:.1}", encoding.name(), 1.0 - i as f32 * 0.1))
+			.chain(["identity;q=0.1".to_string()])
+			.collect::<Vec<_>>()
+			.join(", ");
+		let header =
+			HeaderValue::from_str(&header).expect("Encoding names are valid header values");
+		Self { accept: Some(Accept { header, max_size, stats: compression_stats }), counted }
+	}
+}
+
+impl<S> Layer<S> for DecompressionLayer {
+	type Service = Decompression<S>;
+
+	fn layer(&self, inner: S) -> Self::Service {
+		Decompression { inner, accept: self.accept.clone(), counted: self.counted.clone() }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn accept_encoding_follows_preferences() {
+		let stats = Arc::new(CompressionStats::default());
+		let encodings = [ContentEncoding::Brotli, ContentEncoding::Gzip, ContentEncoding::Deflate];
+		let layer = DecompressionLayer::new(&encodings, 1024, stats.clone(), Default::default());
+		assert_eq!(
+			layer.accept.unwrap().header,
+			"br;q=1.0, gzip;q=0.9, deflate;q=0.8, identity;q=0.1"
+		);
+		assert!(DecompressionLayer::new(&[], 1024, stats, Default::default()).accept.is_none());
+	}
+
+	#[test]
+	fn repeated_encodings_are_accepted_once() {
+		let encodings = [[ContentEncoding::Gzip, ContentEncoding::Brotli]; 8].concat();
+		let layer =
+			DecompressionLayer::new(&encodings, 1024, Default::default(), Default::default());
+		assert_eq!(layer.accept.unwrap().header, "gzip;q=1.0, br;q=0.9, identity;q=0.1");
+	}
+
+	fn gzip_response(data: &[u8]) -> Response<Body> {
+		let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+		std::io::Write::write_all(&mut encoder, data).unwrap();
+		let body = Body::from(encoder.finish().unwrap());
+		Response::builder().header(CONTENT_ENCODING, "gzip").body(body).unwrap()
+	}
+
+	#[tokio::test]
+	async fn decoding_stops_past_the_max_size() {
+		let stats = CompressionStats::default();
+		// A few kilobytes, decoded to 16 MiB.
+		let bomb = gzip_response(&vec![0; 16 * 1024 * 1024]);
+		let error = decode(bomb, 1024 * 1024, &stats).await.unwrap_err();
+		assert!(matches!(error, TransportError::Http(HttpError::TooLarge)), "{error:?}");
+
+		let response = decode(gzip_response(&[1; 1024]), 1024, &stats).await.unwrap();
+		let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+		assert_eq!(body, vec![1; 1024]);
+	}
+
+	#[tokio::test]
+	async fn received_body_is_read_up_to_the_max_size() {
+		let (mut sender, body) = Body::channel();
+		tokio::spawn(async move {
+			// Never ends on its own.
+			while sender.send_data(vec![0; 1024].into()).await.is_ok() {}
+		});
+		let response = Response::builder().header(CONTENT_ENCODING, "gzip").body(body).unwrap();
+		let error = decode(response, 64 * 1024, &Default::default()).await.unwrap_err();
+		assert!(matches!(error, TransportError::Http(HttpError::TooLarge)), "{error:?}");
+	}
+}
@@ -1,26 +1,91 @@
 use crate::{
+	compression::{CompressionStats, ContentEncoding},
 	defaults::WS_URL,
-	types::{ProviderInterface, RpcParamsWrapper},
+	limits::Limits,
+	proxy::Proxy,
+	stats::ProviderStats,
+	trace::{TraceConfig, Tracer},
+	types::{check_method, ProviderInterface, RpcParamsWrapper},
 	Error, Request, Result, RpcParams,
 };
 use jsonrpsee::core::client::ClientT;
-use jsonrpsee_http_client::{HttpClient, HttpClientBuilder};
+use jsonrpsee_http_client::HttpClientBuilder;
 use serde::de::DeserializeOwned;
+use serde_json::value::RawValue;
 use std::sync::Arc;
 use url::Url;
 
+mod decompression;
+mod transport;
+
+pub use decompression::{Decompression, DecompressionLayer};
+pub use transport::{CustomTransport, HttpTransport, HttpTransportLayer};
+
+/// `jsonrpsee` HTTP client used by the [`HttpProvider`].
+pub type HttpClient = jsonrpsee_http_client::HttpClient<Decompression<HttpTransport>>;
+
+/// Options of an [`HttpProvider`].
+#[derive(Debug, Clone, Default)]
+pub struct HttpConfig {
+	/// TLS options for `https` urls. `None` uses the built-in root certificates.
+	#[cfg(feature = "__tls")]
+	pub tls: Option<crate::tls::TlsConfig>,
+	/// Proxy to connect through, none by default.
+	pub proxy: Proxy,
+	/// Compressed response encodings to accept, by order of preference. Empty by default, which
+	/// disables compression.
+	pub compression: Vec<ContentEncoding>,
+	/// Send node-admin methods such as `author_insertKey`, refused with
+	/// [`Error::UnsafeMethod`] otherwise. Disabled by default.
+	pub allow_unsafe: bool,
+	/// Bounds on the responses of the node, and on the requests waiting for it.
+	pub limits: Limits,
+	/// Logging of the params and responses, see [`crate::trace`].
+	pub trace: TraceConfig,
+}
+
+impl HttpConfig {
+	/// Returns the transport layer for `url`, and the url to hand over to `jsonrpsee`.
+	fn transport(&self, url: Url) -> Result<(Url, HttpTransportLayer)> {
+		let proxy = self.proxy.for_url(&url)?;
+		if url.scheme() == "https" {
+			#[cfg(feature = "__tls")]
+			if self.tls.is_some() || proxy.is_some() {
+				// The custom transport takes care of TLS, see `HttpTransport`.
+				let mut url = url;
+				let _ = url.set_scheme("http");
+				let tls = self.tls.clone().unwrap_or_default();
+				return Ok((url, HttpTransportLayer::tls(&tls, proxy)?));
+			}
+			#[cfg(not(feature = "__tls"))]
+			if proxy.is_some() {
+				return Err(Error::Tls(
+					"`https` through a proxy needs the `native-tls` or `rustls` feature".into(),
+				));
+			}
+		} else if let Some(proxy) = proxy {
+			return Ok((url, HttpTransportLayer::proxy(proxy)?));
+		}
+		Ok((url, HttpTransportLayer::default()))
+	}
+}
+
 #[derive(Clone)]
 pub struct HttpProvider {
 	inner: Option<Arc<HttpClient>>,
 	_url: Option<Url>,
+	config: HttpConfig,
+	stats: Arc<CompressionStats>,
+	tracer: Tracer,
 }
 
+#[cfg_attr(feature = "send", maybe_async::async_impl)]
+#[cfg_attr(not(feature = "send"), maybe_async::async_impl(?Send))]
 impl ProviderInterface for HttpProvider {
 	async fn connect(&mut self) -> Result<()> {
-		let url = self._url.clone().unwrap();
-		let client = HttpClientBuilder::default().build(url).unwrap();
-		self.inner = Some(Arc::new(client));
-		return Ok(());
+		let tracer = self.tracer.clone();
+		let reconnect = self.inner.is_some();
+		tracer.connect(reconnect).run(async { self.build_client() }).await
 	}
 
 	async fn disconnect(&mut self) -> Result<()> {
@@ -29,6 +94,28 @@ impl ProviderInterface for HttpProvider {
 }
 
 impl HttpProvider {
+	/// Builds the client, no connection is opened until the first request.
+	fn build_client(&mut self) -> Result<()> {
+		let (url, layer) = self.config.transport(self._url.clone().unwrap())?;
+		let limits = self.config.limits;
+		let decompression = DecompressionLayer::new(
+			&self.config.compression,
+			limits.max_message_size,
+			self.stats.clone(),
+			self.tracer.stats().clone(),
+		);
+		let middleware = tower::ServiceBuilder::new().layer(decompression).layer(layer);
+		let builder = HttpClientBuilder::default()
+			.set_http_middleware(middleware)
+			.max_response_size(limits.max_message_size.try_into().unwrap_or(u32::MAX))
+			.max_concurrent_requests(limits.max_pending_responses);
+		#[cfg(all(feature = "rustls", not(feature = "native-tls")))]
+		let builder = builder.use_webpki_rustls();
+		let client = builder.build(url).map_err(|e| Error::Client(Box::new(e)))?;
+		self.inner = Some(Arc::new(client));
+		Ok(())
+	}
+
 	/// Create a new client to a local Substrate node with default port.
 	pub async fn with_default_url() -> Result<Self> {
 		let mut new_client = Self::new(WS_URL).unwrap();
@@ -41,8 +128,24 @@ impl HttpProvider {
 	}
 
 	pub fn new(url: &str) -> Result<Self> {
+		Self::new_with_config(url, HttpConfig::default())
+	}
+
+	/// Create a new client with the given url string and options.
+	pub fn new_with_config(url: &str, config: HttpConfig) -> Result<Self> {
 		let parsed_url: Url = url.parse().map_err(|e| Error::Client(Box::new(e)))?;
-		Ok(Self { inner: None, _url: Some(parsed_url) })
+		let tracer = Tracer::for_url("http", &parsed_url, config.trace.clone());
+		Ok(Self { inner: None, _url: Some(parsed_url), config, stats: Default::default(), tracer })
+	}
+
+	/// Bytes received with compression enabled, see [`HttpConfig::compression`].
+	pub fn compression_stats(&self) -> &CompressionStats {
+		&self.stats
+	}
+
+	/// Requests, errors and bytes of the provider and its clones.
+	pub fn stats(&self) -> Arc<ProviderStats> {
+		self.tracer.stats().clone()
 	}
 
 	pub async fn new_with_port(address: &str, port: u32) -> Result<Self> {
@@ -50,22 +153,39 @@ impl HttpProvider {
 		Self::new(&url)
 	}
 
+	/// Create a new client with a user-generated Jsonrpsee Client, built with a
+	/// [`DecompressionLayer`] and an [`HttpTransportLayer`] middleware.
 	pub fn new_with_client(client: HttpClient) -> Self {
 		let inner = Some(Arc::new(client));
-		Self { inner, _url: None }
+		let tracer = Tracer::new("http", "custom client".into(), TraceConfig::default());
+		Self { inner, _url: None, config: HttpConfig::default(), stats: Default::default(), tracer }
 	}
 }
 
-#[maybe_async::async_impl(?Send)]
+#[cfg_attr(feature = "send", maybe_async::async_impl)]
+#[cfg_attr(not(feature = "send"), maybe_async::async_impl(?Send))]
 impl Request for HttpProvider {
-	async fn request_raw(&self, method: &str, params: RpcParams) -> Result<String> {
-		self.request::<String>(method, params).await
+	async fn request_raw(&self, method: &str, params: RpcParams) -> Result<Box<RawValue>> {
+		let call = self.tracer.request(method, self.tracer.next_id(), &params);
+		call.run(async {
+			check_method(method, self.config.allow_unsafe)?;
+			// The size of the response is checked by `jsonrpsee`, the nesting of its result here.
+			let result: Box<RawValue> = self
+				.inner()
+				.request(method, RpcParamsWrapper(params))
+				.await
+				.map_err(Error::from)?;
+			self.config
+				.limits
+				.check_message(result.get().as_bytes())
+				.map_err(Error::LimitExceeded)?;
+			Ok(result)
+		})
+		.await
 	}
 
 	async fn request<R: DeserializeOwned>(&self, method: &str, params: RpcParams) -> Result<R> {
-		self.inner()
-			.request(method, RpcParamsWrapper(params))
-			.await
-			.map_err(|e| Error::Client(Box::new(e)))
+		let result = self.request_raw(method, params).await?;
+		Ok(serde_json::from_str(result.get())?)
 	}
 }
@@ -0,0 +1,216 @@
+//! HTTP transport of the [`super::HttpProvider`].
+use crate::proxy::{self, ProxyConfig};
+use futures::future::BoxFuture;
+use hyper::{
+	client::connect::{Connected, Connection},
+	header::{HeaderValue, PROXY_AUTHORIZATION},
+	http::uri::Scheme,
+	Body, Request, Response, Uri,
+};
+use jsonrpsee_http_client::transport::{Error as TransportError, HttpBackend};
+use std::{
+	pin::Pin,
+	task::{Context, Poll},
+};
+use tokio::{
+	io::{AsyncRead, AsyncWrite, ReadBuf},
+	net::TcpStream,
+};
+use tower::{Layer, Service};
+
+/// Sends the requests of an [`super::HttpClient`].
+///
+/// `jsonrpsee` neither supports proxies nor TLS settings other than its own, requests through a
+/// proxy or using a custom [`crate::tls::TlsConfig`] are sent with a separate client.
+#[derive(Clone, Debug)]
+pub enum HttpTransport {
+	/// The transport built by `jsonrpsee`.
+	Default(HttpBackend),
+	/// Custom TLS configuration, or a proxy.
+	Custom(CustomTransport),
+}
+
+/// Client of an [`HttpTransport::Custom`] transport.
+#[derive(Clone, Debug)]
+pub struct CustomTransport(Client);
+
+#[derive(Clone, Debug)]
+enum Client {
+	/// Through a proxy, with the `Proxy-Authorization` header to add to the requests when the
+	/// proxy forwards them.
+	Plain(hyper::Client<Connector>, Option<HeaderValue>),
+	/// `jsonrpsee` is handed the `http` url of the node so that it does not set up TLS itself,
+	/// the scheme is restored before sending the requests.
+	#[cfg(feature = "__tls")]
+	Tls(hyper::Client<hyper_rustls::HttpsConnector<Connector>>),
+}
+
+impl Service<Request<Body>> for HttpTransport {
+	type Response = Response<Body>;
+	type Error = TransportError;
+	type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		let ready = match self {
+			Self::Default(backend) => return backend.poll_ready(cx),
+			Self::Custom(CustomTransport(Client::Plain(client, _))) =>
+				Service::<Request<Body>>::poll_ready(client, cx),
+			#[cfg(feature = "__tls")]
+			Self::Custom(CustomTransport(Client::Tls(client))) =>
+				Service::<Request<Body>>::poll_ready(client, cx),
+		};
+		ready.map_err(|e| TransportError::Http(e.into()))
+	}
+
+	fn call(&mut self, request: Request<Body>) -> Self::Future {
+		let response = match self {
+			Self::Default(backend) => return Box::pin(backend.call(request)),
+			Self::Custom(CustomTransport(Client::Plain(client, authorization))) => {
+				let mut request = request;
+				if let Some(authorization) = authorization {
+					request.headers_mut().insert(PROXY_AUTHORIZATION, authorization.clone());
+				}
+				client.request(request)
+			},
+			#[cfg(feature = "__tls")]
+			Self::Custom(CustomTransport(Client::Tls(client))) => client.request(with_https_scheme(request)),
+		};
+		Box::pin(async move { response.await.map_err(|e| TransportError::Http(e.into())) })
+	}
+}
+
+/// Builds the [`HttpTransport`] out of the `jsonrpsee` one.
+#[derive(Clone, Default)]
+pub struct HttpTransportLayer {
+	custom: Option<CustomTransport>,
+}
+
+impl HttpTransportLayer {
+	/// Sends the requests through `proxy`.
+	pub(crate) fn proxy(proxy: ProxyConfig) -> crate::Result<Self> {
+		let authorization = match proxy.authorization() {
+			Some(authorization) if proxy.forwards_http() => Some(
+				HeaderValue::from_str(&authorization)
+					.map_err(|e| crate::Error::Proxy(e.to_string()))?,
+			),
+			_ => None,
+		};
+		let client =
+			hyper::Client::builder().build(Connector { proxy: Some(proxy), forward: true });
+		Ok(Self { custom: Some(CustomTransport(Client::Plain(client, authorization))) })
+	}
+
+	/// Sends the requests to an `https` url with a custom TLS configuration, through `proxy` if
+	/// any.
+	#[cfg(feature = "__tls")]
+	pub(crate) fn tls(
+		tls: &crate::tls::TlsConfig,
+		proxy: Option<ProxyConfig>,
+	) -> crate::Result<Self> {
+		let connector = hyper_rustls::HttpsConnectorBuilder::new()
+			.with_tls_config((*tls.client_config()?).clone())
+			.https_only()
+			.enable_http1()
+			.wrap_connector(Connector { proxy, forward: false });
+		let client = hyper::Client::builder().build(connector);
+		Ok(Self { custom: Some(CustomTransport(Client::Tls(client))) })
+	}
+}
+
+impl Layer<HttpBackend> for HttpTransportLayer {
+	type Service = HttpTransport;
+
+	fn layer(&self, backend: HttpBackend) -> Self::Service {
+		match &self.custom {
+			Some(custom) => HttpTransport::Custom(custom.clone()),
+			None => HttpTransport::Default(backend),
+		}
+	}
+}
+
+/// Opens the TCP connections of a custom transport, through the proxy if any.
+#[derive(Clone, Debug)]
+struct Connector {
+	proxy: Option<ProxyConfig>,
+	/// Connect to an HTTP proxy itself, for it to forward requests in absolute form, instead of
+	/// tunneling. `false` under TLS, which must be end to end.
+	forward: bool,
+}
+
+impl Service<Uri> for Connector {
+	type Response = ProxiedStream;
+	type Error = std::io::Error;
+	type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+	fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn call(&mut self, uri: Uri) -> Self::Future {
+		let proxy = self.proxy.clone();
+		let forward = self.forward && proxy.as_ref().is_some_and(ProxyConfig::forwards_http);
+		let host = uri.host().unwrap_or_default().trim_matches(['[', ']']).to_string();
+		let default_port = if uri.scheme() == Some(&Scheme::HTTPS) { 443 } else { 80 };
+		let port = uri.port_u16().unwrap_or(default_port);
+		Box::pin(async move {
+			let stream = match (&proxy, forward) {
+				(Some(proxy), true) => proxy.connect_to_proxy().await,
+				_ => proxy::connect(proxy.as_ref(), &host, port).await,
+			};
+			stream
+				.map(|stream| ProxiedStream { stream, forward })
+				.map_err(|e| std::io::Error::other(format!("{e:?}")))
+		})
+	}
+}
+
+/// Connection opened by a [`Connector`].
+struct ProxiedStream {
+	stream: TcpStream,
+	/// Connected to a proxy forwarding the requests, `hyper` then sends them in absolute form.
+	forward: bool,
+}
+
+impl Connection for ProxiedStream {
+	fn connected(&self) -> Connected {
+		self.stream.connected().proxy(self.forward)
+	}
+}
+
+impl AsyncRead for ProxiedStream {
+	fn poll_read(
+		mut self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &mut ReadBuf<'_>,
+	) -> Poll<std::io::Result<()>> {
+		Pin::new(&mut self.stream).poll_read(cx, buf)
+	}
+}
+
+impl AsyncWrite for ProxiedStream {
+	fn poll_write(
+		mut self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &[u8],
+	) -> Poll<std::io::Result<usize>> {
+		Pin::new(&mut self.stream).poll_write(cx, buf)
+	}
+
+	fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+		Pin::new(&mut self.stream).poll_flush(cx)
+	}
+
+	fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+		Pin::new(&mut self.stream).poll_shutdown(cx)
+	}
+}
+
+#[cfg(feature = "__tls")]
+fn with_https_scheme(mut request: Request<Body>) -> Request<Body> {
+	let mut parts = request.uri().clone().into_parts();
+	parts.scheme = Some(Scheme::HTTPS);
+	if let Ok(uri) = Uri::from_parts(parts) {
+		*request.uri_mut() = uri;
+	}
+	request
+}
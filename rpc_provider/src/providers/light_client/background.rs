@@ -0,0 +1,605 @@
+//! The task owning the smoldot client of a [`super::LightClient`] and its providers.
+//!
+//! Providers never touch the client directly: they send their requests over a channel and
+//! receive the answer on a oneshot channel, so no lock is held while waiting for smoldot.
+use super::{
+	config::{ChainConfig, JsonRpc},
+	database::is_database_of,
+	subscription::Notifications,
+};
+use crate::{
+	codec::{decode_notification_subscription, decode_response, decode_response_id},
+	Error, Result,
+};
+use futures::{
+	stream::{self, BoxStream, SelectAll},
+	StreamExt,
+};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use smoldot_light::{platform::PlatformRef, AddChainSuccess, ChainId, Client};
+use std::collections::HashMap;
+use tokio::sync::{mpsc, oneshot};
+
+/// Message sent by the providers to the background task.
+pub(crate) enum ToBackground {
+	/// Send a JSON-RPC request whose `id` is unique for the chain.
+	///
+	/// `notifications` receives the notifications of the subscription opened by the request, if
+	/// any.
+	Request {
+		chain: ChainHandle,
+		id: u64,
+		payload: String,
+		send_back: oneshot::Sender<Result<String>>,
+		notifications: Option<Notifications>,
+	},
+}
+
+/// Message sent by the [`super::LightClient`] to the background task.
+pub(crate) enum ToClient<TChain> {
+	AddChain { chain: NewChain<TChain>, send_back: oneshot::Sender<Result<ChainHandle>> },
+	RemoveChain { chain: ChainHandle, send_back: oneshot::Sender<Result<TChain>> },
+}
+
+/// Chain to add to the smoldot client.
+pub(crate) struct NewChain<TChain> {
+	pub(crate) chain_spec: String,
+	pub(crate) config: ChainConfig,
+	pub(crate) user_data: TChain,
+}
+
+/// A chain of the client, as seen by its providers.
+///
+/// smoldot reuses the ids of removed chains, the generation tells a new chain apart from a
+/// removed one of the same id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ChainHandle {
+	pub(crate) id: ChainId,
+	generation: u64,
+}
+
+/// Requests sent to smoldot and still waiting for their answer, and the open subscriptions.
+#[derive(Default)]
+pub(crate) struct PendingRequests {
+	requests: HashMap<u64, oneshot::Sender<Result<String>>>,
+	/// Subscribing requests, by request id.
+	subscribing: HashMap<u64, Notifications>,
+	/// Open subscriptions, by the JSON of the id smoldot gave them.
+	subscriptions: HashMap<String, Notifications>,
+}
+
+impl PendingRequests {
+	pub(crate) fn insert(&mut self, id: u64, send_back: oneshot::Sender<Result<String>>) {
+		self.requests.insert(id, send_back);
+	}
+
+	/// Like [`Self::insert`], for a request opening a subscription whose notifications are handed
+	/// over to `notifications`.
+	pub(crate) fn insert_subscription(
+		&mut self,
+		id: u64,
+		send_back: oneshot::Sender<Result<String>>,
+		notifications: Notifications,
+	) {
+		self.insert(id, send_back);
+		self.subscribing.insert(id, notifications);
+	}
+
+	fn remove(&mut self, id: u64) -> Option<oneshot::Sender<Result<String>>> {
+		self.subscribing.remove(&id);
+		self.requests.remove(&id)
+	}
+
+	/// Hands a response over to the request it answers, and a notification over to its
+	/// subscription.
+	///
+	/// The subscription is registered before its id is handed over, so that none of its
+	/// notifications is missed. Responses and notifications matching nothing are dropped, as
+	/// are the subscriptions that ended.
+	pub(crate) fn dispatch(&mut self, response: String) {
+		let Some(id) = decode_response_id(response.as_bytes()) else {
+			return self.notify(response)
+		};
+		if let Some(notifications) = self.subscribing.remove(&id) {
+			if let Ok(subscription) = decode_response::<&RawValue>(response.as_bytes()) {
+				self.subscriptions.insert(subscription.get().to_owned(), notifications);
+			}
+		}
+		if let Some(send_back) = self.requests.remove(&id) {
+			let _ = send_back.send(Ok(response));
+		}
+	}
+
+	fn notify(&mut self, notification: String) {
+		let Some(subscription) = decode_notification_subscription(notification.as_bytes()) else {
+			return
+		};
+		let subscription = subscription.to_owned();
+		if let Some(notifications) = self.subscriptions.get_mut(&subscription) {
+			if !notifications.push(&notification) {
+				self.subscriptions.remove(&subscription);
+			}
+		}
+	}
+}
+
+/// The fields of a chain spec linking a parachain to its relay chain.
+#[derive(Debug, Deserialize)]
+struct ChainSpecIds {
+	id: String,
+	#[serde(default, alias = "relayChain")]
+	relay_chain: Option<String>,
+	#[serde(default, alias = "paraId")]
+	para_id: Option<u32>,
+}
+
+impl ChainSpecIds {
+	fn parse(chain_spec: &str) -> Result<Self> {
+		serde_json::from_str(chain_spec).map_err(|e| Error::InvalidChainSpec(format!("{e}")))
+	}
+
+	/// The `id` of the relay chain, when the spec is the one of a parachain.
+	fn relay_chain(&self) -> Option<&str> {
+		match (&self.relay_chain, self.para_id) {
+			(Some(relay_chain), Some(_)) => Some(relay_chain),
+			_ => None,
+		}
+	}
+}
+
+struct Chain {
+	generation: u64,
+	/// `id` field of the chain spec, parachains name their relay chain by it.
+	spec_id: String,
+	json_rpc: bool,
+	pending: PendingRequests,
+}
+
+/// The smoldot client and the chains added to it.
+pub(crate) struct Background<P, TChain>
+where
+	P: PlatformRef,
+{
+	client: Client<P, TChain>,
+	chains: HashMap<ChainId, Chain>,
+	responses: SelectAll<BoxStream<'static, (ChainHandle, String)>>,
+	next_generation: u64,
+}
+
+impl<P, TChain> Background<P, TChain>
+where
+	P: PlatformRef,
+{
+	pub(crate) fn new(platform: P) -> Self {
+		Self {
+			client: Client::new(platform),
+			chains: HashMap::new(),
+			responses: SelectAll::new(),
+			next_generation: 0,
+		}
+	}
+
+	/// Adds a chain, linking a parachain to the relay chain named by its spec.
+	pub(crate) fn add_chain(&mut self, chain: NewChain<TChain>) -> Result<ChainHandle> {
+		let NewChain { chain_spec, config, user_data } = chain;
+		let chain_spec = config.boot_nodes.apply(chain_spec)?;
+		let ids = ChainSpecIds::parse(&chain_spec)?;
+		let mut relay_chains = config.relay_chains;
+		if let Some(relay_chain) = ids.relay_chain() {
+			relay_chains.extend(
+				self.chains
+					.iter()
+					.filter(|(_, added)| added.spec_id == relay_chain)
+					.map(|(id, _)| *id),
+			);
+			if relay_chains.is_empty() {
+				return Err(Error::InvalidChainSpec(format!(
+					"the relay chain `{relay_chain}` of `{}` must be added first",
+					ids.id
+				)))
+			}
+		}
+
+		let mut database_content = config.database_content;
+		if !database_content.is_empty() && !is_database_of(&database_content, &chain_spec) {
+			tracing::warn!(chain = %ids.id, "ignoring a database saved for another genesis");
+			database_content.clear();
+		}
+
+		let AddChainSuccess { chain_id, json_rpc_responses } = self
+			.client
+			.add_chain(smoldot_light::AddChainConfig {
+				specification: &chain_spec,
+				json_rpc: config.json_rpc.into(),
+				potential_relay_chains: relay_chains.into_iter(),
+				database_content: &database_content,
+				user_data,
+			})
+			.map_err(|e| Error::InvalidChainSpec(format!("{e:?}")))?;
+
+		let handle = ChainHandle { id: chain_id, generation: self.next_generation };
+		self.next_generation += 1;
+		self.chains.insert(
+			chain_id,
+			Chain {
+				generation: handle.generation,
+				spec_id: ids.id,
+				json_rpc: config.json_rpc != JsonRpc::Disabled,
+				pending: Default::default(),
+			},
+		);
+		// smoldot ends the stream once the chain is removed.
+		if let Some(json_rpc_responses) = json_rpc_responses {
+			let responses = stream::unfold(json_rpc_responses, |mut responses| async move {
+				let response = responses.next().await?;
+				Some((response, responses))
+			});
+			self.responses.push(responses.map(move |response| (handle, response)).boxed());
+		}
+		Ok(handle)
+	}
+
+	/// Removes a chain, its pending requests fail with [`Error::ConnectionClosed`].
+	///
+	/// A chain of an older generation was already removed, the id now belongs to another chain.
+	pub(crate) fn remove_chain(&mut self, chain: ChainHandle) -> Result<TChain> {
+		if self.chain_mut(chain).is_none() {
+			return Err(Error::ConnectionClosed)
+		}
+		self.chains.remove(&chain.id);
+		Ok(self.client.remove_chain(chain.id))
+	}
+
+	fn request(
+		&mut self,
+		chain: ChainHandle,
+		id: u64,
+		payload: String,
+		send_back: oneshot::Sender<Result<String>>,
+		notifications: Option<Notifications>,
+	) {
+		let Some(added) = self.chain_mut(chain) else {
+			let _ = send_back.send(Err(Error::ConnectionClosed));
+			return
+		};
+		// smoldot panics on requests to such a chain.
+		if !added.json_rpc {
+			let _ = send_back.send(Err(Error::JsonRpcDisabled));
+			return
+		}
+		match notifications {
+			Some(notifications) => added.pending.insert_subscription(id, send_back, notifications),
+			None => added.pending.insert(id, send_back),
+		}
+		if let Err(e) = self.client.json_rpc_request(payload, chain.id) {
+			if let Some(send_back) =
+				self.chain_mut(chain).and_then(|added| added.pending.remove(id))
+			{
+				let _ = send_back.send(Err(Error::Client(Box::new(e))));
+			}
+		}
+	}
+
+	fn chain_mut(&mut self, chain: ChainHandle) -> Option<&mut Chain> {
+		self.chains
+			.get_mut(&chain.id)
+			.filter(|added| added.generation == chain.generation)
+	}
+
+	/// Runs until the [`super::LightClient`] and every provider are dropped.
+	pub(crate) async fn run(
+		mut self,
+		mut from_client: mpsc::UnboundedReceiver<ToClient<TChain>>,
+		mut from_front: mpsc::UnboundedReceiver<ToBackground>,
+	) {
+		let mut client_dropped = false;
+
+		loop {
+			tokio::select! {
+				// Requests are registered before smoldot can answer them.
+				biased;
+
+				message = from_client.recv(), if !client_dropped => match message {
+					Some(ToClient::AddChain { chain, send_back }) => {
+						let _ = send_back.send(self.add_chain(chain));
+					},
+					Some(ToClient::RemoveChain { chain, send_back }) => {
+						let _ = send_back.send(self.remove_chain(chain));
+					},
+					None => client_dropped = true,
+				},
+				message = from_front.recv() => match message {
+					Some(ToBackground::Request { chain, id, payload, send_back, notifications }) =>
+						self.request(chain, id, payload, send_back, notifications),
+					None => break,
+				},
+				Some((chain, response)) = self.responses.next() => {
+					if let Some(added) = self.chain_mut(chain) {
+						added.pending.dispatch(response);
+					}
+				},
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		light_client::{BootNodes, ClientConfig, LightClient, ScProvider},
+		limits::{Limit, Limits},
+		no_params,
+		sync::SyncConfig,
+		ws::{buffer::NotificationBuffer, LagPolicy},
+		HandleSubscription, Request, Subscribe,
+	};
+	use futures::task::noop_waker;
+	use serde_json::Value;
+	use smoldot_light::platform::DefaultPlatform;
+	use std::{
+		sync::Arc,
+		task::{Context, Poll},
+		time::Duration,
+	};
+
+	fn response(id: u64) -> String {
+		format!(r#"{{"jsonrpc":"2.0","id":{id},"result":{id}}}"#)
+	}
+
+	#[test]
+	fn dispatch_routes_out_of_order_responses() {
+		let mut pending = PendingRequests::default();
+		let receivers = (0..3)
+			.map(|id| {
+				let (send_back, receiver) = oneshot::channel();
+				pending.insert(id, send_back);
+				receiver
+			})
+			.collect::<Vec<_>>();
+
+		for id in [2, 0, 1] {
+			pending.dispatch(response(id));
+		}
+
+		assert_eq!(pending.requests.len(), 0);
+		for (id, mut receiver) in receivers.into_iter().enumerate() {
+			assert_eq!(receiver.try_recv().unwrap().unwrap(), response(id as u64));
+		}
+	}
+
+	#[test]
+	fn dispatch_ignores_notifications_and_unknown_ids() {
+		let mut pending = PendingRequests::default();
+		let (send_back, mut receiver) = oneshot::channel();
+		pending.insert(1, send_back);
+
+		pending.dispatch(
+			r#"{"jsonrpc":"2.0","method":"chain_newHead","params":{"subscription":"a"}}"#.into(),
+		);
+		pending.dispatch(response(7));
+
+		assert_eq!(pending.requests.len(), 1);
+		assert!(receiver.try_recv().is_err());
+	}
+
+	fn notification(subscription: &str, result: u64) -> String {
+		format!(
+			r#"{{"jsonrpc":"2.0","method":"chain_newHead","params":{{"subscription":"{subscription}","result":{result}}}}}"#
+		)
+	}
+
+	fn notifications(limits: Limits) -> (Notifications, Arc<NotificationBuffer>) {
+		let buffer = Arc::new(NotificationBuffer::new(16, LagPolicy::Error));
+		(Notifications::new(buffer.clone(), limits, Default::default()), buffer)
+	}
+
+	fn poll(buffer: &NotificationBuffer) -> Poll<Option<Result<Value>>> {
+		buffer.poll_next(&mut Context::from_waker(&noop_waker()))
+	}
+
+	#[test]
+	fn dispatch_routes_notifications_to_their_subscription() {
+		let mut pending = PendingRequests::default();
+		let (send_back, mut answer) = oneshot::channel();
+		let (notifications, buffer) = notifications(Limits::default());
+		pending.insert_subscription(3, send_back, notifications);
+
+		pending.dispatch(r#"{"jsonrpc":"2.0","id":3,"result":"a"}"#.into());
+		assert!(answer.try_recv().unwrap().is_ok());
+		pending.dispatch(notification("a", 1));
+		pending.dispatch(notification("b", 2));
+		assert!(matches!(poll(&buffer), Poll::Ready(Some(Ok(result))) if result == 1));
+		assert!(poll(&buffer).is_pending());
+
+		buffer.close_consumer();
+		pending.dispatch(notification("a", 3));
+		assert!(pending.subscriptions.is_empty());
+	}
+
+	#[test]
+	fn notifications_over_the_limits_end_their_subscription() {
+		let mut pending = PendingRequests::default();
+		let (send_back, _answer) = oneshot::channel();
+		let limits = Limits { max_message_size: notification("a", 1).len(), ..Limits::default() };
+		let (notifications, buffer) = notifications(limits);
+		pending.insert_subscription(1, send_back, notifications);
+
+		pending.dispatch(r#"{"jsonrpc":"2.0","id":1,"result":"a"}"#.into());
+		pending.dispatch(notification("a", 1));
+		pending.dispatch(notification("a", 10));
+		assert!(pending.subscriptions.is_empty());
+		assert!(matches!(poll(&buffer), Poll::Ready(Some(Ok(_)))));
+		assert!(matches!(
+			poll(&buffer),
+			Poll::Ready(Some(Err(Error::LimitExceeded(Limit::MessageSize))))
+		));
+		assert!(matches!(poll(&buffer), Poll::Ready(None)));
+	}
+
+	#[test]
+	fn dropped_subscriptions_end_with_the_connection_closed() {
+		let (notifications, buffer) = notifications(Limits::default());
+		drop(notifications);
+
+		assert!(matches!(poll(&buffer), Poll::Ready(Some(Err(Error::ConnectionClosed)))));
+	}
+
+	#[test]
+	fn failed_subscriptions_are_not_registered() {
+		let mut pending = PendingRequests::default();
+		let (send_back, mut answer) = oneshot::channel();
+		let (notifications, _buffer) = notifications(Limits::default());
+		pending.insert_subscription(1, send_back, notifications);
+
+		pending
+			.dispatch(r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32601,"message":"no"}}"#.into());
+		assert!(answer.try_recv().unwrap().is_ok());
+		assert!(pending.subscribing.is_empty());
+		assert!(pending.subscriptions.is_empty());
+	}
+
+	/// smoldot answers these methods from the spec and its own configuration, without peers.
+	#[cfg(feature = "send")]
+	#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+	async fn concurrent_requests_are_all_answered_to_their_sender() {
+		const REQUESTS: usize = 3000;
+		const EXPECTED: [(&str, &str); 3] =
+			[("system_chain", "Westend"), ("system_name", "throughput"), ("system_version", "1.0")];
+		let westend = include_str!("../../../../chain_spec/demo/westend.json");
+		let config =
+			ChainConfig { boot_nodes: BootNodes::Replace(Vec::new()), ..ChainConfig::default() };
+		let client = ClientConfig { name: "throughput".into(), version: "1.0".into() };
+		let provider = ScProvider::new_with_config(westend, client, config)
+			.unwrap()
+			.with_limits(Limits { max_pending_responses: REQUESTS, ..Limits::default() });
+
+		let requests = (0..REQUESTS)
+			.map(|i| {
+				let provider = provider.clone();
+				let (method, _) = EXPECTED[i % EXPECTED.len()];
+				tokio::spawn(async move { provider.request::<String>(method, no_params!()).await })
+			})
+			.collect::<Vec<_>>();
+		let answers = futures::future::join_all(requests).await;
+
+		assert_eq!(answers.len(), REQUESTS);
+		for (i, answer) in answers.into_iter().enumerate() {
+			let (method, expected) = EXPECTED[i % EXPECTED.len()];
+			assert_eq!(answer.unwrap().unwrap(), expected, "request {i} to {method}");
+		}
+	}
+
+	#[tokio::test]
+	async fn unsafe_methods_are_only_sent_when_allowed() {
+		let westend = include_str!("../../../../chain_spec/demo/westend.json");
+		let config = |allow_unsafe| ChainConfig {
+			boot_nodes: BootNodes::Replace(Vec::new()),
+			allow_unsafe,
+			..ChainConfig::default()
+		};
+
+		let refused =
+			ScProvider::new_with_config(westend, ClientConfig::default(), config(false)).unwrap();
+		let error = refused.request::<String>("author_rotateKeys", no_params!()).await.unwrap_err();
+		assert!(matches!(error, Error::UnsafeMethod(_)), "{error:?}");
+
+		// Sent to smoldot, which has no keystore and answers with an error of its own.
+		let allowed =
+			ScProvider::new_with_config(westend, ClientConfig::default(), config(true)).unwrap();
+		let error = allowed.request::<String>("author_rotateKeys", no_params!()).await.unwrap_err();
+		assert!(matches!(error, Error::JsonRpcError(_)), "{error:?}");
+	}
+
+	#[tokio::test]
+	async fn stale_handles_do_not_remove_a_new_chain() {
+		let westend = include_str!("../../../../chain_spec/demo/westend.json");
+		let chain = || NewChain {
+			chain_spec: westend.to_string(),
+			config: ChainConfig {
+				boot_nodes: BootNodes::Replace(Vec::new()),
+				..ChainConfig::default()
+			},
+			user_data: (),
+		};
+		let mut background = Background::new(DefaultPlatform::new("test".into(), "1.0".into()));
+
+		let removed = background.add_chain(chain()).unwrap();
+		background.remove_chain(removed).unwrap();
+		// smoldot may give the new chain the id of the removed one.
+		let added = background.add_chain(chain()).unwrap();
+
+		assert!(matches!(background.remove_chain(removed), Err(Error::ConnectionClosed)));
+		assert!(background.chain_mut(added).is_some());
+		background.remove_chain(added).unwrap();
+	}
+
+	#[tokio::test]
+	async fn subscriptions_are_opened_and_closed_through_smoldot() {
+		let westend = include_str!("../../../../chain_spec/demo/westend.json");
+		let config =
+			ChainConfig { boot_nodes: BootNodes::Replace(Vec::new()), ..ChainConfig::default() };
+		let provider =
+			ScProvider::new_with_config(westend, ClientConfig::default(), config).unwrap();
+
+		let subscription = provider
+			.subscribe::<serde_json::Value>(
+				"chain_subscribeNewHeads",
+				no_params!(),
+				"chain_unsubscribeNewHeads",
+			)
+			.await
+			.unwrap();
+		assert_eq!(provider.stats().active_subscriptions(), 1);
+		subscription.unsubscribe().await.unwrap();
+		assert_eq!(provider.stats().active_subscriptions(), 0);
+	}
+
+	#[tokio::test]
+	async fn waiting_for_the_sync_fails_once_the_chain_is_removed() {
+		let westend = include_str!("../../../../chain_spec/demo/westend.json");
+		let config =
+			ChainConfig { boot_nodes: BootNodes::Replace(Vec::new()), ..ChainConfig::default() };
+		let client = LightClient::new();
+		let provider = client.add_chain_with_config(westend, config, ()).await.unwrap();
+		client.remove_chain(&provider).await.unwrap();
+
+		let config =
+			SyncConfig { poll_interval: Duration::from_millis(10), ..SyncConfig::default() };
+		let error = provider.wait_until_synced(config).await.unwrap_err();
+		assert!(matches!(error, Error::ConnectionClosed), "{error:?}");
+	}
+
+	#[tokio::test]
+	async fn waiting_for_the_sync_of_a_chain_without_json_rpc_fails() {
+		let westend = include_str!("../../../../chain_spec/demo/westend.json");
+		let config = ChainConfig {
+			boot_nodes: BootNodes::Replace(Vec::new()),
+			json_rpc: JsonRpc::Disabled,
+			..ChainConfig::default()
+		};
+		let provider =
+			ScProvider::new_with_config(westend, ClientConfig::default(), config).unwrap();
+
+		let error = provider.wait_until_synced(SyncConfig::default()).await.unwrap_err();
+		assert!(matches!(error, Error::JsonRpcDisabled), "{error:?}");
+	}
+
+	#[test]
+	fn parachain_spec_names_its_relay_chain() {
+		let westmint = include_str!("../../../../chain_spec/demo/westend-westmint.json");
+		let westend = include_str!("../../../../chain_spec/demo/westend.json");
+
+		let ids = ChainSpecIds::parse(westmint).unwrap();
+		assert_eq!(ids.id, "westmint");
+		assert_eq!(ids.relay_chain(), Some("westend2"));
+		assert_eq!(ChainSpecIds::parse(westend).unwrap().relay_chain(), None);
+	}
+
+	#[test]
+	fn camel_case_relay_chain_fields_are_accepted() {
+		let ids = ChainSpecIds::parse(r#"{"id":"para","relayChain":"relay","paraId":2000}"#);
+		assert_eq!(ids.unwrap().relay_chain(), Some("relay"));
+	}
+}
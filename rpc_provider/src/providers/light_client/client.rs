@@ -0,0 +1,167 @@
+//! One smoldot client shared by the providers of several chains.
+use super::{
+	background::{Background, ChainHandle, NewChain, ToBackground, ToClient},
+	load_database, ChainConfig, ClientConfig, LogConfig, LoggingPlatform, ScProvider,
+};
+use crate::{Error, Result};
+use smoldot_light::platform::{DefaultPlatform, PlatformRef};
+use std::{marker::PhantomData, path::Path, sync::Arc};
+use tokio::sync::{mpsc, oneshot};
+
+/// Embedded smoldot light client to which chains are added and removed at runtime.
+///
+/// A relay chain and its parachains must be added to the same client: a parachain is linked to
+/// the chains already added whose chain spec `id` is the `relay_chain` of its own spec. The
+/// client runs until it and every provider of its chains are dropped.
+///
+/// ```no_run
+/// # async fn run() -> rpc_provider::Result<()> {
+/// use rpc_provider::light_client::LightClient;
+///
+/// let client = LightClient::new();
+/// let westend = client.add_chain(std::fs::read_to_string("westend.json")?, ()).await?;
+/// let westmint = client.add_chain(std::fs::read_to_string("westend-westmint.json")?, ()).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct LightClient<P, TChain = ()>
+where
+	P: PlatformRef,
+{
+	to_client: mpsc::UnboundedSender<ToClient<TChain>>,
+	/// Handed over to the providers.
+	to_background: mpsc::UnboundedSender<ToBackground>,
+	_marker: PhantomData<fn() -> P>,
+}
+
+impl<P, TChain> Clone for LightClient<P, TChain>
+where
+	P: PlatformRef,
+{
+	fn clone(&self) -> Self {
+		Self {
+			to_client: self.to_client.clone(),
+			to_background: self.to_background.clone(),
+			_marker: PhantomData,
+		}
+	}
+}
+
+impl LightClient<Arc<DefaultPlatform>, ()> {
+	/// Client on the default platform, named after this crate.
+	pub fn new() -> Self {
+		Self::new_with_config(ClientConfig::default())
+	}
+
+	pub fn new_with_config(config: ClientConfig) -> Self {
+		// `DefaultPlatform::new` already returns an `Arc`.
+		Self::new_with_platform_ref(DefaultPlatform::new(config.name, config.version))
+	}
+}
+
+impl Default for LightClient<Arc<DefaultPlatform>, ()> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl LightClient<LoggingPlatform<Arc<DefaultPlatform>>, ()> {
+	/// Client on the default platform, forwarding the logs of smoldot to `tracing` or `log`.
+	pub fn new_with_logs(config: ClientConfig, logs: LogConfig) -> Self {
+		let platform = DefaultPlatform::new(config.name, config.version);
+		Self::new_with_platform_ref(LoggingPlatform::new(platform, logs))
+	}
+}
+
+impl<P, TChain> LightClient<Arc<P>, TChain>
+where
+	Arc<P>: PlatformRef,
+	TChain: Send + 'static,
+{
+	pub fn new_with_platform(platform: P) -> Self {
+		Self::new_with_platform_ref(Arc::new(platform))
+	}
+}
+
+impl<P, TChain> LightClient<P, TChain>
+where
+	P: PlatformRef,
+	TChain: Send + 'static,
+{
+	/// Client on a platform implemented by a shared reference, such as a [`LoggingPlatform`].
+	pub fn new_with_platform_ref(platform: P) -> Self {
+		Self::spawn(platform.clone(), Background::new(platform))
+	}
+
+	/// Hands the client over to a background task spawned on the platform.
+	pub(crate) fn spawn(platform: P, background: Background<P, TChain>) -> Self {
+		let (to_client, from_client) = mpsc::unbounded_channel();
+		let (to_background, from_front) = mpsc::unbounded_channel();
+		platform.spawn_task(
+			"rpc-provider-light-client".into(),
+			background.run(from_client, from_front),
+		);
+
+		Self { to_client, to_background, _marker: PhantomData }
+	}
+
+	/// Adds a chain and returns a provider for it.
+	///
+	/// Fails with [`Error::InvalidChainSpec`] when the spec is invalid, or is the one of a
+	/// parachain whose relay chain has not been added yet. A relay chain only used by its
+	/// parachains can be added without JSON-RPC, see [`ChainConfig::json_rpc`].
+	pub async fn add_chain(
+		&self,
+		chain_spec: impl Into<String>,
+		user_data: TChain,
+	) -> Result<ScProvider<P, TChain>> {
+		self.add_chain_with_config(chain_spec, ChainConfig::default(), user_data).await
+	}
+
+	/// Like [`Self::add_chain`], with options other than the defaults of [`ChainConfig`].
+	pub async fn add_chain_with_config(
+		&self,
+		chain_spec: impl Into<String>,
+		config: ChainConfig,
+		user_data: TChain,
+	) -> Result<ScProvider<P, TChain>> {
+		let (send_back, added) = oneshot::channel();
+		let allow_unsafe = config.allow_unsafe;
+		let chain = NewChain { chain_spec: chain_spec.into(), config, user_data };
+		self.to_client
+			.send(ToClient::AddChain { chain, send_back })
+			.map_err(|_| Error::ConnectionClosed)?;
+		let chain = added.await.map_err(|_| Error::ConnectionClosed)??;
+		Ok(self.provider(chain, allow_unsafe))
+	}
+
+	/// Like [`Self::add_chain`], resuming the sync from the database saved at `database` by
+	/// [`ScProvider::save_database`], see [`load_database`].
+	pub async fn add_chain_with_database(
+		&self,
+		chain_spec: impl Into<String>,
+		database: impl AsRef<Path>,
+		user_data: TChain,
+	) -> Result<ScProvider<P, TChain>> {
+		let config =
+			ChainConfig { database_content: load_database(database), ..ChainConfig::default() };
+		self.add_chain_with_config(chain_spec, config, user_data).await
+	}
+
+	/// Removes the chain of `provider` and returns its user data.
+	///
+	/// Its providers then fail with [`Error::ConnectionClosed`]. A relay chain keeps running
+	/// in smoldot as long as one of its parachains does. Fails with [`Error::ConnectionClosed`]
+	/// when the chain was already removed, even if smoldot reused its id for another chain.
+	pub async fn remove_chain(&self, provider: &ScProvider<P, TChain>) -> Result<TChain> {
+		let (send_back, removed) = oneshot::channel();
+		self.to_client
+			.send(ToClient::RemoveChain { chain: provider.chain, send_back })
+			.map_err(|_| Error::ConnectionClosed)?;
+		removed.await.map_err(|_| Error::ConnectionClosed)?
+	}
+
+	pub(crate) fn provider(&self, chain: ChainHandle, allow_unsafe: bool) -> ScProvider<P, TChain> {
+		ScProvider::from_chain(chain, self.to_background.clone(), allow_unsafe)
+	}
+}
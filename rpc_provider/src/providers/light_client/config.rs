@@ -0,0 +1,144 @@
+//! Options of the smoldot client and of the chains added to it.
+use crate::{Error, Result};
+use serde_json::Value;
+use smoldot_light::ChainId;
+use std::num::NonZeroU32;
+
+/// Identity the smoldot client announces to its peers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientConfig {
+	pub name: String,
+	pub version: String,
+}
+
+impl Default for ClientConfig {
+	fn default() -> Self {
+		Self { name: env!("CARGO_PKG_NAME").into(), version: env!("CARGO_PKG_VERSION").into() }
+	}
+}
+
+/// Options of a chain added to a smoldot client.
+#[derive(Debug, Clone, Default)]
+pub struct ChainConfig {
+	pub json_rpc: JsonRpc,
+	pub boot_nodes: BootNodes,
+	/// Relay chains to choose from, on top of the ones matching the `relay_chain` of the spec.
+	pub relay_chains: Vec<ChainId>,
+	/// Database to resume the sync from, see [`super::load_database`]. Empty to sync from the
+	/// chain spec.
+	pub database_content: String,
+	/// Send the methods that are unsafe on a full node, which smoldot does not serve either.
+	/// Refused with [`Error::UnsafeMethod`] by default, like by the other providers.
+	pub allow_unsafe: bool,
+}
+
+/// JSON-RPC endpoint of a chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonRpc {
+	/// For chains only used as the relay chain of parachains, which saves the resources of the
+	/// endpoint. Requests to the chain fail with [`Error::JsonRpcDisabled`].
+	Disabled,
+	Enabled {
+		/// Requests queued by smoldot before new ones are rejected.
+		max_pending_requests: NonZeroU32,
+		/// Active subscriptions before new ones are rejected.
+		max_subscriptions: u32,
+	},
+}
+
+impl Default for JsonRpc {
+	/// The provider is the only, trusted, client of the endpoint: nothing is limited.
+	fn default() -> Self {
+		Self::Enabled { max_pending_requests: NonZeroU32::MAX, max_subscriptions: u32::MAX }
+	}
+}
+
+impl From<JsonRpc> for smoldot_light::AddChainConfigJsonRpc {
+	fn from(json_rpc: JsonRpc) -> Self {
+		match json_rpc {
+			JsonRpc::Disabled => Self::Disabled,
+			JsonRpc::Enabled { max_pending_requests, max_subscriptions } =>
+				Self::Enabled { max_pending_requests, max_subscriptions },
+		}
+	}
+}
+
+/// Boot nodes of a chain, as multiaddresses such as
+/// `/dns/example.org/tcp/30333/ws/p2p/12D3KooW...`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum BootNodes {
+	/// The `bootNodes` of the chain spec.
+	#[default]
+	FromChainSpec,
+	/// The `bootNodes` of the chain spec, then these ones.
+	Extra(Vec<String>),
+	/// These ones instead of the `bootNodes` of the chain spec.
+	Replace(Vec<String>),
+}
+
+impl BootNodes {
+	/// Rewrites the `bootNodes` of `chain_spec`.
+	pub(crate) fn apply(&self, chain_spec: String) -> Result<String> {
+		let (boot_nodes, replace) = match self {
+			Self::FromChainSpec => return Ok(chain_spec),
+			Self::Extra(boot_nodes) => (boot_nodes, false),
+			Self::Replace(boot_nodes) => (boot_nodes, true),
+		};
+
+		let mut spec: Value = serde_json::from_str(&chain_spec)
+			.map_err(|e| Error::InvalidChainSpec(format!("{e}")))?;
+		let Some(fields) = spec.as_object_mut() else {
+			return Err(Error::InvalidChainSpec("the chain spec is not an object".into()))
+		};
+		let spec_boot_nodes = fields.entry("bootNodes").or_insert_with(|| Value::Array(Vec::new()));
+		let Some(spec_boot_nodes) = spec_boot_nodes.as_array_mut() else {
+			return Err(Error::InvalidChainSpec("`bootNodes` is not an array".into()))
+		};
+		if replace {
+			spec_boot_nodes.clear();
+		}
+		spec_boot_nodes.extend(boot_nodes.iter().cloned().map(Value::String));
+		Ok(spec.to_string())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SPEC: &str = r#"{"id":"chain","bootNodes":["/dns/a/tcp/1/ws/p2p/A"]}"#;
+
+	fn boot_nodes(chain_spec: &str) -> Vec<String> {
+		let spec: Value = serde_json::from_str(chain_spec).unwrap();
+		serde_json::from_value(spec["bootNodes"].clone()).unwrap()
+	}
+
+	#[test]
+	fn chain_spec_boot_nodes_are_kept_by_default() {
+		assert_eq!(BootNodes::FromChainSpec.apply(SPEC.into()).unwrap(), SPEC);
+	}
+
+	#[test]
+	fn extra_boot_nodes_are_appended() {
+		let spec = BootNodes::Extra(vec!["/dns/b/tcp/2/ws/p2p/B".into()]).apply(SPEC.into());
+		assert_eq!(boot_nodes(&spec.unwrap()), ["/dns/a/tcp/1/ws/p2p/A", "/dns/b/tcp/2/ws/p2p/B"]);
+	}
+
+	#[test]
+	fn replacement_boot_nodes_drop_the_ones_of_the_spec() {
+		let spec = BootNodes::Replace(vec!["/dns/b/tcp/2/ws/p2p/B".into()]).apply(SPEC.into());
+		assert_eq!(boot_nodes(&spec.unwrap()), ["/dns/b/tcp/2/ws/p2p/B"]);
+
+		let spec = BootNodes::Replace(vec!["/dns/b/tcp/2/ws/p2p/B".into()]).apply("{}".into());
+		assert_eq!(boot_nodes(&spec.unwrap()), ["/dns/b/tcp/2/ws/p2p/B"]);
+	}
+
+	#[test]
+	fn malformed_boot_nodes_are_refused() {
+		let spec = r#"{"bootNodes":"/dns/a/tcp/1/ws/p2p/A"}"#;
+		assert!(matches!(
+			BootNodes::Extra(Vec::new()).apply(spec.into()),
+			Err(Error::InvalidChainSpec(_))
+		));
+	}
+}
@@ -0,0 +1,142 @@
+//! Persistence of the smoldot database of a [`super::ScProvider`].
+//!
+//! smoldot exports what it knows about a chain, its finalized block and the peers it found,
+//! through `chainHead_unstable_finalizedDatabase`. Passing that export back when the chain is
+//! added resumes the sync from it instead of the checkpoint of the chain spec.
+use crate::Result;
+use serde::Deserialize;
+use std::{fs, io, path::Path};
+use types_support::chain_spec::{write_atomically, Genesis, StateVersion};
+
+/// Method exporting the database of a chain.
+pub(crate) const FINALIZED_DATABASE: &str = "chainHead_unstable_finalizedDatabase";
+
+/// Reads a database saved by [`super::ScProvider::save_database`].
+///
+/// A missing, unreadable or corrupt file gives an empty database, the chain then syncs from its
+/// chain spec. A database saved for another genesis is discarded when the chain is added, see
+/// [`is_database_of`].
+pub fn load_database(path: impl AsRef<Path>) -> String {
+	let path = path.as_ref();
+	let content = match fs::read_to_string(path) {
+		Ok(content) => content,
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return String::new(),
+		Err(e) => {
+			tracing::warn!(path = %path.display(), error = %e, "ignoring unreadable database");
+			return String::new()
+		},
+	};
+	if !is_database(&content) {
+		tracing::warn!(path = %path.display(), "ignoring corrupt database");
+		return String::new()
+	}
+	content
+}
+
+/// Writes a database, atomically so that a crash never leaves a truncated file behind.
+pub(crate) fn write_database(path: &Path, content: &str) -> Result<()> {
+	Ok(write_atomically(path, content)?)
+}
+
+/// Whether `content` looks like an export of smoldot.
+///
+/// The format belongs to smoldot and may change, only the genesis hash is checked. Anything else
+/// wrong is caught by smoldot, which then starts from the chain spec.
+pub(crate) fn is_database(content: &str) -> bool {
+	#[derive(Deserialize)]
+	struct Database<'a> {
+		#[serde(rename = "genesisHash", borrow)]
+		genesis_hash: &'a str,
+	}
+
+	match serde_json::from_str::<Database>(content) {
+		Ok(database) =>
+			database.genesis_hash.len() == 64
+				&& database.genesis_hash.bytes().all(|b| b.is_ascii_hexdigit()),
+		Err(_) => false,
+	}
+}
+
+/// Whether `database` was saved for the genesis of `chain_spec`.
+///
+/// The hash is computed from the raw genesis of the spec with both state versions, that of the
+/// runtime is not known. A spec without raw genesis can not tell, its database is kept.
+pub(crate) fn is_database_of(database: &str, chain_spec: &str) -> bool {
+	#[derive(Deserialize)]
+	struct Database<'a> {
+		#[serde(rename = "genesisHash", borrow)]
+		genesis_hash: &'a str,
+	}
+	#[derive(Deserialize)]
+	struct ChainSpec {
+		genesis: Genesis,
+	}
+
+	let Ok(database) = serde_json::from_str::<Database>(database) else { return false };
+	let Ok(ChainSpec { genesis: Genesis::Raw(genesis) }) = serde_json::from_str(chain_spec) else {
+		return true
+	};
+	let saved = database.genesis_hash.trim_start_matches("0x");
+	[StateVersion::V0, StateVersion::V1].into_iter().any(|version| {
+		genesis.block_hash(version).is_ok_and(|hash| {
+			let hash = hash.iter().map(|byte| format!("{byte:02x}")).collect::<String>();
+			hash.eq_ignore_ascii_case(saved)
+		})
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::path::PathBuf;
+
+	const DATABASE: &str = r#"{"genesisHash":"91b171bb158e2d3848fa23a9f1c25182fb8e20313b2c1eb49219da7a70ce90c3","chain":{},"nodes":{}}"#;
+
+	fn temporary_path(name: &str) -> PathBuf {
+		std::env::temp_dir().join(format!("rpc-provider-{}-{name}", std::process::id()))
+	}
+
+	#[test]
+	fn saved_database_is_restored() {
+		let path = temporary_path("restored.json");
+		write_database(&path, DATABASE).unwrap();
+
+		assert_eq!(load_database(&path), DATABASE);
+		fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn missing_database_is_empty() {
+		assert_eq!(load_database(temporary_path("missing.json")), "");
+	}
+
+	#[test]
+	fn only_databases_of_the_same_genesis_match() {
+		let spec = |top: &str| {
+			format!(r#"{{"genesis":{{"raw":{{"top":{top},"childrenDefault":{{}}}}}}}}"#)
+		};
+		let genesis = types_support::chain_spec::RawGenesis {
+			top: [("0x00".to_string(), "0x01".to_string())].into(),
+			..Default::default()
+		};
+		let hash = genesis.block_hash(StateVersion::V1).unwrap();
+		let hash = hash.iter().map(|byte| format!("{byte:02x}")).collect::<String>();
+		let database = DATABASE.replace(&DATABASE[16..80], &hash);
+
+		assert!(is_database_of(&database, &spec(r#"{"0x00":"0x01"}"#)));
+		assert!(!is_database_of(&database, &spec(r#"{"0x00":"0x02"}"#)));
+		assert!(!is_database_of(DATABASE, &spec(r#"{"0x00":"0x01"}"#)));
+		// Nothing to compare with.
+		assert!(is_database_of(&database, r#"{"genesis":{"stateRootHash":"0x00"}}"#));
+	}
+
+	#[test]
+	fn corrupt_database_is_empty() {
+		let path = temporary_path("corrupt.json");
+		for corrupt in ["", "not json", r#"{"chain":{}}"#, r#"{"genesisHash":"0x1234"}"#] {
+			fs::write(&path, corrupt).unwrap();
+			assert_eq!(load_database(&path), "", "{corrupt}");
+		}
+		fs::remove_file(path).unwrap();
+	}
+}
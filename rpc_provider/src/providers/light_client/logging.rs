@@ -0,0 +1,349 @@
+//! Forwarding of the logs of smoldot to `tracing` or `log`.
+//!
+//! smoldot names the chain in its log targets, such as `sync-service-westend2` or
+//! `runtime-westend2`, so the lines of a chain can be told apart and filtered on their own.
+use smoldot_light::platform::{Address, ConnectionType, LogLevel, MultiStreamAddress, PlatformRef};
+use std::{borrow::Cow, fmt, fmt::Write, future::Future, pin::Pin, time::Duration};
+use tracing::level_filters::LevelFilter;
+
+/// Where the logs of smoldot go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogBackend {
+	/// Events of the `smoldot` target, with the target of smoldot in their `source` field.
+	Tracing,
+	/// Records of the `smoldot::<target of smoldot>` target.
+	Log,
+}
+
+/// Logging of a smoldot client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogConfig {
+	pub backend: LogBackend,
+	/// Lines less severe are dropped before being formatted. `INFO` by default, smoldot is very
+	/// verbose at lower levels.
+	pub max_level: LevelFilter,
+}
+
+impl Default for LogConfig {
+	fn default() -> Self {
+		Self { backend: LogBackend::Tracing, max_level: LevelFilter::INFO }
+	}
+}
+
+/// Platform forwarding the logs of smoldot as set by its [`LogConfig`], the rest is left to the
+/// wrapped platform.
+#[derive(Debug, Clone)]
+pub struct LoggingPlatform<T> {
+	inner: T,
+	config: LogConfig,
+}
+
+impl<T> LoggingPlatform<T> {
+	pub fn new(inner: T, config: LogConfig) -> Self {
+		Self { inner, config }
+	}
+}
+
+fn level_filter(level: LogLevel) -> LevelFilter {
+	match level {
+		LogLevel::Error => LevelFilter::ERROR,
+		LogLevel::Warn => LevelFilter::WARN,
+		LogLevel::Info => LevelFilter::INFO,
+		LogLevel::Debug => LevelFilter::DEBUG,
+		LogLevel::Trace => LevelFilter::TRACE,
+	}
+}
+
+/// Appends the key values of smoldot to its message, the way the default platform does.
+fn format_line<'a>(
+	message: &str,
+	key_values: impl Iterator<Item = (&'a str, &'a dyn fmt::Display)>,
+) -> String {
+	let mut line = String::from(message);
+	for (i, (key, value)) in key_values.enumerate() {
+		let _ = write!(line, "{}{key}={value}", if i == 0 { "; " } else { ", " });
+	}
+	line
+}
+
+impl<T: PlatformRef> PlatformRef for LoggingPlatform<T> {
+	type Delay = T::Delay;
+	type Instant = T::Instant;
+	type MultiStream = T::MultiStream;
+	type Stream = T::Stream;
+	type ReadWriteAccess<'a> = T::ReadWriteAccess<'a>;
+	type StreamErrorRef<'a> = T::StreamErrorRef<'a>;
+	type StreamConnectFuture = T::StreamConnectFuture;
+	type MultiStreamConnectFuture = T::MultiStreamConnectFuture;
+	type StreamUpdateFuture<'a> = T::StreamUpdateFuture<'a>;
+	type NextSubstreamFuture<'a> = T::NextSubstreamFuture<'a>;
+
+	fn log<'a>(
+		&self,
+		log_level: LogLevel,
+		log_target: &'a str,
+		message: &'a str,
+		key_values: impl Iterator<Item = (&'a str, &'a dyn fmt::Display)>,
+	) {
+		let level = level_filter(log_level);
+		if level > self.config.max_level {
+			return
+		}
+
+		match self.config.backend {
+			LogBackend::Tracing => {
+				if level > LevelFilter::current() {
+					return
+				}
+				let line = format_line(message, key_values);
+				// The level of a `tracing` event must be a constant.
+				match log_level {
+					LogLevel::Error =>
+						tracing::error!(target: "smoldot", source = log_target, "{line}"),
+					LogLevel::Warn =>
+						tracing::warn!(target: "smoldot", source = log_target, "{line}"),
+					LogLevel::Info =>
+						tracing::info!(target: "smoldot", source = log_target, "{line}"),
+					LogLevel::Debug =>
+						tracing::debug!(target: "smoldot", source = log_target, "{line}"),
+					LogLevel::Trace =>
+						tracing::trace!(target: "smoldot", source = log_target, "{line}"),
+				}
+			},
+			LogBackend::Log => {
+				let level = match log_level {
+					LogLevel::Error => log::Level::Error,
+					LogLevel::Warn => log::Level::Warn,
+					LogLevel::Info => log::Level::Info,
+					LogLevel::Debug => log::Level::Debug,
+					LogLevel::Trace => log::Level::Trace,
+				};
+				if level > log::max_level() {
+					return
+				}
+				let target = format!("smoldot::{log_target}");
+				let line = format_line(message, key_values);
+				log::logger().log(
+					&log::Record::builder()
+						.level(level)
+						.target(&target)
+						.args(format_args!("{line}"))
+						.build(),
+				);
+			},
+		}
+	}
+
+	fn now_from_unix_epoch(&self) -> Duration {
+		self.inner.now_from_unix_epoch()
+	}
+
+	fn now(&self) -> Self::Instant {
+		self.inner.now()
+	}
+
+	fn fill_random_bytes(&self, buffer: &mut [u8]) {
+		self.inner.fill_random_bytes(buffer)
+	}
+
+	fn sleep(&self, duration: Duration) -> Self::Delay {
+		self.inner.sleep(duration)
+	}
+
+	fn sleep_until(&self, when: Self::Instant) -> Self::Delay {
+		self.inner.sleep_until(when)
+	}
+
+	fn spawn_task(&self, task_name: Cow<str>, task: impl Future<Output = ()> + Send + 'static) {
+		self.inner.spawn_task(task_name, task)
+	}
+
+	fn client_name(&self) -> Cow<str> {
+		self.inner.client_name()
+	}
+
+	fn client_version(&self) -> Cow<str> {
+		self.inner.client_version()
+	}
+
+	fn supports_connection_type(&self, connection_type: ConnectionType) -> bool {
+		self.inner.supports_connection_type(connection_type)
+	}
+
+	fn connect_stream(&self, address: Address) -> Self::StreamConnectFuture {
+		self.inner.connect_stream(address)
+	}
+
+	fn connect_multistream(&self, address: MultiStreamAddress) -> Self::MultiStreamConnectFuture {
+		self.inner.connect_multistream(address)
+	}
+
+	fn open_out_substream(&self, connection: &mut Self::MultiStream) {
+		self.inner.open_out_substream(connection)
+	}
+
+	fn next_substream<'a>(
+		&self,
+		connection: &'a mut Self::MultiStream,
+	) -> Self::NextSubstreamFuture<'a> {
+		self.inner.next_substream(connection)
+	}
+
+	fn read_write_access<'a>(
+		&self,
+		stream: Pin<&'a mut Self::Stream>,
+	) -> Result<Self::ReadWriteAccess<'a>, Self::StreamErrorRef<'a>> {
+		self.inner.read_write_access(stream)
+	}
+
+	fn wait_read_write_again<'a>(
+		&self,
+		stream: Pin<&'a mut Self::Stream>,
+	) -> Self::StreamUpdateFuture<'a> {
+		self.inner.wait_read_write_again(stream)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use smoldot_light::platform::DefaultPlatform;
+	use std::sync::{Arc, Mutex};
+	use tracing::{
+		field::{Field, Visit},
+		Event, Subscriber,
+	};
+	use tracing_subscriber::{layer::Context, prelude::*, Layer};
+
+	/// Target of a chain no other test logs to.
+	const CHAIN_TARGET: &str = "sync-service-logging-test";
+
+	/// Level, target and message of the lines forwarded to a backend, and the `source` field of
+	/// the `tracing` events.
+	type Line = (String, String, Option<String>, String);
+
+	fn platform(backend: LogBackend) -> LoggingPlatform<Arc<DefaultPlatform>> {
+		let config = LogConfig { backend, max_level: LevelFilter::INFO };
+		LoggingPlatform::new(DefaultPlatform::new("test".into(), "0".into()), config)
+	}
+
+	/// Logs a line above and a line below the `INFO` level of [`platform`].
+	fn log_lines(platform: &LoggingPlatform<Arc<DefaultPlatform>>) {
+		let peer: &dyn fmt::Display = &"12D3KooW";
+		platform.log(LogLevel::Warn, CHAIN_TARGET, "block-announce", [("peer", peer)].into_iter());
+		platform.log(LogLevel::Debug, CHAIN_TARGET, "dropped", std::iter::empty());
+	}
+
+	#[derive(Clone, Default)]
+	struct CapturedEvents(Arc<Mutex<Vec<Line>>>);
+
+	#[derive(Default)]
+	struct EventFields {
+		source: Option<String>,
+		message: String,
+	}
+
+	impl Visit for EventFields {
+		fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+			if field.name() == "message" {
+				self.message = format!("{value:?}");
+			}
+		}
+
+		fn record_str(&mut self, field: &Field, value: &str) {
+			if field.name() == "source" {
+				self.source = Some(value.to_string());
+			}
+		}
+	}
+
+	impl<S: Subscriber> Layer<S> for CapturedEvents {
+		fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+			let metadata = event.metadata();
+			let mut fields = EventFields::default();
+			event.record(&mut fields);
+			let line = (
+				metadata.level().to_string(),
+				metadata.target().to_string(),
+				fields.source,
+				fields.message,
+			);
+			self.0.lock().unwrap().push(line);
+		}
+	}
+
+	/// Records the lines logged to [`CHAIN_TARGET`], other tests may log meanwhile.
+	struct CapturedRecords(Mutex<Vec<Line>>);
+
+	impl log::Log for CapturedRecords {
+		fn enabled(&self, _: &log::Metadata) -> bool {
+			true
+		}
+
+		fn log(&self, record: &log::Record) {
+			if record.target().ends_with(CHAIN_TARGET) {
+				let line = (
+					record.level().to_string(),
+					record.target().to_string(),
+					None,
+					record.args().to_string(),
+				);
+				self.0.lock().unwrap().push(line);
+			}
+		}
+
+		fn flush(&self) {}
+	}
+
+	fn line(level: &str, target: &str, source: Option<&str>, message: &str) -> Line {
+		(level.to_string(), target.to_string(), source.map(str::to_string), message.to_string())
+	}
+
+	#[test]
+	fn tracing_events_name_the_chain_in_their_source() {
+		let captured = CapturedEvents::default();
+		let _guard = tracing_subscriber::registry().with(captured.clone()).set_default();
+
+		log_lines(&platform(LogBackend::Tracing));
+
+		assert_eq!(
+			*captured.0.lock().unwrap(),
+			[line("WARN", "smoldot", Some(CHAIN_TARGET), "block-announce; peer=12D3KooW")]
+		);
+	}
+
+	#[test]
+	fn log_records_name_the_chain_in_their_target() {
+		let captured: &'static CapturedRecords =
+			Box::leak(Box::new(CapturedRecords(Mutex::new(Vec::new()))));
+		log::set_logger(captured).unwrap();
+		log::set_max_level(log::LevelFilter::Trace);
+
+		log_lines(&platform(LogBackend::Log));
+
+		assert_eq!(
+			*captured.0.lock().unwrap(),
+			[line(
+				"WARN",
+				&format!("smoldot::{CHAIN_TARGET}"),
+				None,
+				"block-announce; peer=12D3KooW"
+			)]
+		);
+	}
+
+	#[test]
+	fn key_values_are_appended_to_the_message() {
+		let (peer, block): (&dyn fmt::Display, &dyn fmt::Display) = (&"12D3KooW", &42);
+		let line = format_line("block-announce", [("peer", peer), ("block", block)].into_iter());
+		assert_eq!(line, "block-announce; peer=12D3KooW, block=42");
+		assert_eq!(format_line("started", std::iter::empty()), "started");
+	}
+
+	#[test]
+	fn levels_are_ordered_like_tracing() {
+		let config = LogConfig::default();
+		assert!(level_filter(LogLevel::Warn) <= config.max_level);
+		assert!(level_filter(LogLevel::Debug) > config.max_level);
+	}
+}
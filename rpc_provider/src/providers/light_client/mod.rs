@@ -1,36 +1,56 @@
 //! Reference: https://github.com/smol-dot/smoldot/blob/main/light-base/examples/basic.rs
 use crate::{
-	error::RpcError, primitives::RpcParams, to_json_req, types::ProviderInterface, Error, Request,
-	Result,
+	codec::{decode_response, encode_request},
+	limits::{Limits, PendingResponses},
+	no_params,
+	primitives::RpcParams,
+	rpc_params,
+	stats::ProviderStats,
+	sync::{sync_status_stream, wait_until_synced, SyncConfig, SyncStatus},
+	trace::{TraceConfig, Tracer},
+	types::{check_method, ProviderInterface},
+	ws::{buffer::NotificationBuffer, SubscriptionConfig},
+	Error, MaybeSend, Request, Result, Subscribe,
 };
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use background::{Background, ChainHandle, NewChain, ToBackground};
+pub use client::LightClient;
+pub use config::{BootNodes, ChainConfig, ClientConfig, JsonRpc};
+pub use database::load_database;
+use database::{write_database, FINALIZED_DATABASE};
+use futures::Stream;
+pub use logging::{LogBackend, LogConfig, LoggingPlatform};
+use serde::de::DeserializeOwned;
+use serde_json::value::RawValue;
 use smoldot_light::{
 	platform::{DefaultPlatform, PlatformRef},
-	AddChainSuccess, ChainId, JsonRpcResponses,
+	ChainId,
 };
 use std::{
 	fmt::Debug,
-	num::NonZeroU32,
-	sync::{Arc, Mutex},
+	marker::PhantomData,
+	path::{Path, PathBuf},
+	sync::Arc,
+	time::Duration,
+};
+use subscription::Notifications;
+pub use subscription::ScSubscription;
+use tokio::{
+	sync::{mpsc, oneshot},
+	task::JoinHandle,
 };
 
-#[derive(Clone)]
-pub struct SuccessChainConnection<P: PlatformRef>(Arc<Mutex<JsonRpcResponses<P>>>);
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct JsonRpcResponse<R>
-where
-	R: Debug,
-{
-	id: String,
-	jsonrpc: String,
-	result: Option<R>,
-	error: Option<RpcError>,
-}
+mod background;
+mod client;
+mod config;
+mod database;
+mod logging;
+mod subscription;
 
-impl<P, TChain> ProviderInterface for ScProvider<Arc<P>, TChain>
+#[cfg_attr(feature = "send", maybe_async::async_impl)]
+#[cfg_attr(not(feature = "send"), maybe_async::async_impl(?Send))]
+impl<P, TChain> ProviderInterface for ScProvider<P, TChain>
 where
-	Arc<P>: PlatformRef,
+	P: PlatformRef,
 {
 	async fn connect(&mut self) -> Result<()> {
 		return Ok(());
@@ -41,15 +61,43 @@ where
 	}
 }
 
-#[derive(Clone)]
+/// Provider backed by an embedded smoldot light client.
+///
+/// The smoldot client and its response streams are owned by a background task, clones of the
+/// provider only share a channel to that task and can be used concurrently. Providers of chains
+/// sharing a client are made by a [`LightClient`].
 pub struct ScProvider<P, TChain>
 where
 	P: PlatformRef,
 {
-	inner: Arc<Mutex<smoldot_light::Client<P, TChain>>>,
-	id: Option<ChainId>,
-	_connection: Option<SuccessChainConnection<P>>,
-	_chain_spec: Option<String>,
+	chain: ChainHandle,
+	to_background: mpsc::UnboundedSender<ToBackground>,
+	/// Also numbers the JSON-RPC requests.
+	tracer: Tracer,
+	limits: Limits,
+	subscription: SubscriptionConfig,
+	pending: PendingResponses,
+	/// See [`ChainConfig::allow_unsafe`].
+	allow_unsafe: bool,
+	_marker: PhantomData<fn() -> (P, TChain)>,
+}
+
+impl<P, TChain> Clone for ScProvider<P, TChain>
+where
+	P: PlatformRef,
+{
+	fn clone(&self) -> Self {
+		Self {
+			chain: self.chain,
+			to_background: self.to_background.clone(),
+			tracer: self.tracer.clone(),
+			limits: self.limits,
+			subscription: self.subscription,
+			pending: self.pending.clone(),
+			allow_unsafe: self.allow_unsafe,
+			_marker: PhantomData,
+		}
+	}
 }
 
 impl<P> ScProvider<Arc<P>, ()>
@@ -57,32 +105,64 @@ where
 	Arc<P>: PlatformRef,
 {
 	/// Default platform is a "plug and play" platfrom use environments of your device
+	///
+	/// The chain gets a smoldot client of its own, use a [`LightClient`] to add a relay chain and
+	/// its parachains to the same client.
 	pub fn new(
-		chain_spec: &'static str,
+		chain_spec: impl Into<String>,
+		ids: Vec<ChainId>,
+	) -> Result<ScProvider<Arc<DefaultPlatform>, ()>> {
+		let config = ChainConfig { relay_chains: ids, ..ChainConfig::default() };
+		Self::new_with_config(chain_spec, ClientConfig::default(), config)
+	}
+
+	/// Like [`Self::new`], resuming the sync from the database saved at `database` by
+	/// [`ScProvider::save_database`], see [`load_database`].
+	pub fn new_with_database(
+		chain_spec: impl Into<String>,
 		ids: Vec<ChainId>,
-	) -> ScProvider<Arc<DefaultPlatform>, ()> {
-		let (client_name, client_version) =
-			(env!("CARGO_PKG_NAME").into(), env!("CARGO_PKG_VERSION").into());
-		let platfrom = DefaultPlatform::new(client_name, client_version);
-		let client = smoldot_light::Client::new(platfrom);
-
-		let mut provider = ScProvider {
-			id: None,
-			inner: Arc::new(Mutex::new(client)),
-			_chain_spec: Some(chain_spec.to_string()),
-			_connection: None,
+		database: impl AsRef<Path>,
+	) -> Result<ScProvider<Arc<DefaultPlatform>, ()>> {
+		let config = ChainConfig {
+			relay_chains: ids,
+			database_content: load_database(database),
+			..ChainConfig::default()
 		};
+		Self::new_with_config(chain_spec, ClientConfig::default(), config)
+	}
+
+	/// Like [`Self::new`], with options other than the defaults of [`ClientConfig`] and
+	/// [`ChainConfig`].
+	pub fn new_with_config(
+		chain_spec: impl Into<String>,
+		client: ClientConfig,
+		config: ChainConfig,
+	) -> Result<ScProvider<Arc<DefaultPlatform>, ()>> {
+		let platfrom = DefaultPlatform::new(client.name, client.version);
 
 		// Add a chain to the provider
-		provider.add_chain(chain_spec.to_string(), (), Some(ids)).unwrap();
+		let chain = NewChain { chain_spec: chain_spec.into(), config, user_data: () };
+		ScProvider::<Arc<DefaultPlatform>, ()>::spawn(platfrom, chain)
+	}
 
-		return provider;
+	/// Like [`Self::new_with_config`], forwarding the logs of smoldot to `tracing` or `log`.
+	pub fn new_with_logs(
+		chain_spec: impl Into<String>,
+		client: ClientConfig,
+		config: ChainConfig,
+		logs: LogConfig,
+	) -> Result<ScProvider<LoggingPlatform<Arc<DefaultPlatform>>, ()>> {
+		let platform =
+			LoggingPlatform::new(DefaultPlatform::new(client.name, client.version), logs);
+		let chain = NewChain { chain_spec: chain_spec.into(), config, user_data: () };
+		ScProvider::spawn(platform, chain)
 	}
 }
 
 impl<P, TChain> ScProvider<Arc<P>, TChain>
 where
 	Arc<P>: PlatformRef,
+	TChain: Send + 'static,
 {
 	/// Any advance usage will likely require a custom implementation of these bindings.
 	pub fn new_with_platform(
@@ -90,90 +170,218 @@ where
 		chain_spec: String,
 		user_data: TChain,
 		ids: Vec<ChainId>,
-	) -> Self {
-		let platform = Arc::new(platform);
-		let client = smoldot_light::Client::new(platform);
-		let mut provider = ScProvider::<Arc<P>, TChain> {
-			id: None,
-			inner: Arc::new(Mutex::new(client)),
-			_chain_spec: Some(chain_spec.clone()),
-			_connection: None,
-		};
-		// Add a chain to the provider
-		provider.add_chain(chain_spec, user_data, Some(ids)).unwrap();
-
-		return provider;
+	) -> Result<Self> {
+		let config = ChainConfig { relay_chains: ids, ..ChainConfig::default() };
+		Self::new_with_platform_and_config(platform, chain_spec, config, user_data)
 	}
 
-	/// Connect to Substrate-based blockchain
-	/// - `relay_chains``: If the chain spec is for the parachain, we need to add the relaychain that parachain relies on
-	/// Because the `Client` might contain multiple different chains whose similar identifier
-	fn add_chain(
-		&mut self,
-		chain_spec: String,
+	/// Like [`Self::new_with_platform`], with options other than the defaults of
+	/// [`ChainConfig`].
+	pub fn new_with_platform_and_config(
+		platform: P,
+		chain_spec: impl Into<String>,
+		config: ChainConfig,
 		user_data: TChain,
-		relay_chains: Option<Vec<ChainId>>,
-	) -> Result<()> {
-		// Ask the client to connect to Polkadot.
-		let mut guarded_client = self.inner.lock().unwrap();
-		let add_chain_success = guarded_client
-			.add_chain(smoldot_light::AddChainConfig {
-				specification: &chain_spec,
-				// JSON RPC is always enabled in the context of RPC provider
-				json_rpc: smoldot_light::AddChainConfigJsonRpc::Enabled {
-					// Maximum number of JSON-RPC in the queue of requests waiting to be processed.
-					max_pending_requests: NonZeroU32::new(u32::max_value()).unwrap(),
-					// Maximum number of active subscriptions before new ones are automatically rejected.
-					max_subscriptions: u32::max_value(),
-				},
-				potential_relay_chains: relay_chains.unwrap_or_default().into_iter(),
-				database_content: "",
-				user_data,
-			})
-			.unwrap();
-
-		let AddChainSuccess { chain_id, json_rpc_responses } = add_chain_success;
-		// If connected chain does not respond, throw error
-		let Some(rpc_responses) = json_rpc_responses else {
-			return Err(Error::InvalidChainSpec(format!(
-				"Could not connect to the provided chain: {:?}",
-				chain_spec
-			)));
-		};
+	) -> Result<Self> {
+		// Add a chain to the provider
+		let chain = NewChain { chain_spec: chain_spec.into(), config, user_data };
+		Self::spawn(Arc::new(platform), chain)
+	}
+}
 
-		self._connection = Some(SuccessChainConnection(Arc::new(Mutex::new(rpc_responses))));
-		self.id = Some(chain_id);
+impl<P, TChain> ScProvider<P, TChain>
+where
+	P: PlatformRef,
+	TChain: Send + 'static,
+{
+	/// Creates a smoldot client, adds the chain to it and hands it over to a background task
+	/// spawned on the platform.
+	fn spawn(platform: P, chain: NewChain<TChain>) -> Result<Self> {
+		let mut background = Background::new(platform.clone());
+		let allow_unsafe = chain.config.allow_unsafe;
+		let chain = background.add_chain(chain)?;
+		// Only the providers keep the client alive from now on.
+		Ok(LightClient::spawn(platform, background).provider(chain, allow_unsafe))
+	}
+}
 
-		Ok(())
+impl<P, TChain> ScProvider<P, TChain>
+where
+	P: PlatformRef,
+{
+	pub(crate) fn from_chain(
+		chain: ChainHandle,
+		to_background: mpsc::UnboundedSender<ToBackground>,
+		allow_unsafe: bool,
+	) -> Self {
+		ScProvider {
+			chain,
+			to_background,
+			tracer: Tracer::new("light_client", format!("{:?}", chain.id), TraceConfig::default()),
+			limits: Limits::default(),
+			subscription: SubscriptionConfig::default(),
+			pending: PendingResponses::default(),
+			allow_unsafe,
+			_marker: PhantomData,
+		}
 	}
 
 	pub fn id(&self) -> ChainId {
-		return self.id.unwrap();
+		self.chain.id
+	}
+
+	/// Replaces the default [`Limits`] on the responses and notifications of smoldot.
+	pub fn with_limits(mut self, limits: Limits) -> Self {
+		self.limits = limits;
+		self
+	}
+
+	/// Replaces the default buffering of the subscriptions.
+	/// [`SubscriptionConfig::inactivity_timeout`] is not used by the light client.
+	pub fn with_subscription_config(mut self, config: SubscriptionConfig) -> Self {
+		self.subscription = config;
+		self
+	}
+
+	/// Requests, errors and bytes of the provider and its clones.
+	pub fn stats(&self) -> Arc<ProviderStats> {
+		self.tracer.stats().clone()
+	}
+
+	/// Replaces the default logging of the params and responses, see [`crate::trace`].
+	pub fn with_trace_config(mut self, config: TraceConfig) -> Self {
+		self.tracer = self.tracer.with_config(config);
+		self
+	}
+
+	/// Exports what smoldot knows about the chain, to be passed back as
+	/// [`ChainConfig::database_content`] when the chain is added again.
+	///
+	/// `max_size_bytes` bounds the export, smoldot then leaves out part of the known peers.
+	pub async fn export_database(&self, max_size_bytes: Option<u64>) -> Result<String> {
+		match max_size_bytes {
+			Some(max_size_bytes) =>
+				self.request(FINALIZED_DATABASE, rpc_params![max_size_bytes]).await,
+			None => self.request(FINALIZED_DATABASE, no_params!()).await,
+		}
+	}
+
+	/// Exports the database to `path`, from where [`ScProvider::new_with_database`] restores it.
+	pub async fn save_database(&self, path: impl AsRef<Path>) -> Result<()> {
+		let database = self.export_database(None).await?;
+		write_database(path.as_ref(), &database)
+	}
+
+	/// Saves the database to `path` every `period` until the task is aborted or the chain is
+	/// removed. Failed saves are logged and retried at the next period.
+	///
+	/// The task is spawned on `tokio`, which needs the `send` feature.
+	#[cfg(feature = "send")]
+	pub fn save_database_periodically(
+		&self,
+		path: impl Into<PathBuf>,
+		period: Duration,
+	) -> JoinHandle<()>
+	where
+		TChain: 'static,
+		P: 'static,
+	{
+		let provider = self.clone();
+		let path = path.into();
+		tokio::spawn(async move {
+			let mut interval = tokio::time::interval(period);
+			// The first tick completes at once, there is nothing new to save yet.
+			interval.tick().await;
+			loop {
+				interval.tick().await;
+				match provider.save_database(&path).await {
+					Ok(()) => {},
+					Err(Error::ConnectionClosed) => break,
+					Err(e) => tracing::warn!(
+						path = %path.display(),
+						error = ?e,
+						"failed to save the light client database"
+					),
+				}
+			}
+		})
+	}
+
+	/// Waits until smoldot is done warp-syncing the chain, before which its answers are stale.
+	pub async fn wait_until_synced(&self, config: SyncConfig) -> Result<SyncStatus> {
+		wait_until_synced(self, config).await
+	}
+
+	/// Sync progress of the chain, following its new heads and polled every `poll_interval`, see
+	/// [`sync_status_stream`].
+	pub fn sync_status(
+		&self,
+		poll_interval: Duration,
+	) -> impl Stream<Item = Result<SyncStatus>> + 'static
+	where
+		TChain: 'static,
+		P: 'static,
+	{
+		sync_status_stream(self.clone(), poll_interval)
+	}
+
+	/// Sends a request to smoldot and returns the raw JSON-RPC answer.
+	///
+	/// `notifications` receives the notifications of the subscription opened by the request, if
+	/// any.
+	async fn send_request(
+		&self,
+		id: u64,
+		method: &str,
+		params: RpcParams,
+		notifications: Option<Notifications>,
+	) -> Result<String> {
+		check_method(method, self.allow_unsafe)?;
+		let _pending = self.pending.acquire(self.limits.max_pending_responses)?;
+		let payload = encode_request(id, method, params)?;
+		let stats = self.tracer.stats();
+		stats.record_sent(payload.len());
+
+		let (send_back, response) = oneshot::channel();
+		self.to_background
+			.send(ToBackground::Request {
+				chain: self.chain,
+				id,
+				payload,
+				send_back,
+				notifications,
+			})
+			.map_err(|_| Error::ConnectionClosed)?;
+		let response = response.await.map_err(|_| Error::ConnectionClosed)??;
+		stats.record_received(response.len());
+		self.limits.check_message(response.as_bytes()).map_err(Error::LimitExceeded)?;
+		Ok(response)
+	}
+
+	/// Sends a request to smoldot without waiting for its answer, such as the unsubscribe of a
+	/// dropped [`ScSubscription`].
+	fn send_without_answer(&self, method: &str, params: RpcParams) {
+		let id = self.tracer.next_id();
+		let Ok(payload) = encode_request(id, method, params) else { return };
+		let (send_back, _) = oneshot::channel();
+		let _ = self.to_background.send(ToBackground::Request {
+			chain: self.chain,
+			id,
+			payload,
+			send_back,
+			notifications: None,
+		});
 	}
 }
 
-#[maybe_async::async_impl(?Send)]
-impl<P, TChain> Request for ScProvider<Arc<P>, TChain>
+#[cfg_attr(feature = "send", maybe_async::async_impl)]
+#[cfg_attr(not(feature = "send"), maybe_async::async_impl(?Send))]
+impl<P, TChain> Request for ScProvider<P, TChain>
 where
-	Arc<P>: PlatformRef,
+	P: PlatformRef,
 {
-	async fn request_raw(&self, method: &str, params: RpcParams) -> Result<String> {
-		let mut guarded_client = self.inner.lock().unwrap();
-		match &self._connection {
-			Some(SuccessChainConnection(json_rpc_responses)) => {
-				let payload = to_json_req(method, params).unwrap();
-
-				guarded_client
-					.json_rpc_request(payload, self.id.unwrap())
-					.map_err(|e| Error::Client(Box::new(e)))
-					.unwrap();
-
-				let mut guarded_responses = json_rpc_responses.lock().unwrap();
-				let next_data = guarded_responses.next().await.unwrap();
-				return Ok(next_data);
-			},
-			None => return Err(Error::ConnectionClosed),
-		}
+	async fn request_raw(&self, method: &str, params: RpcParams) -> Result<Box<RawValue>> {
+		self.request(method, params).await
 	}
 
 	async fn request<R: DeserializeOwned + Debug>(
@@ -181,11 +389,42 @@ where
 		method: &str,
 		params: RpcParams,
 	) -> Result<R> {
-		let raw_response = self.request_raw(method, params).await.unwrap();
-		let parsed_response = serde_json::from_str::<JsonRpcResponse<R>>(&raw_response).unwrap();
-		match parsed_response.result {
-			Some(data) => return Ok(data),
-			None => return Err(Error::JsonRpcError(parsed_response.error.unwrap_or_default())),
-		}
+		let id = self.tracer.next_id();
+		let call = self.tracer.request(method, id, &params);
+		call.run(async {
+			let response = self.send_request(id, method, params, None).await?;
+			decode_response(response.as_bytes())
+		})
+		.await
+	}
+}
+
+#[cfg_attr(feature = "send", maybe_async::async_impl)]
+#[cfg_attr(not(feature = "send"), maybe_async::async_impl(?Send))]
+impl<P, TChain> Subscribe for ScProvider<P, TChain>
+where
+	P: PlatformRef,
+{
+	type Subscription<Notification> = ScSubscription<P, TChain, Notification> where Notification: DeserializeOwned + MaybeSend;
+
+	async fn subscribe<Notification: DeserializeOwned + MaybeSend>(
+		&self,
+		sub: &str,
+		params: RpcParams,
+		unsub: &str,
+	) -> Result<Self::Subscription<Notification>> {
+		let id = self.tracer.next_id();
+		let call = self.tracer.subscribe(sub, id, &params);
+		call.run_without_response(async {
+			let buffer = Arc::new(NotificationBuffer::new(
+				self.subscription.buffer_capacity,
+				self.subscription.lag_policy,
+			));
+			let notifications = Notifications::new(buffer.clone(), self.limits, self.stats());
+			let response = self.send_request(id, sub, params, Some(notifications)).await?;
+			let subscription = decode_response::<Box<RawValue>>(response.as_bytes())?;
+			Ok(ScSubscription::new(self.clone(), buffer, subscription, unsub))
+		})
+		.await
 	}
 }
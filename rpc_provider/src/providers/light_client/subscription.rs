@@ -0,0 +1,169 @@
+//! Subscriptions opened through an [`ScProvider`].
+use super::ScProvider;
+use crate::{
+	codec::decode_notification,
+	limits::{Limit, Limits, NotificationRate},
+	rpc_params,
+	stats::{ActiveSubscription, ProviderStats},
+	ws::buffer::{Failure, NotificationBuffer, Sink},
+	HandleSubscription, MaybeSend, Request, Result,
+};
+use futures::{Stream, StreamExt};
+use serde::de::DeserializeOwned;
+use serde_json::{value::RawValue, Value};
+use smoldot_light::platform::PlatformRef;
+use std::{
+	marker::PhantomData,
+	pin::Pin,
+	sync::Arc,
+	task::{Context, Poll},
+	time::Instant,
+};
+
+/// Hands the notifications of a subscription over to its [`ScSubscription`], within the
+/// [`Limits`] of the provider.
+///
+/// Owned by the background task, which never waits on the consumer. Dropped before the
+/// subscription ended, for instance when the chain is removed, it ends the subscription with
+/// [`crate::Error::ConnectionClosed`].
+pub(crate) struct Notifications {
+	sink: Sink,
+	limits: Limits,
+	rate: Option<NotificationRate>,
+	stats: Arc<ProviderStats>,
+	ended: bool,
+}
+
+impl Notifications {
+	/// Needs a `tokio` runtime for a [`crate::ws::LagPolicy::Block`] buffer, so it is made by the
+	/// provider rather than the background task.
+	pub(crate) fn new(
+		buffer: Arc<NotificationBuffer>,
+		limits: Limits,
+		stats: Arc<ProviderStats>,
+	) -> Self {
+		Self {
+			sink: Sink::new(buffer),
+			rate: limits.max_notifications_per_second.map(NotificationRate::new),
+			limits,
+			stats,
+			ended: false,
+		}
+	}
+
+	/// Hands a JSON-RPC notification over. Returns `false` once the subscription ended, after
+	/// which the notifications are to be dropped.
+	pub(crate) fn push(&mut self, notification: &str) -> bool {
+		self.stats.record_received(notification.len());
+		match self.decode(notification.as_bytes()) {
+			Ok(result) => self.ended = !self.sink.push(result),
+			Err(failure) => {
+				self.sink.close(Some(failure));
+				self.ended = true;
+			},
+		}
+		!self.ended
+	}
+
+	fn decode(&mut self, notification: &[u8]) -> core::result::Result<Value, Failure> {
+		self.limits.check_message(notification).map_err(Failure::LimitExceeded)?;
+		if self.rate.as_mut().is_some_and(|rate| !rate.record(Instant::now())) {
+			return Err(Failure::LimitExceeded(Limit::NotificationRate))
+		}
+		decode_notification(notification).map_err(|e| Failure::Notification(Arc::new(e)))
+	}
+}
+
+impl Drop for Notifications {
+	fn drop(&mut self) {
+		if !self.ended {
+			self.sink.close(Some(Failure::Disconnected));
+		}
+	}
+}
+
+/// Handle of a subscription opened through an [`ScProvider`], usable as a [`Stream`] of
+/// notifications.
+///
+/// Notifications are buffered as set by [`ScProvider::with_subscription_config`] and checked
+/// against [`ScProvider::with_limits`]. A notification breaking the limits ends the stream with
+/// [`crate::Error::LimitExceeded`], and removing the chain ends it with
+/// [`crate::Error::ConnectionClosed`], both after the buffered notifications. Dropping the handle
+/// unsubscribes from smoldot, [`HandleSubscription::unsubscribe`] can be used to wait until
+/// smoldot acknowledged it.
+pub struct ScSubscription<P, TChain, Notification>
+where
+	P: PlatformRef,
+{
+	buffer: Arc<NotificationBuffer>,
+	provider: ScProvider<P, TChain>,
+	/// The unsubscribe method and the id smoldot gave the subscription, until unsubscribed.
+	unsubscribe: Option<(String, Box<RawValue>)>,
+	_active: ActiveSubscription,
+	_marker: PhantomData<fn() -> Notification>,
+}
+
+impl<P, TChain, Notification> ScSubscription<P, TChain, Notification>
+where
+	P: PlatformRef,
+{
+	pub(crate) fn new(
+		provider: ScProvider<P, TChain>,
+		buffer: Arc<NotificationBuffer>,
+		id: Box<RawValue>,
+		unsub: &str,
+	) -> Self {
+		Self {
+			buffer,
+			_active: provider.tracer.stats().subscription_opened(),
+			provider,
+			unsubscribe: Some((unsub.to_string(), id)),
+			_marker: PhantomData,
+		}
+	}
+}
+
+impl<P, TChain, Notification> Stream for ScSubscription<P, TChain, Notification>
+where
+	P: PlatformRef,
+	Notification: DeserializeOwned,
+{
+	type Item = Result<Notification>;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		self.buffer.poll_next(cx).map(|notification| {
+			notification.map(|notification| Ok(serde_json::from_value(notification?)?))
+		})
+	}
+}
+
+impl<P, TChain, Notification> Drop for ScSubscription<P, TChain, Notification>
+where
+	P: PlatformRef,
+{
+	fn drop(&mut self) {
+		self.buffer.close_consumer();
+		if let Some((unsub, id)) = self.unsubscribe.take() {
+			self.provider.send_without_answer(&unsub, rpc_params![id]);
+		}
+	}
+}
+
+#[cfg_attr(feature = "send", maybe_async::async_impl)]
+#[cfg_attr(not(feature = "send"), maybe_async::async_impl(?Send))]
+impl<P, TChain, Notification> HandleSubscription<Notification>
+	for ScSubscription<P, TChain, Notification>
+where
+	P: PlatformRef,
+	Notification: DeserializeOwned + MaybeSend,
+{
+	async fn next(&mut self) -> Option<Result<Notification>> {
+		StreamExt::next(self).await
+	}
+
+	async fn unsubscribe(mut self) -> Result<()> {
+		self.buffer.close_consumer();
+		let Some((unsub, id)) = self.unsubscribe.take() else { return Ok(()) };
+		self.provider.request::<bool>(&unsub, rpc_params![id]).await.map(|_| ())
+	}
+}
@@ -0,0 +1,238 @@
+//! Bounds on what a node may send to a provider.
+//!
+//! Public nodes are not trusted: a hostile or broken node could otherwise send huge or deeply
+//! nested JSON, flood a subscription or leave requests unanswered forever.
+use crate::{Error, Result};
+use std::{
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	},
+	time::{Duration, Instant},
+};
+
+/// Default largest message, the default maximum response size of `jsonrpsee`.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 10 * 1024 * 1024;
+
+/// Default deepest nesting of JSON arrays and objects.
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 64;
+
+/// Default most requests waiting for their response, the default of `jsonrpsee`.
+pub const DEFAULT_MAX_PENDING_RESPONSES: usize = 256;
+
+/// Limits of a provider, see [`crate::ws::WsConfig::limits`], [`crate::http::HttpConfig::limits`]
+/// and [`crate::light_client::ScProvider::with_limits`]. Breaking one fails with
+/// [`Error::LimitExceeded`].
+///
+/// A WebSocket message breaking the size or nesting limits closes the connection: the pending
+/// requests fail with the limit, the subscriptions end with [`Error::ConnectionClosed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+	/// Largest response or notification, in bytes, after decompression.
+	pub max_message_size: usize,
+	/// Deepest nesting of JSON arrays and objects in a response or notification.
+	pub max_nesting_depth: usize,
+	/// Most notifications a subscription may receive within a second. The subscription ends
+	/// once exceeded. Unlimited by default.
+	pub max_notifications_per_second: Option<u32>,
+	/// Most requests of the provider waiting for their response. Further requests are refused
+	/// until a response comes in.
+	pub max_pending_responses: usize,
+}
+
+impl Default for Limits {
+	fn default() -> Self {
+		Self {
+			max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+			max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+			max_notifications_per_second: None,
+			max_pending_responses: DEFAULT_MAX_PENDING_RESPONSES,
+		}
+	}
+}
+
+/// The limit reported by [`Error::LimitExceeded`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+	MessageSize,
+	NestingDepth,
+	NotificationRate,
+	PendingResponses,
+}
+
+impl Limits {
+	/// Checks the size and the nesting depth of a message, before it is parsed.
+	pub(crate) fn check_message(&self, message: &[u8]) -> core::result::Result<(), Limit> {
+		if message.len() > self.max_message_size {
+			return Err(Limit::MessageSize)
+		}
+		if nesting_depth_exceeds(message, self.max_nesting_depth) {
+			return Err(Limit::NestingDepth)
+		}
+		Ok(())
+	}
+}
+
+/// Whether the arrays and objects of `json` are nested deeper than `max`.
+///
+/// Only brackets outside of strings are counted, the JSON is not validated otherwise.
+fn nesting_depth_exceeds(json: &[u8], max: usize) -> bool {
+	let mut depth = 0usize;
+	let mut in_string = false;
+	let mut escaped = false;
+	for &byte in json {
+		if in_string {
+			match byte {
+				_ if escaped => escaped = false,
+				b'\\' => escaped = true,
+				b'"' => in_string = false,
+				_ => (),
+			}
+			continue;
+		}
+		match byte {
+			b'"' => in_string = true,
+			b'[' | b'{' => {
+				depth += 1;
+				if depth > max {
+					return true;
+				}
+			},
+			b']' | b'}' => depth = depth.saturating_sub(1),
+			_ => (),
+		}
+	}
+	false
+}
+
+/// Notifications received by a subscription within the current second.
+#[derive(Debug)]
+pub(crate) struct NotificationRate {
+	max_per_second: u32,
+	window_start: Instant,
+	received: u32,
+}
+
+impl NotificationRate {
+	pub(crate) fn new(max_per_second: u32) -> Self {
+		Self { max_per_second, window_start: Instant::now(), received: 0 }
+	}
+
+	/// Counts a notification received at `now`, returns `false` once the rate is exceeded.
+	pub(crate) fn record(&mut self, now: Instant) -> bool {
+		if now.duration_since(self.window_start) >= Duration::from_secs(1) {
+			self.window_start = now;
+			self.received = 0;
+		}
+		self.received = self.received.saturating_add(1);
+		self.received <= self.max_per_second
+	}
+}
+
+/// Requests waiting for their response, for providers tracking them on their own. Clones share
+/// the same count.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PendingResponses {
+	count: Arc<AtomicUsize>,
+}
+
+impl PendingResponses {
+	/// Counts a request as pending until the returned guard is dropped.
+	pub(crate) fn acquire(&self, max: usize) -> Result<PendingGuard> {
+		let acquired = self.count.fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+			(count < max).then_some(count + 1)
+		});
+		match acquired {
+			Ok(_) => Ok(PendingGuard { count: self.count.clone() }),
+			Err(_) => Err(Error::LimitExceeded(Limit::PendingResponses)),
+		}
+	}
+}
+
+pub(crate) struct PendingGuard {
+	count: Arc<AtomicUsize>,
+}
+
+impl Drop for PendingGuard {
+	fn drop(&mut self) {
+		self.count.fetch_sub(1, Ordering::AcqRel);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use proptest::prelude::*;
+	use serde_json::Value;
+
+	#[test]
+	fn brackets_in_strings_are_not_counted() {
+		assert!(!nesting_depth_exceeds(br#"{"a":[["[[[{{{"]]}"#, 3));
+		assert!(!nesting_depth_exceeds(br#"["\"[[[[", "\\"]"#, 1));
+		assert!(nesting_depth_exceeds(br#"["\\", [[]]]"#, 2));
+	}
+
+	#[test]
+	fn notification_rate_is_counted_per_second() {
+		let start = Instant::now();
+		let mut rate = NotificationRate { max_per_second: 2, window_start: start, received: 0 };
+		assert!(rate.record(start));
+		assert!(rate.record(start + Duration::from_millis(500)));
+		assert!(!rate.record(start + Duration::from_millis(999)));
+		assert!(rate.record(start + Duration::from_secs(1)));
+	}
+
+	/// Arbitrary JSON, with strings full of brackets, quotes and escapes.
+	fn json() -> impl Strategy<Value = Value> {
+		let leaf = prop_oneof![
+			Just(Value::Null),
+			any::<bool>().prop_map(Value::from),
+			any::<i64>().prop_map(Value::from),
+			r#"[\[\]{}"\\a-z]{0,8}"#.prop_map(Value::from),
+		];
+		leaf.prop_recursive(12, 256, 4, |inner| {
+			prop_oneof![
+				prop::collection::vec(inner.clone(), 0..4).prop_map(Value::Array),
+				prop::collection::btree_map(r#"[\[{"a-z]{0,4}"#, inner, 0..4)
+					.prop_map(|map| Value::Object(map.into_iter().collect())),
+			]
+		})
+	}
+
+	fn depth(value: &Value) -> usize {
+		match value {
+			Value::Array(values) => 1 + values.iter().map(depth).max().unwrap_or(0),
+			Value::Object(map) => 1 + map.values().map(depth).max().unwrap_or(0),
+			_ => 0,
+		}
+	}
+
+	proptest! {
+		#[test]
+		fn nesting_depth_of_arbitrary_json(value in json(), max in 0usize..14) {
+			let json = serde_json::to_vec(&value).unwrap();
+			prop_assert_eq!(nesting_depth_exceeds(&json, max), depth(&value) > max);
+		}
+
+		#[test]
+		fn arbitrary_messages_are_checked(
+			message in prop::collection::vec(any::<u8>(), 0..512),
+			max_message_size in 0usize..512,
+			max_nesting_depth in 0usize..8,
+		) {
+			let limits = Limits { max_message_size, max_nesting_depth, ..Default::default() };
+			let result = limits.check_message(&message);
+			prop_assert_eq!(result == Err(Limit::MessageSize), message.len() > max_message_size);
+		}
+	}
+
+	#[test]
+	fn pending_responses_are_released() {
+		let pending = PendingResponses::default();
+		let first = pending.acquire(1).unwrap();
+		let error = pending.clone().acquire(1).err().unwrap();
+		assert!(matches!(error, Error::LimitExceeded(Limit::PendingResponses)));
+		drop(first);
+		assert!(pending.acquire(1).is_ok());
+	}
+}
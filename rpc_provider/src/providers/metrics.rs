@@ -0,0 +1,343 @@
+//! Export of the [`ProviderStats`] of providers to Prometheus, behind the `prometheus` feature.
+//!
+//! A [`ProviderCollector`] registers the stats of a provider in a [`prometheus::Registry`]:
+//!
+//! | Metric | Type | Labels |
+//! | --- | --- | --- |
+//! | `rpc_provider_requests_total` | counter | `method`, `outcome` |
+//! | `rpc_provider_request_duration_seconds` | histogram | `method` |
+//! | `rpc_provider_errors_total` | counter | `class` |
+//! | `rpc_provider_reconnects_total` | counter | |
+//! | `rpc_provider_active_subscriptions` | gauge | |
+//! | `rpc_provider_sent_bytes_total` | counter | |
+//! | `rpc_provider_received_bytes_total` | counter | |
+//!
+//! Methods beyond [`crate::stats::MAX_UNLISTED_METHODS`] share the `other` method label. Every
+//! metric is labelled with the name of the provider as well. The registry can be scraped as
+//! usual, or rendered as OpenMetrics text with [`encode_openmetrics`].
+use crate::stats::{ErrorClass, ProviderStats};
+use prometheus::{
+	core::{Collector, Desc},
+	proto::{self, LabelPair, Metric, MetricFamily, MetricType},
+};
+use std::{collections::HashMap, fmt::Write, sync::Arc};
+
+const REQUESTS: &str = "rpc_provider_requests_total";
+const REQUEST_DURATION: &str = "rpc_provider_request_duration_seconds";
+const ERRORS: &str = "rpc_provider_errors_total";
+const RECONNECTS: &str = "rpc_provider_reconnects_total";
+const ACTIVE_SUBSCRIPTIONS: &str = "rpc_provider_active_subscriptions";
+const SENT_BYTES: &str = "rpc_provider_sent_bytes_total";
+const RECEIVED_BYTES: &str = "rpc_provider_received_bytes_total";
+
+/// Collects the stats of a provider, see the [module documentation](self).
+pub struct ProviderCollector {
+	stats: Arc<ProviderStats>,
+	provider: String,
+	descs: Vec<Desc>,
+}
+
+impl ProviderCollector {
+	/// Collects `stats` with the `provider` label, which tells apart the providers registered in
+	/// the same registry.
+	pub fn new(stats: Arc<ProviderStats>, provider: &str) -> Self {
+		let descs = [
+			(
+				REQUESTS,
+				"Requests and subscriptions sent, by method and outcome.",
+				&["method", "outcome"][..],
+			),
+			(
+				REQUEST_DURATION,
+				"Latency of the requests and subscriptions, by method.",
+				&["method"],
+			),
+			(ERRORS, "Failed requests, subscriptions and connects, by class.", &["class"]),
+			(RECONNECTS, "Successful connects, but the first one.", &[]),
+			(ACTIVE_SUBSCRIPTIONS, "Subscription handles not dropped yet.", &[]),
+			(SENT_BYTES, "Bytes of the JSON-RPC messages sent.", &[]),
+			(RECEIVED_BYTES, "Bytes of the JSON-RPC messages received, after decompression.", &[]),
+		]
+		.into_iter()
+		.map(|(name, help, labels)| {
+			let labels = labels.iter().map(|label| label.to_string()).collect();
+			let provider = HashMap::from([("provider".to_string(), provider.to_string())]);
+			Desc::new(name.into(), help.into(), labels, provider).expect("Metrics are valid")
+		})
+		.collect();
+		Self { stats, provider: provider.into(), descs }
+	}
+
+	fn family(&self, name: &str, kind: MetricType, metrics: Vec<Metric>) -> MetricFamily {
+		let desc = self.descs.iter().find(|desc| desc.fq_name == name).expect("Described above");
+		let mut family = MetricFamily::default();
+		family.set_name(name.into());
+		family.set_help(desc.help.clone());
+		family.set_field_type(kind);
+		family.set_metric(metrics);
+		family
+	}
+
+	fn metric(&self, labels: &[(&str, &str)]) -> Metric {
+		let mut labels = labels.to_vec();
+		labels.push(("provider", &self.provider));
+		labels.sort();
+		let labels: Vec<_> = labels
+			.into_iter()
+			.map(|(name, value)| {
+				let mut label = LabelPair::default();
+				label.set_name(name.to_string());
+				label.set_value(value.to_string());
+				label
+			})
+			.collect();
+		let mut metric = Metric::default();
+		metric.set_label(labels);
+		metric
+	}
+
+	fn counter(&self, labels: &[(&str, &str)], value: u64) -> Metric {
+		let mut metric = self.metric(labels);
+		let mut counter = proto::Counter::default();
+		counter.set_value(value as f64);
+		metric.set_counter(counter);
+		metric
+	}
+}
+
+impl Collector for ProviderCollector {
+	fn desc(&self) -> Vec<&Desc> {
+		self.descs.iter().collect()
+	}
+
+	fn collect(&self) -> Vec<MetricFamily> {
+		let methods = self.stats.methods();
+		let requests = methods
+			.iter()
+			.flat_map(|(method, stats)| {
+				[
+					self.counter(
+						&[("method", method), ("outcome", "ok")],
+						stats.requests - stats.errors,
+					),
+					self.counter(&[("method", method), ("outcome", "error")], stats.errors),
+				]
+			})
+			.collect();
+		let durations = methods
+			.iter()
+			.map(|(method, stats)| {
+				let mut histogram = proto::Histogram::default();
+				histogram.set_sample_count(stats.latency.count());
+				histogram.set_sample_sum(stats.latency.sum());
+				let buckets: Vec<_> = stats
+					.latency
+					.buckets()
+					.map(|(upper_bound, count)| {
+						let mut bucket = proto::Bucket::default();
+						bucket.set_upper_bound(upper_bound);
+						bucket.set_cumulative_count(count);
+						bucket
+					})
+					.collect();
+				histogram.set_bucket(buckets);
+				let mut metric = self.metric(&[("method", method)]);
+				metric.set_histogram(histogram);
+				metric
+			})
+			.collect();
+		let errors = ErrorClass::ALL
+			.iter()
+			.map(|class| self.counter(&[("class", class.as_str())], self.stats.errors(*class)))
+			.collect();
+		let mut active_subscriptions = self.metric(&[]);
+		let mut gauge = proto::Gauge::default();
+		gauge.set_value(self.stats.active_subscriptions() as f64);
+		active_subscriptions.set_gauge(gauge);
+
+		vec![
+			self.family(REQUESTS, MetricType::COUNTER, requests),
+			self.family(REQUEST_DURATION, MetricType::HISTOGRAM, durations),
+			self.family(ERRORS, MetricType::COUNTER, errors),
+			self.family(
+				RECONNECTS,
+				MetricType::COUNTER,
+				vec![self.counter(&[], self.stats.reconnects())],
+			),
+			self.family(ACTIVE_SUBSCRIPTIONS, MetricType::GAUGE, vec![active_subscriptions]),
+			self.family(
+				SENT_BYTES,
+				MetricType::COUNTER,
+				vec![self.counter(&[], self.stats.sent_bytes())],
+			),
+			self.family(
+				RECEIVED_BYTES,
+				MetricType::COUNTER,
+				vec![self.counter(&[], self.stats.received_bytes())],
+			),
+		]
+	}
+}
+
+/// Renders metric families, such as the output of [`prometheus::Registry::gather`], as
+/// OpenMetrics text.
+pub fn encode_openmetrics(families: &[MetricFamily]) -> String {
+	let mut text = String::new();
+	for family in families {
+		let name = family.get_name();
+		let (kind, name) = match family.get_field_type() {
+			// The `_total` suffix belongs to the samples of a counter, not to its name.
+			MetricType::COUNTER => ("counter", name.strip_suffix("_total").unwrap_or(name)),
+			MetricType::GAUGE => ("gauge", name),
+			MetricType::HISTOGRAM => ("histogram", name),
+			MetricType::SUMMARY => ("summary", name),
+			MetricType::UNTYPED => ("unknown", name),
+		};
+		let _ = writeln!(text, "# TYPE {name} {kind}");
+		let _ = writeln!(text, "# HELP {name} {}", escape(family.get_help()));
+		for metric in family.get_metric() {
+			let labels = metric.get_label();
+			match family.get_field_type() {
+				MetricType::COUNTER => sample(
+					&mut text,
+					name,
+					"_total",
+					labels,
+					None,
+					metric.get_counter().get_value(),
+				),
+				MetricType::GAUGE =>
+					sample(&mut text, name, "", labels, None, metric.get_gauge().get_value()),
+				#[allow(deprecated)]
+				MetricType::UNTYPED =>
+					sample(&mut text, name, "", labels, None, metric.get_untyped().get_value()),
+				MetricType::HISTOGRAM => {
+					let histogram = metric.get_histogram();
+					for bucket in histogram.get_bucket() {
+						let le = ("le", float(bucket.get_upper_bound()));
+						let count = bucket.get_cumulative_count() as f64;
+						sample(&mut text, name, "_bucket", labels, Some(le), count);
+					}
+					let count = histogram.get_sample_count() as f64;
+					sample(&mut text, name, "_bucket", labels, Some(("le", "+Inf".into())), count);
+					sample(&mut text, name, "_count", labels, None, count);
+					sample(&mut text, name, "_sum", labels, None, histogram.get_sample_sum());
+				},
+				MetricType::SUMMARY => {
+					let summary = metric.get_summary();
+					for quantile in summary.get_quantile() {
+						let label = ("quantile", float(quantile.get_quantile()));
+						sample(&mut text, name, "", labels, Some(label), quantile.get_value());
+					}
+					let count = summary.get_sample_count() as f64;
+					sample(&mut text, name, "_count", labels, None, count);
+					sample(&mut text, name, "_sum", labels, None, summary.get_sample_sum());
+				},
+			}
+		}
+	}
+	text.push_str("# EOF\n");
+	text
+}
+
+fn sample(
+	text: &mut String,
+	name: &str,
+	suffix: &str,
+	labels: &[LabelPair],
+	extra: Option<(&str, String)>,
+	value: f64,
+) {
+	let labels: Vec<_> = labels
+		.iter()
+		.map(|label| (label.get_name(), escape(label.get_value())))
+		.chain(extra)
+		.map(|(name, value)| format!("{name}=\"{value}\""))
+		.collect();
+	let labels =
+		if labels.is_empty() { String::new() } else { format!("{{{}}}", labels.join(",")) };
+	let _ = writeln!(text, "{name}{suffix}{labels} {}", float(value));
+}
+
+/// Formats a float as OpenMetrics expects it, `1.0` rather than `1`.
+fn float(value: f64) -> String {
+	match value {
+		_ if value == f64::INFINITY => "+Inf".into(),
+		_ if value == f64::NEG_INFINITY => "-Inf".into(),
+		_ if value.is_finite() && value.fract() == 0.0 => format!("{value:.1}"),
+		_ => value.to_string(),
+	}
+}
+
+fn escape(value: &str) -> String {
+	value.replace('\\', r"\\").replace('"', "\\\"").replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{stats::ActiveSubscription, Error};
+	use prometheus::Registry;
+	use std::time::Duration;
+
+	/// Returns a registry of two providers, and an active subscription of the first one.
+	fn registry() -> (Registry, ActiveSubscription) {
+		let stats = Arc::new(ProviderStats::default());
+		stats.record_call("chain_getHeader", Duration::from_millis(20), None);
+		stats.record_call(
+			"chain_getHeader",
+			Duration::from_secs(2),
+			Some(&Error::ConnectionClosed),
+		);
+		stats.record_connect(true, None);
+		stats.record_sent(100);
+		stats.record_received(250);
+		let subscription = stats.subscription_opened();
+
+		let registry = Registry::new();
+		registry
+			.register(Box::new(ProviderCollector::new(stats.clone(), "relay")))
+			.unwrap();
+		let other = Arc::new(ProviderStats::default());
+		registry.register(Box::new(ProviderCollector::new(other, "para"))).unwrap();
+		(registry, subscription)
+	}
+
+	#[test]
+	fn stats_are_collected_per_provider() {
+		let (registry, _subscription) = registry();
+		let families = registry.gather();
+		let requests = families.iter().find(|family| family.get_name() == REQUESTS).unwrap();
+		// Only the relay provider sent a request.
+		assert_eq!(requests.get_metric().len(), 2);
+		let errors = families.iter().find(|family| family.get_name() == ERRORS).unwrap();
+		assert_eq!(errors.get_metric().len(), 2 * ErrorClass::ALL.len());
+
+		let text = encode_openmetrics(&families);
+		for line in [
+			"# TYPE rpc_provider_requests counter",
+			r#"rpc_provider_requests_total{method="chain_getHeader",outcome="error",provider="relay"} 1.0"#,
+			r#"rpc_provider_request_duration_seconds_bucket{method="chain_getHeader",provider="relay",le="0.025"} 1.0"#,
+			r#"rpc_provider_request_duration_seconds_bucket{method="chain_getHeader",provider="relay",le="+Inf"} 2.0"#,
+			r#"rpc_provider_request_duration_seconds_sum{method="chain_getHeader",provider="relay"} 2.02"#,
+			r#"rpc_provider_errors_total{class="connection",provider="relay"} 1.0"#,
+			r#"rpc_provider_reconnects_total{provider="relay"} 1.0"#,
+			"# TYPE rpc_provider_active_subscriptions gauge",
+			r#"rpc_provider_active_subscriptions{provider="relay"} 1.0"#,
+			r#"rpc_provider_active_subscriptions{provider="para"} 0.0"#,
+			r#"rpc_provider_sent_bytes_total{provider="relay"} 100.0"#,
+			r#"rpc_provider_received_bytes_total{provider="relay"} 250.0"#,
+		] {
+			assert!(text.lines().any(|l| l == line), "Missing {line} in:\n{text}");
+		}
+		assert!(text.ends_with("# EOF\n"));
+	}
+
+	#[test]
+	fn label_values_and_help_are_escaped() {
+		assert_eq!(escape("a\"b\\c\nd"), r#"a\"b\\c\nd"#);
+		assert_eq!(float(1.0), "1.0");
+		assert_eq!(float(0.005), "0.005");
+		assert_eq!(float(f64::INFINITY), "+Inf");
+	}
+}
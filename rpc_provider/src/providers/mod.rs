@@ -1,4 +1,15 @@
+pub mod coalesce;
+pub mod compression;
 pub mod http;
 pub mod light_client;
+pub mod limits;
+#[cfg(feature = "prometheus")]
+pub mod metrics;
+pub mod proxy;
+pub mod stats;
+pub mod sync;
+#[cfg(feature = "__tls")]
+pub mod tls;
+pub mod trace;
 pub mod types;
 pub mod ws;
@@ -0,0 +1,319 @@
+//! Counters of a provider: requests and their latency by method, errors by class, reconnects,
+//! active subscriptions and bytes sent and received.
+//!
+//! Every provider records them, clones of a provider share its counters. The `prometheus`
+//! feature exports them, see `crate::metrics`.
+use crate::Error;
+use std::{
+	collections::BTreeMap,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc, Mutex,
+	},
+	time::Duration,
+};
+use types_support::metadata::v15::{kusama_rpc::KusamaRpcMethod, polkadot_rpc::PolkadotRpcMethod};
+
+/// Upper bounds of the buckets of the latency histograms, in seconds. The Prometheus defaults.
+pub const LATENCY_BUCKETS: [f64; 11] =
+	[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Most methods counted under their own name besides the ones of [`PolkadotRpcMethod`] and
+/// [`KusamaRpcMethod`]. Calls to further methods are counted under [`OTHER_METHODS`], which keeps
+/// the number of `method` labels of the Prometheus metrics bounded.
+pub const MAX_UNLISTED_METHODS: usize = 64;
+
+/// Name the methods beyond [`MAX_UNLISTED_METHODS`] are counted under.
+pub const OTHER_METHODS: &str = "other";
+
+/// Kind of a failed call, see [`ErrorClass::of`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ErrorClass {
+	/// Error answered by the node.
+	Rpc,
+	/// Response which could not be decoded.
+	Decode,
+	/// Failed or lost connection.
+	Connection,
+	/// One of the [`crate::limits::Limits`] was exceeded, or a subscription lagged.
+	Limit,
+	/// Call refused by the provider itself, such as an unsafe method.
+	Refused,
+	Other,
+}
+
+impl ErrorClass {
+	pub const ALL: [ErrorClass; 6] =
+		[Self::Rpc, Self::Decode, Self::Connection, Self::Limit, Self::Refused, Self::Other];
+
+	pub fn of(error: &Error) -> Self {
+		match error {
+			Error::JsonRpcError(_) => Self::Rpc,
+			Error::SerdeJson(_) => Self::Decode,
+			Error::MpscSend(_)
+			| Error::RecvError(_)
+			| Error::Io(_)
+			| Error::Tls(_)
+			| Error::Proxy(_)
+			| Error::MaxConnectionAttemptsExceeded
+			| Error::ConnectionClosed => Self::Connection,
+			Error::LimitExceeded(_) | Error::SubscriptionLagged(_) => Self::Limit,
+			Error::UnsafeMethod(_) | Error::JsonRpcDisabled => Self::Refused,
+			Error::Shared(error) => Self::of(error),
+			Error::ExtrinsicFailed(_)
+			| Error::InvalidUrl(_)
+			| Error::InvalidChainSpec(_)
+			| Error::SyncTimeout(_)
+			| Error::Client(_) => Self::Other,
+		}
+	}
+
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Rpc => "rpc",
+			Self::Decode => "decode",
+			Self::Connection => "connection",
+			Self::Limit => "limit",
+			Self::Refused => "refused",
+			Self::Other => "other",
+		}
+	}
+}
+
+/// Latency histogram, over the [`LATENCY_BUCKETS`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Histogram {
+	/// Observations at most as long as each bucket.
+	buckets: [u64; LATENCY_BUCKETS.len()],
+	count: u64,
+	sum: f64,
+}
+
+impl Histogram {
+	pub fn count(&self) -> u64 {
+		self.count
+	}
+
+	/// Sum of the observations, in seconds.
+	pub fn sum(&self) -> f64 {
+		self.sum
+	}
+
+	/// Upper bound of each bucket, in seconds, with the observations at most that long.
+	pub fn buckets(&self) -> impl Iterator<Item = (f64, u64)> + '_ {
+		LATENCY_BUCKETS.into_iter().zip(self.buckets.iter().copied())
+	}
+
+	fn observe(&mut self, seconds: f64) {
+		for (bound, count) in LATENCY_BUCKETS.iter().zip(&mut self.buckets) {
+			if seconds <= *bound {
+				*count += 1;
+			}
+		}
+		self.count += 1;
+		self.sum += seconds;
+	}
+}
+
+/// Requests of a method, subscriptions included.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MethodStats {
+	pub requests: u64,
+	/// Failed requests, also counted in `requests`.
+	pub errors: u64,
+	pub latency: Histogram,
+}
+
+/// Counters of a provider, see the [module documentation](self).
+///
+/// Bytes are counted per JSON-RPC message, after decompression, see
+/// [`crate::compression::CompressionStats`] for the bytes received on the wire. WebSocket and HTTP
+/// providers built out of a custom client do not count them.
+#[derive(Debug, Default)]
+pub struct ProviderStats {
+	methods: Mutex<Methods>,
+	errors: [AtomicU64; ErrorClass::ALL.len()],
+	reconnects: AtomicU64,
+	active_subscriptions: AtomicU64,
+	sent: AtomicU64,
+	received: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct Methods {
+	stats: BTreeMap<String, MethodStats>,
+	/// Methods counted under their own name although neither chain lists them.
+	unlisted: usize,
+}
+
+impl Methods {
+	fn get_mut(&mut self, method: &str) -> &mut MethodStats {
+		if self.stats.contains_key(method) {
+			return self.stats.get_mut(method).expect("checked above")
+		}
+		let listed =
+			PolkadotRpcMethod::parse(method).is_some() || KusamaRpcMethod::parse(method).is_some();
+		let method = if listed {
+			method
+		} else if self.unlisted < MAX_UNLISTED_METHODS {
+			self.unlisted += 1;
+			method
+		} else {
+			OTHER_METHODS
+		};
+		self.stats.entry(method.to_string()).or_default()
+	}
+}
+
+impl ProviderStats {
+	/// Requests by method, see [`MAX_UNLISTED_METHODS`].
+	pub fn methods(&self) -> BTreeMap<String, MethodStats> {
+		self.methods.lock().unwrap().stats.clone()
+	}
+
+	pub fn method(&self, method: &str) -> Option<MethodStats> {
+		self.methods.lock().unwrap().stats.get(method).cloned()
+	}
+
+	/// Failed requests, subscriptions and connects of the class.
+	pub fn errors(&self, class: ErrorClass) -> u64 {
+		self.errors[class as usize].load(Ordering::Relaxed)
+	}
+
+	/// Successful connects, but the first one.
+	pub fn reconnects(&self) -> u64 {
+		self.reconnects.load(Ordering::Relaxed)
+	}
+
+	/// Subscription handles not dropped yet.
+	pub fn active_subscriptions(&self) -> u64 {
+		self.active_subscriptions.load(Ordering::Relaxed)
+	}
+
+	pub fn sent_bytes(&self) -> u64 {
+		self.sent.load(Ordering::Relaxed)
+	}
+
+	pub fn received_bytes(&self) -> u64 {
+		self.received.load(Ordering::Relaxed)
+	}
+
+	pub(crate) fn record_call(&self, method: &str, latency: Duration, error: Option<&Error>) {
+		let mut methods = self.methods.lock().unwrap();
+		let stats = methods.get_mut(method);
+		stats.requests += 1;
+		stats.latency.observe(latency.as_secs_f64());
+		if let Some(error) = error {
+			stats.errors += 1;
+			self.record_error(error);
+		}
+	}
+
+	pub(crate) fn record_connect(&self, reconnect: bool, error: Option<&Error>) {
+		match error {
+			Some(error) => self.record_error(error),
+			None if reconnect => {
+				self.reconnects.fetch_add(1, Ordering::Relaxed);
+			},
+			None => (),
+		}
+	}
+
+	fn record_error(&self, error: &Error) {
+		self.errors[ErrorClass::of(error) as usize].fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub(crate) fn record_sent(&self, bytes: usize) {
+		self.sent.fetch_add(bytes as u64, Ordering::Relaxed);
+	}
+
+	pub(crate) fn record_received(&self, bytes: usize) {
+		self.received.fetch_add(bytes as u64, Ordering::Relaxed);
+	}
+
+	/// Counts a subscription as active until the returned guard is dropped.
+	pub(crate) fn subscription_opened(self: &Arc<Self>) -> ActiveSubscription {
+		self.active_subscriptions.fetch_add(1, Ordering::Relaxed);
+		ActiveSubscription(self.clone())
+	}
+}
+
+pub(crate) struct ActiveSubscription(Arc<ProviderStats>);
+
+impl Drop for ActiveSubscription {
+	fn drop(&mut self) {
+		self.0.active_subscriptions.fetch_sub(1, Ordering::Relaxed);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::limits::Limit;
+
+	#[test]
+	fn latency_buckets_are_cumulative() {
+		let mut histogram = Histogram::default();
+		histogram.observe(0.003);
+		histogram.observe(0.2);
+		histogram.observe(20.0);
+		let buckets: Vec<_> = histogram.buckets().collect();
+		assert_eq!(buckets[0], (0.005, 1));
+		assert_eq!(buckets[4], (0.1, 1));
+		assert_eq!(buckets[5], (0.25, 2));
+		assert_eq!(buckets[10], (10.0, 2));
+		assert_eq!(histogram.count(), 3);
+		assert!((histogram.sum() - 20.203).abs() < 1e-9);
+	}
+
+	#[test]
+	fn calls_are_counted_by_method_and_error_class() {
+		let stats = ProviderStats::default();
+		let latency = Duration::from_millis(20);
+		stats.record_call("chain_getHeader", latency, None);
+		stats.record_call("chain_getHeader", latency, Some(&Error::ConnectionClosed));
+		let shared = Error::Shared(Arc::new(Error::LimitExceeded(Limit::MessageSize)));
+		stats.record_call("state_getMetadata", latency, Some(&shared));
+
+		let header = stats.method("chain_getHeader").unwrap();
+		assert_eq!((header.requests, header.errors, header.latency.count()), (2, 1, 2));
+		assert_eq!(stats.methods().len(), 2);
+		assert_eq!(stats.errors(ErrorClass::Connection), 1);
+		assert_eq!(stats.errors(ErrorClass::Limit), 1);
+		assert_eq!(stats.errors(ErrorClass::Rpc), 0);
+	}
+
+	#[test]
+	fn unlisted_methods_beyond_the_cap_are_counted_together() {
+		let stats = ProviderStats::default();
+		let latency = Duration::from_millis(20);
+		for i in 0..MAX_UNLISTED_METHODS + 10 {
+			stats.record_call(&format!("custom_method{i}"), latency, None);
+		}
+		stats.record_call("custom_method0", latency, None);
+		stats.record_call("chain_getHeader", latency, None);
+
+		let methods = stats.methods();
+		assert_eq!(methods.len(), MAX_UNLISTED_METHODS + 2);
+		assert_eq!(methods["custom_method0"].requests, 2);
+		assert_eq!(methods[OTHER_METHODS].requests, 10);
+		assert_eq!(methods["chain_getHeader"].requests, 1);
+		assert!(stats.method(&format!("custom_method{MAX_UNLISTED_METHODS}")).is_none());
+	}
+
+	#[test]
+	fn reconnects_and_subscriptions_are_counted() {
+		let stats = Arc::new(ProviderStats::default());
+		stats.record_connect(false, None);
+		stats.record_connect(true, Some(&Error::ConnectionClosed));
+		stats.record_connect(true, None);
+		assert_eq!(stats.reconnects(), 1);
+		assert_eq!(stats.errors(ErrorClass::Connection), 1);
+
+		let first = stats.subscription_opened();
+		let _second = stats.subscription_opened();
+		assert_eq!(stats.active_subscriptions(), 2);
+		drop(first);
+		assert_eq!(stats.active_subscriptions(), 1);
+	}
+}
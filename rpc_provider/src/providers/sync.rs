@@ -0,0 +1,182 @@
+//! Sync progress of a node, and waiting for it to catch up with its chain.
+//!
+//! A light client answers from the blocks it knows, which are stale while it is still
+//! warp-syncing. The status is polled through `system_health`, `chain_getHeader` and
+//! `chain_getFinalizedHead`, so [`wait_until_synced`] works with any provider.
+//! [`sync_status_stream`] also follows the new heads, and needs a provider able to subscribe.
+use crate::{no_params, rpc_params, Error, HandleSubscription, Request, Result, Subscribe};
+use futures::{stream, Stream};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::time::Interval;
+
+/// What a node knows about its chain.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncStatus {
+	pub peers: u64,
+	pub is_syncing: bool,
+	/// `false` for a node started without any boot node.
+	pub should_have_peers: bool,
+	pub best_block: u64,
+	pub finalized_block: u64,
+}
+
+impl SyncStatus {
+	/// Whether the node caught up with the peers it should have.
+	pub fn is_synced(&self) -> bool {
+		!self.is_syncing && (self.peers > 0 || !self.should_have_peers)
+	}
+
+	/// Current status of the node behind `provider`.
+	pub async fn fetch<R: Request>(provider: &R) -> Result<Self> {
+		let health: Health = provider.request("system_health", no_params!()).await?;
+		let best: Header = provider.request("chain_getHeader", no_params!()).await?;
+		let finalized_hash: String =
+			provider.request("chain_getFinalizedHead", no_params!()).await?;
+		let finalized: Header =
+			provider.request("chain_getHeader", rpc_params!(finalized_hash)).await?;
+
+		Ok(Self {
+			peers: health.peers,
+			is_syncing: health.is_syncing,
+			should_have_peers: health.should_have_peers,
+			best_block: best.number()?,
+			finalized_block: finalized.number()?,
+		})
+	}
+}
+
+/// Polling of a [`SyncStatus`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncConfig {
+	pub poll_interval: Duration,
+	/// [`wait_until_synced`] fails with [`Error::SyncTimeout`] once elapsed.
+	pub timeout: Duration,
+}
+
+impl Default for SyncConfig {
+	fn default() -> Self {
+		Self { poll_interval: Duration::from_secs(1), timeout: Duration::from_secs(120) }
+	}
+}
+
+/// Waits until the node behind `provider` is synced and returns its status.
+///
+/// JSON-RPC errors, which a node may answer while it has no block yet, only delay the next poll.
+pub async fn wait_until_synced<R: Request>(provider: &R, config: SyncConfig) -> Result<SyncStatus> {
+	let mut last = SyncStatus::default();
+	let wait = async {
+		let mut interval = tokio::time::interval(config.poll_interval);
+		loop {
+			interval.tick().await;
+			match SyncStatus::fetch(provider).await {
+				Ok(status) if status.is_synced() => return Ok(status),
+				Ok(status) => last = status,
+				Err(Error::JsonRpcError(_)) => {},
+				Err(e) => return Err(e),
+			}
+		}
+	};
+	let result = tokio::time::timeout(config.timeout, wait).await;
+	result.unwrap_or(Err(Error::SyncTimeout(last)))
+}
+
+/// Follows the status of the node behind `provider`, and yields it when it changed.
+///
+/// The best block follows the `chain_subscribeNewHeads` subscription, the rest of the status is
+/// polled every `poll_interval`. The stream does not need a runtime until first polled, and ends
+/// after the first error.
+pub fn sync_status_stream<R>(
+	provider: R,
+	poll_interval: Duration,
+) -> impl Stream<Item = Result<SyncStatus>>
+where
+	R: Request + Subscribe,
+{
+	let follow = Follow { provider, poll_interval, following: None };
+	stream::unfold(Some(follow), |follow| async move {
+		let mut follow = follow?;
+		match follow.next().await {
+			Ok(status) => Some((Ok(status), Some(follow))),
+			Err(e) => Some((Err(e), None)),
+		}
+	})
+}
+
+/// State of a [`sync_status_stream`].
+struct Follow<R: Subscribe> {
+	provider: R,
+	poll_interval: Duration,
+	/// Started once polled, `tokio` panics on intervals created outside of a runtime.
+	following: Option<Following<R>>,
+}
+
+struct Following<R: Subscribe> {
+	interval: Interval,
+	heads: R::Subscription<Header>,
+	last: SyncStatus,
+}
+
+impl<R: Request + Subscribe> Follow<R> {
+	async fn next(&mut self) -> Result<SyncStatus> {
+		let following = match self.following.take() {
+			Some(following) => self.following.insert(following),
+			None => {
+				let following = self.start().await?;
+				return Ok(self.following.insert(following).last)
+			},
+		};
+		loop {
+			let status = tokio::select! {
+				_ = following.interval.tick() => SyncStatus::fetch(&self.provider).await?,
+				head = following.heads.next() => {
+					let best_block = head.ok_or(Error::ConnectionClosed)??.number()?;
+					SyncStatus { best_block, ..following.last }
+				},
+			};
+			if status != following.last {
+				following.last = status;
+				return Ok(status)
+			}
+		}
+	}
+
+	async fn start(&self) -> Result<Following<R>> {
+		// Subscribed first, so that no head is missed while polling.
+		let heads = self
+			.provider
+			.subscribe("chain_subscribeNewHeads", no_params!(), "chain_unsubscribeNewHeads")
+			.await?;
+		let last = SyncStatus::fetch(&self.provider).await?;
+		let mut interval = tokio::time::interval(self.poll_interval);
+		// The first tick would complete at once, the status was just polled.
+		interval.reset();
+		Ok(Following { interval, heads, last })
+	}
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Health {
+	peers: u64,
+	is_syncing: bool,
+	should_have_peers: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Header {
+	/// Hexadecimal, such as `0x1a2b`.
+	number: String,
+}
+
+impl Header {
+	fn number(&self) -> Result<u64> {
+		let digits = self.number.trim_start_matches("0x");
+		u64::from_str_radix(digits, 16).map_err(|_| {
+			Error::SerdeJson(serde::de::Error::custom(format!(
+				"invalid block number `{}`",
+				self.number
+			)))
+		})
+	}
+}
@@ -0,0 +1,204 @@
+//! TLS configuration shared by the WebSocket and HTTP providers.
+use crate::{
+	proxy::{self, ProxyConfig},
+	Error, Result,
+};
+use std::{io::BufReader, sync::Arc};
+use tokio::net::TcpStream;
+use tokio_rustls::{
+	client::TlsStream,
+	rustls::{
+		client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+		crypto::{ring, verify_tls12_signature, verify_tls13_signature, WebPkiSupportedAlgorithms},
+		pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+		ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+	},
+	TlsConnector,
+};
+use url::Url;
+
+/// TLS options of a provider, used for `wss` and `https` urls.
+///
+/// The default configuration trusts the built-in root certificates: the certificate store of the
+/// platform with the `native-tls` feature, the Mozilla root certificates with the `rustls`
+/// feature.
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+	/// Trust the built-in root certificates. Disable it to only trust `root_certificates_pem`.
+	pub builtin_roots: bool,
+	/// Additional root certificates to trust, PEM encoded. Several certificates can be
+	/// concatenated.
+	pub root_certificates_pem: Vec<u8>,
+	/// Certificate presented to servers requiring client authentication (mTLS).
+	pub client_certificate: Option<ClientCertificate>,
+	/// Accept any server certificate, whoever signed it and whatever its name.
+	///
+	/// Only meant for local testing: the connection is encrypted but not authenticated.
+	pub insecure: bool,
+}
+
+impl Default for TlsConfig {
+	fn default() -> Self {
+		Self {
+			builtin_roots: true,
+			root_certificates_pem: Vec::new(),
+			client_certificate: None,
+			insecure: false,
+		}
+	}
+}
+
+/// Client certificate and its private key, PEM encoded.
+#[derive(Clone)]
+pub struct ClientCertificate {
+	/// The certificate followed by its intermediate certificates, if any.
+	pub certificate_chain_pem: Vec<u8>,
+	/// PKCS#1, PKCS#8 or SEC1 private key.
+	pub private_key_pem: Vec<u8>,
+}
+
+impl core::fmt::Debug for ClientCertificate {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		f.debug_struct("ClientCertificate").finish_non_exhaustive()
+	}
+}
+
+impl TlsConfig {
+	/// Only trusts the given PEM encoded root certificates, for instance the CA of an internal PKI.
+	pub fn with_root_certificates(root_certificates_pem: impl Into<Vec<u8>>) -> Self {
+		Self {
+			builtin_roots: false,
+			root_certificates_pem: root_certificates_pem.into(),
+			..Default::default()
+		}
+	}
+
+	/// Accepts any server certificate, see [`TlsConfig::insecure`].
+	pub fn insecure() -> Self {
+		Self { insecure: true, ..Default::default() }
+	}
+
+	pub(crate) fn client_config(&self) -> Result<Arc<ClientConfig>> {
+		let builder = ClientConfig::builder();
+		let builder = if self.insecure {
+			builder
+				.dangerous()
+				.with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate(
+					ring::default_provider().signature_verification_algorithms,
+				)))
+		} else {
+			builder.with_root_certificates(self.root_store()?)
+		};
+		let config = match &self.client_certificate {
+			Some(client) => {
+				let chain = read_certificates(&client.certificate_chain_pem)?;
+				let key = read_private_key(&client.private_key_pem)?;
+				builder.with_client_auth_cert(chain, key).map_err(tls_error)?
+			},
+			None => builder.with_no_client_auth(),
+		};
+		Ok(Arc::new(config))
+	}
+
+	fn root_store(&self) -> Result<RootCertStore> {
+		let mut roots = RootCertStore::empty();
+		if self.builtin_roots {
+			add_builtin_roots(&mut roots)?;
+		}
+		for certificate in read_certificates(&self.root_certificates_pem)? {
+			roots.add(certificate).map_err(tls_error)?;
+		}
+		if roots.is_empty() {
+			return Err(Error::Tls("No root certificate to trust".into()))
+		}
+		Ok(roots)
+	}
+
+	/// Opens a TLS connection to the host of `url`, through `proxy` if any.
+	pub(crate) async fn connect(
+		&self,
+		url: &Url,
+		proxy: Option<&ProxyConfig>,
+	) -> Result<TlsStream<TcpStream>> {
+		let (host, port) = proxy::host_and_port(url)?;
+		let server_name = ServerName::try_from(host.to_string())
+			.map_err(|_| Error::InvalidUrl(url.to_string()))?;
+
+		let connector = TlsConnector::from(self.client_config()?);
+		let tcp = proxy::connect(proxy, host, port).await?;
+		connector.connect(server_name, tcp).await.map_err(tls_error)
+	}
+}
+
+#[cfg(feature = "native-tls")]
+fn add_builtin_roots(roots: &mut RootCertStore) -> Result<()> {
+	let certificates = rustls_native_certs::load_native_certs()?;
+	roots.add_parsable_certificates(certificates);
+	Ok(())
+}
+
+#[cfg(all(feature = "rustls", not(feature = "native-tls")))]
+fn add_builtin_roots(roots: &mut RootCertStore) -> Result<()> {
+	roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+	Ok(())
+}
+
+#[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+fn add_builtin_roots(_roots: &mut RootCertStore) -> Result<()> {
+	Err(Error::Tls("Built-in root certificates need the `native-tls` or `rustls` feature".into()))
+}
+
+fn read_certificates(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>> {
+	rustls_pemfile::certs(&mut BufReader::new(pem))
+		.collect::<core::result::Result<_, _>>()
+		.map_err(tls_error)
+}
+
+fn read_private_key(pem: &[u8]) -> Result<PrivateKeyDer<'static>> {
+	rustls_pemfile::private_key(&mut BufReader::new(pem))
+		.map_err(tls_error)?
+		.ok_or_else(|| Error::Tls("No private key found".into()))
+}
+
+fn tls_error(error: impl core::fmt::Display) -> Error {
+	Error::Tls(error.to_string())
+}
+
+/// Accepts every certificate, but still checks that the handshake is signed by its key.
+#[derive(Debug)]
+struct AcceptAnyCertificate(WebPkiSupportedAlgorithms);
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+	fn verify_server_cert(
+		&self,
+		_end_entity: &CertificateDer<'_>,
+		_intermediates: &[CertificateDer<'_>],
+		_server_name: &ServerName<'_>,
+		_ocsp_response: &[u8],
+		_now: UnixTime,
+	) -> core::result::Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+		Ok(ServerCertVerified::assertion())
+	}
+
+	fn verify_tls12_signature(
+		&self,
+		message: &[u8],
+		cert: &CertificateDer<'_>,
+		dss: &DigitallySignedStruct,
+	) -> core::result::Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+		verify_tls12_signature(message, cert, dss, &self.0)
+	}
+
+	fn verify_tls13_signature(
+		&self,
+		message: &[u8],
+		cert: &CertificateDer<'_>,
+		dss: &DigitallySignedStruct,
+	) -> core::result::Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+		verify_tls13_signature(message, cert, dss, &self.0)
+	}
+
+	fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+		self.0.supported_schemes()
+	}
+}
@@ -1,13 +1,22 @@
-use crate::{Request, Result};
+use crate::{Error, Request, Result};
+use types_support::metadata::v15::is_unsafe_rpc_method;
 
+#[cfg_attr(feature = "send", maybe_async::maybe_async)]
+#[cfg_attr(not(feature = "send"), maybe_async::maybe_async(?Send))]
 pub trait ProviderInterface: Request {
-	#[allow(async_fn_in_trait)]
 	async fn connect(&mut self) -> Result<()>;
 
-	#[allow(async_fn_in_trait)]
 	async fn disconnect(&mut self) -> Result<()>;
 }
 
+/// Refuses `method` if it is unsafe, unless the provider was built with `allow_unsafe`.
+pub(crate) fn check_method(method: &str, allow_unsafe: bool) -> Result<()> {
+	if !allow_unsafe && is_unsafe_rpc_method(method) {
+		return Err(Error::UnsafeMethod(method.to_string()))
+	}
+	Ok(())
+}
+
 mod jsonrpsee_types {
 	use crate::primitives::RpcParams;
 	use jsonrpsee::core::traits::ToRpcParams;
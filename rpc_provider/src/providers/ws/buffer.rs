@@ -0,0 +1,369 @@
+//! Bounded buffer between the transport and a subscription handle.
+use super::subscription::{LagPolicy, DEFAULT_BUFFER_CAPACITY};
+use crate::{limits::Limit, Error, Result};
+use serde_json::Value;
+use std::{
+	collections::VecDeque,
+	sync::{Arc, Mutex},
+	task::{Context, Poll, Waker},
+};
+use tokio::sync::{
+	mpsc::{self, error::TrySendError},
+	Notify,
+};
+
+/// Notifications received for a subscription and not yet consumed.
+///
+/// The transport side pushes notifications, the subscription handle polls them. What happens
+/// when the consumer falls behind is decided by the [`LagPolicy`].
+pub(crate) struct NotificationBuffer {
+	state: Mutex<State>,
+	capacity: usize,
+	lag_policy: LagPolicy,
+	/// Notified whenever room is made in the queue or the consumer goes away.
+	space: Notify,
+}
+
+/// Why the notifications of a subscription ended before the node or the consumer ended them.
+#[derive(Debug, Clone)]
+pub(crate) enum Failure {
+	/// The connection was lost or found stale, reported as [`Error::ConnectionClosed`].
+	Disconnected,
+	LimitExceeded(Limit),
+	/// The client failed to hand a notification over, reported to every handle.
+	Notification(Arc<Error>),
+	/// A [`LagPolicy::Block`] handle fell so far behind that its backlog is full, reported as
+	/// [`Error::SubscriptionLagged`].
+	Lagged(u64),
+}
+
+impl From<Failure> for Error {
+	fn from(failure: Failure) -> Self {
+		match failure {
+			Failure::Disconnected => Error::ConnectionClosed,
+			Failure::LimitExceeded(limit) => Error::LimitExceeded(limit),
+			Failure::Notification(error) => Error::from_shared(error),
+			Failure::Lagged(lagged) => Error::SubscriptionLagged(lagged),
+		}
+	}
+}
+
+#[derive(Default)]
+struct State {
+	queue: VecDeque<Value>,
+	/// Notifications dropped since the consumer was last told about it.
+	lagged: u64,
+	/// No more notifications will be pushed.
+	closed: bool,
+	/// Why the notifications ended early, reported once after the queue.
+	failure: Option<Failure>,
+	/// The subscription handle was dropped.
+	consumer_gone: bool,
+	waker: Option<Waker>,
+}
+
+impl NotificationBuffer {
+	pub(crate) fn new(capacity: usize, lag_policy: LagPolicy) -> Self {
+		Self {
+			state: Mutex::default(),
+			capacity: capacity.max(1),
+			lag_policy,
+			space: Notify::new(),
+		}
+	}
+
+	pub(crate) fn capacity(&self) -> usize {
+		self.capacity
+	}
+
+	pub(crate) fn lag_policy(&self) -> LagPolicy {
+		self.lag_policy
+	}
+
+	/// Queues a notification according to the lag policy, waiting for room under
+	/// [`LagPolicy::Block`].
+	///
+	/// Returns `false` once no more notifications should be pushed, because the consumer is gone
+	/// or lagged behind under [`LagPolicy::Error`].
+	pub(crate) async fn push(&self, mut notification: Value) -> bool {
+		loop {
+			let space = self.space.notified();
+			match self.try_push(notification) {
+				Ok(pushed) => return pushed,
+				Err(full) => notification = full,
+			}
+			space.await;
+		}
+	}
+
+	/// Queues a notification according to the lag policy without waiting.
+	///
+	/// Same as [`Self::push`], except that the notification is handed back when the queue is full
+	/// under [`LagPolicy::Block`].
+	pub(crate) fn try_push(&self, notification: Value) -> core::result::Result<bool, Value> {
+		let mut state = self.state.lock().unwrap();
+		if state.consumer_gone || state.closed {
+			return Ok(false);
+		}
+		if state.queue.len() >= self.capacity {
+			match self.lag_policy {
+				LagPolicy::Block => return Err(notification),
+				LagPolicy::DropOldest => {
+					state.queue.pop_front();
+					state.lagged += 1;
+				},
+				LagPolicy::Error => {
+					state.lagged += 1;
+					state.closed = true;
+					Self::wake(state);
+					return Ok(false);
+				},
+			}
+		}
+		state.queue.push_back(notification);
+		Self::wake(state);
+		Ok(true)
+	}
+
+	/// Marks the end of the notifications. Queued notifications can still be consumed.
+	pub(crate) fn close(&self) {
+		let mut state = self.state.lock().unwrap();
+		state.closed = true;
+		Self::wake(state);
+	}
+
+	/// Marks the end of the notifications because of `failure`. The consumer gets its error after
+	/// the queued notifications.
+	pub(crate) fn close_failed(&self, failure: Failure) {
+		let mut state = self.state.lock().unwrap();
+		state.closed = true;
+		state.failure = Some(failure);
+		Self::wake(state);
+	}
+
+	/// Called when the subscription handle is dropped, unblocks a pending [`Self::push`].
+	pub(crate) fn close_consumer(&self) {
+		self.state.lock().unwrap().consumer_gone = true;
+		self.space.notify_waiters();
+	}
+
+	/// Completes once the subscription handle is dropped.
+	async fn consumer_gone(&self) {
+		loop {
+			let space = self.space.notified();
+			if self.state.lock().unwrap().consumer_gone {
+				return;
+			}
+			space.await;
+		}
+	}
+
+	/// Returns the next notification, or [`Error::SubscriptionLagged`] when notifications were
+	/// dropped before it.
+	pub(crate) fn poll_next(&self, cx: &mut Context<'_>) -> Poll<Option<Result<Value>>> {
+		let mut state = self.state.lock().unwrap();
+		// Under `LagPolicy::Error` the lag is only reported once every queued notification was read.
+		let report_lag = match self.lag_policy {
+			LagPolicy::Error => state.queue.is_empty(),
+			LagPolicy::DropOldest | LagPolicy::Block => true,
+		};
+		if state.lagged > 0 && report_lag {
+			let lagged = std::mem::take(&mut state.lagged);
+			return Poll::Ready(Some(Err(Error::SubscriptionLagged(lagged))));
+		}
+		if let Some(notification) = state.queue.pop_front() {
+			drop(state);
+			self.space.notify_one();
+			return Poll::Ready(Some(Ok(notification)));
+		}
+		if state.closed {
+			if let Some(failure) = state.failure.take() {
+				return Poll::Ready(Some(Err(failure.into())));
+			}
+			return Poll::Ready(None);
+		}
+		state.waker = Some(cx.waker().clone());
+		Poll::Pending
+	}
+
+	fn wake(mut state: std::sync::MutexGuard<'_, State>) {
+		let waker = state.waker.take();
+		drop(state);
+		if let Some(waker) = waker {
+			waker.wake();
+		}
+	}
+}
+
+/// Hands the notifications of a subscription over to the buffer of a handle.
+///
+/// Notifications are handed over without waiting, so that a handle falling behind never holds
+/// back the others sharing the subscription, nor the task routing the notifications. A [`LagPolicy::Block`] handle waits for its consumer through a backlog of its
+/// own, moved into its buffer by [`drain_backlog`]. Once the backlog is full as well, the handle
+/// ends with [`crate::Error::SubscriptionLagged`] after the notifications it holds.
+pub(crate) struct Sink {
+	buffer: Arc<NotificationBuffer>,
+	backlog: Option<Backlog>,
+}
+
+struct Backlog {
+	notifications: mpsc::Sender<Value>,
+	/// Why the notifications ended, set before `notifications` is dropped.
+	failure: Arc<Mutex<Option<Failure>>>,
+}
+
+impl Sink {
+	pub(crate) fn new(buffer: Arc<NotificationBuffer>) -> Self {
+		if buffer.lag_policy() != LagPolicy::Block {
+			return Self { buffer, backlog: None };
+		}
+		// As many notifications as the transport buffers for a blocked subscription.
+		let (notifications, backlog) =
+			mpsc::channel(buffer.capacity().max(DEFAULT_BUFFER_CAPACITY));
+		let failure = Arc::new(Mutex::new(None));
+		tokio::spawn(drain_backlog(buffer.clone(), backlog, failure.clone()));
+		Self { buffer, backlog: Some(Backlog { notifications, failure }) }
+	}
+
+	/// Returns `false` once the handle takes no more notifications.
+	pub(crate) fn push(&self, notification: Value) -> bool {
+		let Some(backlog) = &self.backlog else {
+			// Only `LagPolicy::Block` hands the notification back.
+			return self.buffer.try_push(notification).unwrap_or(true);
+		};
+		match backlog.notifications.try_send(notification) {
+			Ok(()) => true,
+			Err(TrySendError::Full(_)) => {
+				*backlog.failure.lock().unwrap() = Some(Failure::Lagged(1));
+				false
+			},
+			// The consumer is gone.
+			Err(TrySendError::Closed(_)) => false,
+		}
+	}
+
+	/// Ends the notifications, once the ones the handle holds are consumed.
+	pub(crate) fn close(&self, failure: Option<Failure>) {
+		match (&self.backlog, failure) {
+			(Some(backlog), failure) => *backlog.failure.lock().unwrap() = failure,
+			(None, Some(failure)) => self.buffer.close_failed(failure),
+			(None, None) => self.buffer.close(),
+		}
+	}
+}
+
+/// Moves the backlog of a [`LagPolicy::Block`] handle into its buffer as the consumer makes room,
+/// then ends the buffer once the backlog is closed.
+async fn drain_backlog(
+	buffer: Arc<NotificationBuffer>,
+	mut backlog: mpsc::Receiver<Value>,
+	failure: Arc<Mutex<Option<Failure>>>,
+) {
+	loop {
+		let notification = tokio::select! {
+			notification = backlog.recv() => notification,
+			// Nothing consumes the backlog anymore, the sender may be kept for long.
+			_ = buffer.consumer_gone() => return,
+		};
+		let Some(notification) = notification else { break };
+		if !buffer.push(notification).await {
+			return;
+		}
+	}
+	match failure.lock().unwrap().take() {
+		Some(failure) => buffer.close_failed(failure),
+		None => buffer.close(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::task::noop_waker;
+	use serde_json::json;
+
+	fn drain(buffer: &NotificationBuffer) -> Vec<core::result::Result<Value, u64>> {
+		let waker = noop_waker();
+		let mut cx = Context::from_waker(&waker);
+		let mut items = Vec::new();
+		while let Poll::Ready(Some(item)) = buffer.poll_next(&mut cx) {
+			items.push(item.map_err(|e| match e {
+				Error::SubscriptionLagged(lagged) => lagged,
+				e => panic!("Unexpected error {e:?}"),
+			}));
+		}
+		items
+	}
+
+	#[tokio::test]
+	async fn drop_oldest_reports_lag_before_the_remaining_notifications() {
+		let buffer = NotificationBuffer::new(2, LagPolicy::DropOldest);
+		for i in 0..5 {
+			assert!(buffer.push(json!(i)).await);
+		}
+
+		assert_eq!(drain(&buffer), vec![Err(3), Ok(json!(3)), Ok(json!(4))]);
+	}
+
+	#[tokio::test]
+	async fn error_policy_closes_after_the_queued_notifications() {
+		let buffer = NotificationBuffer::new(2, LagPolicy::Error);
+		assert!(buffer.push(json!(0)).await);
+		assert!(buffer.push(json!(1)).await);
+		assert!(!buffer.push(json!(2)).await);
+		assert!(!buffer.push(json!(3)).await);
+
+		let waker = noop_waker();
+		let mut cx = Context::from_waker(&waker);
+		assert_eq!(drain(&buffer), vec![Ok(json!(0)), Ok(json!(1)), Err(1)]);
+		assert!(matches!(buffer.poll_next(&mut cx), Poll::Ready(None)));
+	}
+
+	#[tokio::test]
+	async fn connection_loss_is_reported_after_the_queued_notifications() {
+		let buffer = NotificationBuffer::new(2, LagPolicy::Error);
+		assert!(buffer.push(json!(0)).await);
+		buffer.close_failed(Failure::Disconnected);
+
+		let waker = noop_waker();
+		let mut cx = Context::from_waker(&waker);
+		assert!(matches!(buffer.poll_next(&mut cx), Poll::Ready(Some(Ok(_)))));
+		assert!(matches!(
+			buffer.poll_next(&mut cx),
+			Poll::Ready(Some(Err(Error::ConnectionClosed)))
+		));
+		assert!(matches!(buffer.poll_next(&mut cx), Poll::Ready(None)));
+	}
+
+	#[tokio::test]
+	async fn block_policy_waits_for_the_consumer() {
+		let buffer = std::sync::Arc::new(NotificationBuffer::new(1, LagPolicy::Block));
+		assert!(buffer.push(json!(0)).await);
+
+		let producer = {
+			let buffer = buffer.clone();
+			tokio::spawn(async move { buffer.push(json!(1)).await })
+		};
+		tokio::task::yield_now().await;
+		assert!(!producer.is_finished());
+
+		assert_eq!(drain(&buffer), vec![Ok(json!(0))]);
+		assert!(producer.await.unwrap());
+		assert_eq!(drain(&buffer), vec![Ok(json!(1))]);
+	}
+
+	#[tokio::test]
+	async fn push_stops_once_the_consumer_is_gone() {
+		let buffer = std::sync::Arc::new(NotificationBuffer::new(1, LagPolicy::Block));
+		assert!(buffer.push(json!(0)).await);
+
+		let producer = {
+			let buffer = buffer.clone();
+			tokio::spawn(async move { buffer.push(json!(1)).await })
+		};
+		tokio::task::yield_now().await;
+		buffer.close_consumer();
+
+		assert!(!producer.await.unwrap());
+	}
+}
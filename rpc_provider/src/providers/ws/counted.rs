@@ -0,0 +1,51 @@
+//! Counts the messages sent and received by the [`super::WsProvider`] in its [`ProviderStats`].
+use crate::stats::ProviderStats;
+use jsonrpsee::core::client::{ReceivedMessage, TransportReceiverT, TransportSenderT};
+use std::sync::Arc;
+
+/// Sender or receiver of a connection, counting the bytes of its messages.
+pub(crate) struct Counted<T> {
+	inner: T,
+	stats: Arc<ProviderStats>,
+}
+
+impl<T> Counted<T> {
+	pub(crate) fn new(inner: T, stats: Arc<ProviderStats>) -> Self {
+		Self { inner, stats }
+	}
+}
+
+#[async_trait::async_trait]
+impl<S: TransportSenderT + Send> TransportSenderT for Counted<S> {
+	type Error = S::Error;
+
+	async fn send(&mut self, message: String) -> core::result::Result<(), Self::Error> {
+		let len = message.len();
+		self.inner.send(message).await?;
+		self.stats.record_sent(len);
+		Ok(())
+	}
+
+	async fn send_ping(&mut self) -> core::result::Result<(), Self::Error> {
+		self.inner.send_ping().await
+	}
+
+	async fn close(&mut self) -> core::result::Result<(), Self::Error> {
+		self.inner.close().await
+	}
+}
+
+#[async_trait::async_trait]
+impl<R: TransportReceiverT + Send> TransportReceiverT for Counted<R> {
+	type Error = R::Error;
+
+	async fn receive(&mut self) -> core::result::Result<ReceivedMessage, Self::Error> {
+		let message = self.inner.receive().await?;
+		match &message {
+			ReceivedMessage::Text(text) => self.stats.record_received(text.len()),
+			ReceivedMessage::Bytes(bytes) => self.stats.record_received(bytes.len()),
+			ReceivedMessage::Pong => (),
+		}
+		Ok(message)
+	}
+}
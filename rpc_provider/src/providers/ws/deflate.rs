@@ -0,0 +1,174 @@
+//! WebSocket transport negotiating the `permessage-deflate` extension, which the `jsonrpsee`
+//! transport does not support.
+use crate::{compression::CompressionStats, Error, Result};
+use futures::io::{BufReader, BufWriter};
+use jsonrpsee::core::client::{ReceivedMessage, TransportReceiverT, TransportSenderT};
+use soketto::{
+	connection::{self, Error as WsError},
+	data::ByteSlice125,
+	extension::deflate::Deflate,
+	handshake::{client::Client as Handshake, ServerResponse},
+	Data, Incoming,
+};
+use std::{
+	pin::Pin,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc,
+	},
+	task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_util::compat::{Compat, TokioAsyncReadCompatExt};
+use url::Url;
+
+type Socket<T> = BufReader<BufWriter<Compat<Counted<T>>>>;
+
+pub(crate) struct Sender<T> {
+	inner: connection::Sender<Socket<T>>,
+}
+
+pub(crate) struct Receiver<T> {
+	inner: connection::Receiver<Socket<T>>,
+	received: Arc<AtomicU64>,
+	/// Bytes read from the connection already accounted for in `stats`.
+	recorded: u64,
+	stats: Arc<CompressionStats>,
+}
+
+/// Runs the WebSocket handshake over `stream`, offering `permessage-deflate`. Returns whether the
+/// server accepted it.
+pub(crate) async fn handshake<T>(
+	url: &Url,
+	stream: T,
+	max_message_size: usize,
+	stats: Arc<CompressionStats>,
+) -> Result<(Sender<T>, Receiver<T>, bool)>
+where
+	T: AsyncRead + AsyncWrite + Unpin,
+{
+	let host = match url.port() {
+		Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+		None => url.host_str().unwrap_or_default().to_string(),
+	};
+	let resource = match url.query() {
+		Some(query) => format!("{}?{query}", url.path()),
+		None => url.path().to_string(),
+	};
+	let received = Arc::new(AtomicU64::new(0));
+	let stream = Counted { inner: stream, received: received.clone() };
+	let mut client =
+		Handshake::new(BufReader::new(BufWriter::new(stream.compat())), &host, &resource);
+	client.add_extension(Box::new(Deflate::new(soketto::Mode::Client)));
+
+	match client.handshake().await.map_err(|e| Error::Client(Box::new(e)))? {
+		ServerResponse::Accepted { .. } => (),
+		ServerResponse::Rejected { status_code } =>
+			return Err(Error::Client(Box::new(format!(
+				"WebSocket handshake rejected: {status_code}"
+			)))),
+		ServerResponse::Redirect { status_code, location } =>
+			return Err(Error::Client(Box::new(format!(
+				"WebSocket handshake redirected ({status_code}) to {location}"
+			)))),
+	}
+	let extensions: Vec<_> = client.drain_extensions().collect();
+	let negotiated = extensions.iter().any(|extension| extension.is_enabled());
+	let mut builder = client.into_builder();
+	builder.add_extensions(extensions);
+	builder.set_max_message_size(max_message_size);
+	let (sender, receiver) = builder.finish();
+
+	let recorded = received.load(Ordering::Relaxed);
+	let receiver = Receiver { inner: receiver, received, recorded, stats };
+	Ok((Sender { inner: sender }, receiver, negotiated))
+}
+
+#[async_trait::async_trait]
+impl<T> TransportSenderT for Sender<T>
+where
+	T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+	type Error = WsError;
+
+	async fn send(&mut self, body: String) -> core::result::Result<(), Self::Error> {
+		self.inner.send_text_owned(body).await?;
+		self.inner.flush().await
+	}
+
+	async fn send_ping(&mut self) -> core::result::Result<(), Self::Error> {
+		let empty = ByteSlice125::try_from(&[][..]).expect("An empty slice fits in 125 bytes");
+		self.inner.send_ping(empty).await?;
+		self.inner.flush().await
+	}
+
+	async fn close(&mut self) -> core::result::Result<(), Self::Error> {
+		self.inner.close().await
+	}
+}
+
+#[async_trait::async_trait]
+impl<T> TransportReceiverT for Receiver<T>
+where
+	T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+	type Error = WsError;
+
+	async fn receive(&mut self) -> core::result::Result<ReceivedMessage, Self::Error> {
+		loop {
+			let mut message = Vec::new();
+			let incoming = self.inner.receive(&mut message).await?;
+			let received = self.received.load(Ordering::Relaxed);
+			self.stats.record((received - self.recorded) as usize, message.len());
+			self.recorded = received;
+			match incoming {
+				Incoming::Data(Data::Text(_)) => {
+					let text =
+						String::from_utf8(message).map_err(|e| WsError::Utf8(e.utf8_error()))?;
+					break Ok(ReceivedMessage::Text(text));
+				},
+				Incoming::Data(Data::Binary(_)) => break Ok(ReceivedMessage::Bytes(message)),
+				Incoming::Pong(_) => break Ok(ReceivedMessage::Pong),
+				_ => continue,
+			}
+		}
+	}
+}
+
+/// Counts the bytes read from the connection.
+struct Counted<T> {
+	inner: T,
+	received: Arc<AtomicU64>,
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Counted<T> {
+	fn poll_read(
+		mut self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &mut ReadBuf<'_>,
+	) -> Poll<std::io::Result<()>> {
+		let filled = buf.filled().len();
+		let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+		let read = buf.filled().len() - filled;
+		self.received.fetch_add(read as u64, Ordering::Relaxed);
+		poll
+	}
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Counted<T> {
+	fn poll_write(
+		mut self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &[u8],
+	) -> Poll<std::io::Result<usize>> {
+		Pin::new(&mut self.inner).poll_write(cx, buf)
+	}
+
+	fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+		Pin::new(&mut self.inner).poll_flush(cx)
+	}
+
+	fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+		Pin::new(&mut self.inner).poll_shutdown(cx)
+	}
+}
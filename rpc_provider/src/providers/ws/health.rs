@@ -0,0 +1,37 @@
+//! Liveness of a WebSocket connection, as observed by the subscription watchdogs.
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+/// Set once a subscription watchdog found the connection stale.
+///
+/// `jsonrpsee` only closes the connection on its own when the keepalive pings go unanswered, a
+/// stale connection stays open until the provider reconnects. In the meantime it is reported as
+/// disconnected, and its requests and subscriptions fail with [`crate::Error::ConnectionClosed`].
+#[derive(Default)]
+pub(crate) struct ConnectionHealth {
+	stale: Mutex<Option<String>>,
+	notify: Notify,
+}
+
+impl ConnectionHealth {
+	/// Marks the connection as stale. Only the first reason is kept.
+	pub(crate) fn mark_stale(&self, reason: String) {
+		self.stale.lock().unwrap().get_or_insert(reason);
+		self.notify.notify_waiters();
+	}
+
+	pub(crate) fn stale_reason(&self) -> Option<String> {
+		self.stale.lock().unwrap().clone()
+	}
+
+	/// Completes once the connection is marked as stale, returning the reason.
+	pub(crate) async fn stale(&self) -> String {
+		loop {
+			let notified = self.notify.notified();
+			if let Some(reason) = self.stale_reason() {
+				return reason;
+			}
+			notified.await;
+		}
+	}
+}
@@ -0,0 +1,86 @@
+//! Checks every message of the node against the [`Limits`] before `jsonrpsee` parses it.
+use crate::limits::{Limit, Limits};
+use jsonrpsee::core::client::{ReceivedMessage, TransportReceiverT};
+use std::{error::Error as StdError, fmt};
+
+/// Receiver failing once the node breaks a limit, which closes the connection.
+pub(crate) struct Limited<R> {
+	inner: R,
+	limits: Limits,
+}
+
+impl<R> Limited<R> {
+	pub(crate) fn new(inner: R, limits: Limits) -> Self {
+		Self { inner, limits }
+	}
+}
+
+#[async_trait::async_trait]
+impl<R: TransportReceiverT + Send> TransportReceiverT for Limited<R> {
+	type Error = ReceiveError;
+
+	async fn receive(&mut self) -> core::result::Result<ReceivedMessage, Self::Error> {
+		let message = self.inner.receive().await.map_err(ReceiveError::from_transport)?;
+		let bytes = match &message {
+			ReceivedMessage::Text(text) => text.as_bytes(),
+			ReceivedMessage::Bytes(bytes) => bytes,
+			ReceivedMessage::Pong => return Ok(message),
+		};
+		self.limits.check_message(bytes).map_err(ReceiveError::Limit)?;
+		Ok(message)
+	}
+}
+
+/// Error of a [`Limited`] receiver, found back by [`crate::Error`] in the reason the connection
+/// was closed for.
+#[derive(Debug)]
+pub(crate) enum ReceiveError {
+	Transport(Box<dyn StdError + Send + Sync>),
+	Limit(Limit),
+}
+
+impl ReceiveError {
+	fn from_transport<E: StdError + Send + Sync + 'static>(error: E) -> Self {
+		// The transport enforces the message size on its own, while reading the message.
+		if is_message_too_large(&error) {
+			return Self::Limit(Limit::MessageSize)
+		}
+		Self::Transport(Box::new(error))
+	}
+
+	pub(crate) fn limit(&self) -> Option<Limit> {
+		match self {
+			Self::Transport(_) => None,
+			Self::Limit(limit) => Some(*limit),
+		}
+	}
+}
+
+fn is_message_too_large(error: &(dyn StdError + 'static)) -> bool {
+	let mut error = Some(error);
+	while let Some(current) = error {
+		if let Some(soketto::connection::Error::MessageTooLarge { .. }) = current.downcast_ref() {
+			return true;
+		}
+		error = current.source();
+	}
+	false
+}
+
+impl fmt::Display for ReceiveError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Transport(error) => error.fmt(f),
+			Self::Limit(limit) => write!(f, "The node exceeded the {limit:?} limit"),
+		}
+	}
+}
+
+impl StdError for ReceiveError {
+	fn source(&self) -> Option<&(dyn StdError + 'static)> {
+		match self {
+			Self::Transport(error) => Some(&**error),
+			Self::Limit(_) => None,
+		}
+	}
+}
@@ -1,36 +1,142 @@
 use crate::{
+	compression::CompressionStats,
 	defaults::WS_URL,
-	types::{ProviderInterface, RpcParamsWrapper},
-	Error, Request, Result, RpcParams, Subscribe,
+	limits::Limits,
+	proxy::Proxy,
+	stats::ProviderStats,
+	trace::{TraceConfig, Tracer},
+	types::{check_method, ProviderInterface, RpcParamsWrapper},
+	Error, MaybeSend, Request, Result, RpcParams, Subscribe,
 };
+use counted::Counted;
+use health::ConnectionHealth;
 use jsonrpsee::{
+	async_client::PingConfig,
 	client_transport::ws::{Url, WsTransportClientBuilder},
-	core::client::{Client, ClientBuilder, ClientT, Error as JsonrpseeError, SubscriptionClientT},
+	core::client::{
+		Client, ClientBuilder, ClientT, Error as JsonrpseeError, SubscriptionClientT,
+		TransportReceiverT, TransportSenderT,
+	},
 };
+use limited::Limited;
+use raw::{RawReceiver, RawRequests, RawSender};
 use serde::de::DeserializeOwned;
-use std::sync::Arc;
-use subscription::SubscriptionWrapper;
+use serde_json::{value::RawValue, Value};
+use std::{fmt::Debug, future::Future, sync::Arc, time::Duration};
+use upstream::{SharedSubscriptions, SubscriptionKey, Upstream, Watch};
 
-mod subscription;
+pub(crate) mod buffer;
+mod counted;
+mod deflate;
+mod health;
+mod limited;
+mod raw;
+pub mod subscription;
+mod upstream;
+
+pub(crate) use limited::ReceiveError;
+pub use subscription::{LagPolicy, SubscriptionConfig, SubscriptionWrapper};
+
+/// Options of a [`WsProvider`].
+#[derive(Debug, Clone)]
+pub struct WsConfig {
+	/// Buffering of the subscriptions opened by the provider.
+	pub subscription: SubscriptionConfig,
+	/// Open a single subscription on the node for identical subscriptions, same methods and
+	/// parameters, and broadcast its notifications to every handle. Enabled by default.
+	///
+	/// A handle only receives the notifications sent after it was opened: nodes which only send
+	/// the current state right after subscribing do not send it again to later handles.
+	pub share_subscriptions: bool,
+	/// WebSocket ping/pong keepalive, `None` disables it. Enabled by default.
+	pub keepalive: Option<KeepaliveConfig>,
+	/// TLS options for `wss` urls. `None` uses the built-in root certificates.
+	#[cfg(feature = "__tls")]
+	pub tls: Option<crate::tls::TlsConfig>,
+	/// Proxy to connect through, none by default.
+	pub proxy: Proxy,
+	/// Offer the `permessage-deflate` extension to the node. Disabled by default.
+	pub compression: bool,
+	/// Send node-admin methods such as `author_insertKey`, refused with
+	/// [`Error::UnsafeMethod`] otherwise. Disabled by default.
+	pub allow_unsafe: bool,
+	/// Bounds on the messages of the node, and on the requests waiting for it.
+	pub limits: Limits,
+	/// Logging of the params and responses, see [`crate::trace`].
+	pub trace: TraceConfig,
+}
+
+impl Default for WsConfig {
+	fn default() -> Self {
+		Self {
+			subscription: SubscriptionConfig::default(),
+			share_subscriptions: true,
+			keepalive: Some(KeepaliveConfig::default()),
+			#[cfg(feature = "__tls")]
+			tls: None,
+			proxy: Proxy::None,
+			compression: false,
+			allow_unsafe: false,
+			limits: Limits::default(),
+			trace: TraceConfig::default(),
+		}
+	}
+}
+
+/// WebSocket keepalive of a [`WsProvider`].
+///
+/// A ping is sent every `ping_interval`. When nothing, pong or any other message, is received
+/// for `inactive_limit` `max_failures` times in a row, the connection is closed and
+/// [`WsProvider::on_disconnect`] completes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeepaliveConfig {
+	pub ping_interval: Duration,
+	/// Should be longer than a ping round trip.
+	pub inactive_limit: Duration,
+	/// Must be at least 1.
+	pub max_failures: usize,
+}
+
+impl Default for KeepaliveConfig {
+	fn default() -> Self {
+		Self {
+			ping_interval: Duration::from_secs(30),
+			inactive_limit: Duration::from_secs(40),
+			max_failures: 1,
+		}
+	}
+}
+
+impl From<KeepaliveConfig> for PingConfig {
+	fn from(config: KeepaliveConfig) -> Self {
+		PingConfig::new()
+			.ping_interval(config.ping_interval)
+			.inactive_limit(config.inactive_limit)
+			.max_failures(config.max_failures)
+	}
+}
 
 #[derive(Clone)]
 pub struct WsProvider {
 	inner: Option<Arc<Client>>,
 	_url: Option<Url>,
+	config: WsConfig,
+	shared: Arc<SharedSubscriptions>,
+	health: Arc<ConnectionHealth>,
+	stats: Arc<CompressionStats>,
+	compression_negotiated: bool,
+	/// Ids of the pending [`Request::request_raw`], `None` for a client built by the caller.
+	raw_requests: Option<Arc<RawRequests>>,
+	tracer: Tracer,
 }
 
+#[cfg_attr(feature = "send", maybe_async::async_impl)]
+#[cfg_attr(not(feature = "send"), maybe_async::async_impl(?Send))]
 impl ProviderInterface for WsProvider {
 	async fn connect(&mut self) -> Result<()> {
-		let (tx, rx) = WsTransportClientBuilder::default()
-			.build(self._url.clone().unwrap())
-			.await
-			.map_err(|e| Error::Client(Box::new(e)))?;
-		let client = ClientBuilder::default()
-			.max_buffer_capacity_per_subscription(4096)
-			.build_with_tokio(tx, rx);
-
-		self.inner = Some(Arc::new(client));
-		return Ok(());
+		let tracer = self.tracer.clone();
+		let reconnect = self.inner.is_some();
+		tracer.connect(reconnect).run(self.open()).await
 	}
 
 	async fn disconnect(&mut self) -> Result<()> {
@@ -39,6 +145,111 @@ impl ProviderInterface for WsProvider {
 }
 
 impl WsProvider {
+	/// Opens the connection, replacing the current one.
+	async fn open(&mut self) -> Result<()> {
+		let url = self._url.clone().unwrap();
+		let max_message_size = self.config.limits.max_message_size.try_into().unwrap_or(u32::MAX);
+		let builder = WsTransportClientBuilder::default().max_response_size(max_message_size);
+		#[cfg(all(feature = "rustls", not(feature = "native-tls")))]
+		let builder = builder.use_webpki_rustls();
+
+		let proxy = self.config.proxy.for_url(&url)?;
+		let secure = url.scheme() == "wss";
+		#[cfg(feature = "__tls")]
+		let custom_tls = secure && self.config.tls.is_some();
+		#[cfg(not(feature = "__tls"))]
+		let custom_tls = false;
+
+		// The connection is only opened here when `jsonrpsee` can not do it on its own.
+		if !custom_tls && proxy.is_none() && !self.config.compression {
+			let (tx, rx) = builder.build(url).await.map_err(|e| Error::Client(Box::new(e)))?;
+			self.set_client(tx, rx);
+			return Ok(());
+		}
+
+		if secure {
+			#[cfg(feature = "__tls")]
+			{
+				let tls = self.config.tls.clone().unwrap_or_default();
+				let stream = tls.connect(&url, proxy.as_ref()).await?;
+				return self.connect_with_stream(builder, url, stream).await;
+			}
+			#[cfg(not(feature = "__tls"))]
+			return Err(Error::Tls(
+				"`wss` through a proxy or with compression needs the `native-tls` or `rustls` \
+				 feature"
+					.into(),
+			));
+		}
+		let (host, port) = crate::proxy::host_and_port(&url)?;
+		let stream = crate::proxy::connect(proxy.as_ref(), host, port).await?;
+		self.connect_with_stream(builder, url, stream).await
+	}
+
+	/// Runs the WebSocket handshake over an already opened connection.
+	async fn connect_with_stream<T>(
+		&mut self,
+		builder: WsTransportClientBuilder,
+		url: Url,
+		stream: T,
+	) -> Result<()>
+	where
+		T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+	{
+		if self.config.compression {
+			let stats = self.stats.clone();
+			let max_message_size = self.config.limits.max_message_size;
+			let (tx, rx, negotiated) =
+				deflate::handshake(&url, stream, max_message_size, stats).await?;
+			self.set_client(tx, rx);
+			self.compression_negotiated = negotiated;
+			return Ok(());
+		}
+
+		let (tx, rx) = builder
+			.build_with_stream(url, stream)
+			.await
+			.map_err(|e| Error::Client(Box::new(e)))?;
+		self.set_client(tx, rx);
+		self.compression_negotiated = false;
+		Ok(())
+	}
+
+	fn set_client<S, R>(&mut self, tx: S, rx: R)
+	where
+		S: TransportSenderT + Send,
+		R: TransportReceiverT + Send,
+	{
+		// Notifications are moved to the subscription buffers, or to the backlogs of the ones
+		// blocking under `LagPolicy::Block`, right away. The transport buffer only fills up when
+		// the forwarding falls behind the node.
+		let transport_capacity = self
+			.config
+			.subscription
+			.buffer_capacity
+			.max(subscription::DEFAULT_BUFFER_CAPACITY);
+		let mut builder = ClientBuilder::default()
+			.max_buffer_capacity_per_subscription(transport_capacity)
+			.max_concurrent_requests(self.config.limits.max_pending_responses);
+		if let Some(keepalive) = self.config.keepalive {
+			builder = builder.enable_ws_ping(keepalive.into());
+		}
+		let stats = self.tracer.stats();
+		let raw_requests = Arc::new(RawRequests::default());
+		let client = builder.build_with_tokio(
+			RawSender::new(Counted::new(tx, stats.clone()), raw_requests.clone()),
+			RawReceiver::new(
+				Limited::new(Counted::new(rx, stats.clone()), self.config.limits),
+				raw_requests.clone(),
+			),
+		);
+
+		self.inner = Some(Arc::new(client));
+		self.raw_requests = Some(raw_requests);
+		self.shared = Default::default();
+		self.health = Default::default();
+	}
+
 	/// Create a new client to a local Substrate node with default port.
 	pub async fn with_default_url() -> Result<Self> {
 		let mut new_client = Self::new(WS_URL).unwrap();
@@ -53,8 +264,24 @@ impl WsProvider {
 	/// Create a new client with the given url string.
 	/// Example url input: "ws://127.0.0.1:9944"
 	pub fn new(url: &str) -> Result<Self> {
+		Self::new_with_config(url, WsConfig::default())
+	}
+
+	/// Create a new client with the given url string and options.
+	pub fn new_with_config(url: &str, config: WsConfig) -> Result<Self> {
 		let parsed_url: Url = url.parse().map_err(|e| Error::Client(Box::new(e)))?;
-		Ok(Self { inner: None, _url: Some(parsed_url) })
+		let tracer = Tracer::for_url("ws", &parsed_url, config.trace.clone());
+		Ok(Self {
+			inner: None,
+			_url: Some(parsed_url),
+			config,
+			tracer,
+			shared: Default::default(),
+			health: Default::default(),
+			stats: Default::default(),
+			compression_negotiated: false,
+			raw_requests: None,
+		})
 	}
 
 	/// Create a new client with the given address, port and max number of reconnection attempts.
@@ -67,68 +294,198 @@ impl WsProvider {
 	}
 
 	/// Create a new client with a user-generated Jsonrpsee Client.
+	///
+	/// The results of [`Request::request_raw`] are then parsed by the client, and written back to
+	/// JSON with their keys sorted.
 	pub fn new_with_client(client: Client) -> Self {
 		let inner = Some(Arc::new(client));
-		Self { inner, _url: None }
+		Self {
+			inner,
+			_url: None,
+			config: WsConfig::default(),
+			shared: Default::default(),
+			health: Default::default(),
+			stats: Default::default(),
+			compression_negotiated: false,
+			raw_requests: None,
+			tracer: Tracer::new("ws", "custom client".into(), TraceConfig::default()),
+		}
+	}
+
+	/// Whether the node accepted `permessage-deflate` on the current connection, see
+	/// [`WsConfig::compression`].
+	pub fn compression_negotiated(&self) -> bool {
+		self.compression_negotiated
+	}
+
+	/// Bytes received with compression enabled, see [`WsConfig::compression`].
+	pub fn compression_stats(&self) -> &CompressionStats {
+		&self.stats
+	}
+
+	/// Requests, errors, reconnects, subscriptions and bytes of the provider and its clones.
+	pub fn stats(&self) -> Arc<ProviderStats> {
+		self.tracer.stats().clone()
 	}
 
 	/// Checks if the client is connected to the target.
+	///
+	/// A connection found stale by a subscription watchdog, see
+	/// [`SubscriptionConfig::inactivity_timeout`], is reported as disconnected.
 	pub fn is_connected(&self) -> bool {
-		self.inner().is_connected()
+		self.inner().is_connected() && self.health.stale_reason().is_none()
 	}
 
 	/// This is similar to [`Client::on_disconnect`] but it can be used to get
 	/// the reason why the client was disconnected but it's not cancel-safe.
 	///
 	/// The typical use-case is that this method will be called after
-	/// [`Client::on_disconnect`] has returned in a "select loop".
+	/// [`Client::on_disconnect`] has returned in a "select loop". A stale connection is
+	/// reported as [`JsonrpseeError::Custom`].
 	///
 	/// # Cancel-safety
 	///
 	/// This method is not cancel-safe
 	pub async fn disconnect_reason(&self) -> JsonrpseeError {
-		self.inner().disconnect_reason().await
+		let client = self.inner();
+		tokio::select! {
+			reason = client.disconnect_reason() => reason,
+			reason = self.health.stale() => JsonrpseeError::Custom(format!("Connection stale: {reason}")),
+		}
 	}
 
-	/// Completes when the client is disconnected or the client's background task encountered an error.
+	/// Completes when the client is disconnected, the client's background task encountered an
+	/// error or the connection was found stale.
 	/// If the client is already disconnected, the future produced by this method will complete immediately.
 	///
 	/// # Cancel safety
 	///
 	/// This method is cancel safe.
 	pub async fn on_disconnect(&self) {
-		self.inner().on_disconnect().await;
+		let client = self.inner();
+		tokio::select! {
+			_ = client.on_disconnect() => {},
+			_ = self.health.stale() => {},
+		}
 	}
 }
 
-#[maybe_async::async_impl(?Send)]
+#[cfg_attr(feature = "send", maybe_async::async_impl)]
+#[cfg_attr(not(feature = "send"), maybe_async::async_impl(?Send))]
 impl Request for WsProvider {
-	async fn request_raw(&self, method: &str, params: RpcParams) -> Result<String> {
-		self.request::<String>(method, params).await
+	async fn request_raw(&self, method: &str, params: RpcParams) -> Result<Box<RawValue>> {
+		let Some(raw_requests) = &self.raw_requests else {
+			// Nothing turns the results of a custom client into strings, see `raw`.
+			let result = self.request::<Value>(method, params).await?;
+			return Ok(serde_json::value::to_raw_value(&result)?)
+		};
+		let call = self.tracer.request(method, self.tracer.next_id(), &params);
+		call.run(async {
+			check_method(method, self.config.allow_unsafe)?;
+			let raw = raw_requests.track();
+			let result: String = self
+				.unless_stale(self.inner().request(&raw.method(method), RpcParamsWrapper(params)))
+				.await?;
+			Ok(RawValue::from_string(result)?)
+		})
+		.await
 	}
 
-	async fn request<R: DeserializeOwned>(&self, method: &str, params: RpcParams) -> Result<R> {
-		self.inner()
-			.request(method, RpcParamsWrapper(params))
-			.await
-			.map_err(|e| Error::Client(Box::new(e)))
+	async fn request<R: DeserializeOwned + Debug>(
+		&self,
+		method: &str,
+		params: RpcParams,
+	) -> Result<R> {
+		let call = self.tracer.request(method, self.tracer.next_id(), &params);
+		call.run(async {
+			check_method(method, self.config.allow_unsafe)?;
+			self.unless_stale(self.inner().request(method, RpcParamsWrapper(params))).await
+		})
+		.await
 	}
 }
 
-#[maybe_async::async_impl(?Send)]
+#[cfg_attr(feature = "send", maybe_async::async_impl)]
+#[cfg_attr(not(feature = "send"), maybe_async::async_impl(?Send))]
 impl Subscribe for WsProvider {
-	type Subscription<Notification> = SubscriptionWrapper<Notification> where Notification: DeserializeOwned;
+	type Subscription<Notification> = SubscriptionWrapper<Notification> where Notification: DeserializeOwned + MaybeSend;
 
-	async fn subscribe<Notification: DeserializeOwned>(
+	async fn subscribe<Notification: DeserializeOwned + MaybeSend>(
 		&self,
 		sub: &str,
 		params: RpcParams,
 		unsub: &str,
 	) -> Result<Self::Subscription<Notification>> {
-		self.inner()
-			.subscribe(sub, RpcParamsWrapper(params), unsub)
-			.await
-			.map(|sub| sub.into())
-			.map_err(|e| Error::Client(Box::new(e)))
+		let call = self.tracer.subscribe(sub, self.tracer.next_id(), &params);
+		let subscription = call
+			.run_without_response(async {
+				check_method(sub, self.config.allow_unsafe)?;
+				self.open_subscription(sub, params, unsub).await
+			})
+			.await?;
+		Ok(subscription.counted_in(self.tracer.stats()))
+	}
+}
+
+impl WsProvider {
+	/// Subscribes, sharing an upstream subscription when enabled.
+	async fn open_subscription<Notification: DeserializeOwned + MaybeSend>(
+		&self,
+		sub: &str,
+		params: RpcParams,
+		unsub: &str,
+	) -> Result<SubscriptionWrapper<Notification>> {
+		let config = self.config.subscription;
+		if !self.config.share_subscriptions {
+			let upstream = self.unless_stale(self.subscribe_upstream(sub, params, unsub)).await?;
+			return Ok(SubscriptionWrapper::attach(Arc::new(upstream), config));
+		}
+
+		let slot = self.shared.slot(SubscriptionKey::new(sub, params.clone().build(), unsub));
+		// Held while subscribing, so that concurrent identical subscriptions end up sharing.
+		let mut shared = slot.lock().await;
+		if let Some(upstream) = shared.upgrade().filter(|upstream| !upstream.is_closed()) {
+			return Ok(SubscriptionWrapper::attach(upstream, config));
+		}
+
+		let upstream =
+			Arc::new(self.unless_stale(self.subscribe_upstream(sub, params, unsub)).await?);
+		*shared = Arc::downgrade(&upstream);
+		Ok(SubscriptionWrapper::attach(upstream, config))
+	}
+
+	/// Runs `request` on the connection, failing with [`Error::ConnectionClosed`] as soon as the
+	/// connection is found stale. A stale connection may never answer.
+	async fn unless_stale<T, E: Into<Error>>(
+		&self,
+		request: impl Future<Output = core::result::Result<T, E>>,
+	) -> Result<T> {
+		if self.health.stale_reason().is_some() {
+			return Err(Error::ConnectionClosed);
+		}
+		tokio::select! {
+			biased;
+
+			_ = self.health.stale() => Err(Error::ConnectionClosed),
+			result = request => result.map_err(Into::into),
+		}
+	}
+
+	async fn subscribe_upstream(
+		&self,
+		sub: &str,
+		params: RpcParams,
+		unsub: &str,
+	) -> Result<Upstream> {
+		let client = self.inner();
+		let inner = client.subscribe::<Value, _>(sub, RpcParamsWrapper(params), unsub).await?;
+		let watch = Watch {
+			sub: sub.to_string(),
+			client: Arc::downgrade(&client),
+			health: self.health.clone(),
+			inactivity_timeout: self.config.subscription.inactivity_timeout,
+			max_notifications_per_second: self.config.limits.max_notifications_per_second,
+		};
+		Ok(Upstream::spawn(inner, watch))
 	}
 }
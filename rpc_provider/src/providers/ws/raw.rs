@@ -0,0 +1,239 @@
+//! Hands the `result` of [`crate::Request::request_raw`] over as the node sent it.
+//!
+//! The `jsonrpsee` WebSocket client parses every result into a `serde_json::Value`, which sorts
+//! the keys of objects and normalises numbers. The method of a raw request is prefixed with
+//! [`RAW_PREFIX`] and a token of the [`RawRequest`]: the sender strips both and remembers the id
+//! of the request, and the receiver turns the `result` answered for this id into a JSON string,
+//! which the client hands over as is. Dropping the [`RawRequest`] forgets the id, so requests
+//! timing out or cancelled before their response do not stay tracked.
+use crate::codec::deserialize_some;
+use jsonrpsee::core::client::{ReceivedMessage, TransportReceiverT, TransportSenderT};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use std::{
+	borrow::Cow,
+	collections::HashMap,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc, Mutex,
+	},
+};
+
+pub(crate) const RAW_PREFIX: &str = "rpc_provider_raw:";
+
+/// Ids of the raw requests waiting for their response, with the token of their [`RawRequest`].
+#[derive(Default)]
+pub(crate) struct RawRequests {
+	next_token: AtomicU64,
+	ids: Mutex<HashMap<String, u64>>,
+}
+
+impl RawRequests {
+	/// Starts tracking a raw request, until its response is received or the returned
+	/// [`RawRequest`] is dropped.
+	pub(crate) fn track(self: &Arc<Self>) -> RawRequest {
+		let token = self.next_token.fetch_add(1, Ordering::Relaxed);
+		RawRequest { requests: self.clone(), token }
+	}
+
+	fn is_empty(&self) -> bool {
+		self.ids.lock().unwrap().is_empty()
+	}
+}
+
+/// A raw request sent through the client, see [`RawRequests::track`].
+pub(crate) struct RawRequest {
+	requests: Arc<RawRequests>,
+	token: u64,
+}
+
+impl RawRequest {
+	/// The method to hand to the client in place of `method`.
+	pub(crate) fn method(&self, method: &str) -> String {
+		format!("{RAW_PREFIX}{}:{method}", self.token)
+	}
+}
+
+impl Drop for RawRequest {
+	fn drop(&mut self) {
+		self.requests.ids.lock().unwrap().retain(|_, token| *token != self.token);
+	}
+}
+
+#[derive(Deserialize)]
+struct Request<'a> {
+	#[serde(borrow)]
+	id: &'a RawValue,
+	#[serde(borrow)]
+	method: Cow<'a, str>,
+}
+
+#[derive(Deserialize)]
+struct Response<'a> {
+	#[serde(borrow)]
+	id: Option<&'a RawValue>,
+	/// A `null` result is kept as `Some`, only a missing field maps to `None`.
+	#[serde(borrow, default, deserialize_with = "deserialize_some")]
+	result: Option<&'a RawValue>,
+	#[serde(borrow, default)]
+	error: Option<&'a RawValue>,
+}
+
+/// Sender stripping [`RAW_PREFIX`] from the methods of the requests.
+pub(crate) struct RawSender<S> {
+	inner: S,
+	requests: Arc<RawRequests>,
+}
+
+impl<S> RawSender<S> {
+	pub(crate) fn new(inner: S, requests: Arc<RawRequests>) -> Self {
+		Self { inner, requests }
+	}
+
+	fn strip_prefix(&self, message: String) -> String {
+		if !message.contains(RAW_PREFIX) {
+			return message
+		}
+		let (id, token) = match serde_json::from_str::<Request>(&message) {
+			Ok(request) => match request
+				.method
+				.strip_prefix(RAW_PREFIX)
+				.and_then(|method| method.split_once(':'))
+				.and_then(|(token, _)| token.parse::<u64>().ok())
+			{
+				Some(token) => (request.id.get().to_string(), token),
+				None => return message,
+			},
+			Err(_) => return message,
+		};
+		self.requests.ids.lock().unwrap().insert(id, token);
+		// The method comes before the params, which may contain the prefix as well.
+		message.replacen(&format!("\"{RAW_PREFIX}{token}:"), "\"", 1)
+	}
+}
+
+#[async_trait::async_trait]
+impl<S: TransportSenderT + Send> TransportSenderT for RawSender<S> {
+	type Error = S::Error;
+
+	async fn send(&mut self, message: String) -> core::result::Result<(), Self::Error> {
+		let message = self.strip_prefix(message);
+		self.inner.send(message).await
+	}
+
+	async fn send_ping(&mut self) -> core::result::Result<(), Self::Error> {
+		self.inner.send_ping().await
+	}
+
+	async fn close(&mut self) -> core::result::Result<(), Self::Error> {
+		self.inner.close().await
+	}
+}
+
+/// Receiver turning the results of raw requests into JSON strings.
+pub(crate) struct RawReceiver<R> {
+	inner: R,
+	requests: Arc<RawRequests>,
+}
+
+impl<R> RawReceiver<R> {
+	pub(crate) fn new(inner: R, requests: Arc<RawRequests>) -> Self {
+		Self { inner, requests }
+	}
+
+	fn wrap_result(&self, message: &[u8]) -> Option<String> {
+		// Batches and notifications fail to parse, and are left to the client.
+		let response = serde_json::from_slice::<Response>(message).ok()?;
+		let id = response.id?;
+		if response.result.is_none() && response.error.is_none() {
+			return None
+		}
+		self.requests.ids.lock().unwrap().remove(id.get())?;
+		// Errors are left to the client, the id is forgotten all the same.
+		let result = serde_json::to_string(response.result?.get()).ok()?;
+		Some(format!(r#"{{"jsonrpc":"2.0","id":{},"result":{result}}}"#, id.get()))
+	}
+}
+
+#[async_trait::async_trait]
+impl<R: TransportReceiverT + Send> TransportReceiverT for RawReceiver<R> {
+	type Error = R::Error;
+
+	async fn receive(&mut self) -> core::result::Result<ReceivedMessage, Self::Error> {
+		let message = self.inner.receive().await?;
+		if self.requests.is_empty() {
+			return Ok(message)
+		}
+		let wrapped = match &message {
+			ReceivedMessage::Text(text) => self.wrap_result(text.as_bytes()),
+			ReceivedMessage::Bytes(bytes) => self.wrap_result(bytes),
+			ReceivedMessage::Pong => None,
+		};
+		Ok(wrapped.map_or(message, ReceivedMessage::Text))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn requests(ids: &[&str]) -> Arc<RawRequests> {
+		let ids = ids.iter().map(|id| (id.to_string(), 0)).collect();
+		Arc::new(RawRequests { ids: Mutex::new(ids), ..Default::default() })
+	}
+
+	#[test]
+	fn prefix_is_stripped_and_id_remembered() {
+		let sender = RawSender::new((), requests(&[]));
+		let request = sender.requests.track();
+		let message = format!(
+			r#"{{"jsonrpc":"2.0","id":7,"method":"{}","params":["{RAW_PREFIX}"]}}"#,
+			request.method("state_call")
+		);
+		assert_eq!(
+			sender.strip_prefix(message),
+			format!(
+				r#"{{"jsonrpc":"2.0","id":7,"method":"state_call","params":["{RAW_PREFIX}"]}}"#
+			)
+		);
+		assert!(sender.requests.ids.lock().unwrap().contains_key("7"));
+
+		let message = r#"{"jsonrpc":"2.0","id":8,"method":"state_call","params":[]}"#;
+		assert_eq!(sender.strip_prefix(message.into()), message);
+		assert!(!sender.requests.ids.lock().unwrap().contains_key("8"));
+
+		// A request timing out or cancelled before its response is forgotten.
+		drop(request);
+		assert!(sender.requests.is_empty());
+	}
+
+	#[test]
+	fn only_results_of_raw_requests_are_wrapped() {
+		let receiver = RawReceiver::new((), requests(&["7", "8"]));
+		let response = br#"{"jsonrpc":"2.0","result":{"b":1,"a":1.50},"id":7}"#;
+		assert_eq!(
+			receiver.wrap_result(response).unwrap(),
+			r#"{"jsonrpc":"2.0","id":7,"result":"{\"b\":1,\"a\":1.50}"}"#
+		);
+		// Answered once.
+		assert_eq!(receiver.wrap_result(response), None);
+
+		let receiver = RawReceiver::new((), requests(&["7", "8"]));
+		assert_eq!(
+			receiver.wrap_result(br#"{"jsonrpc":"2.0","result":null,"id":7}"#).unwrap(),
+			r#"{"jsonrpc":"2.0","id":7,"result":"null"}"#
+		);
+		// Neither a result nor an error, the id is kept.
+		assert_eq!(receiver.wrap_result(br#"{"jsonrpc":"2.0","id":8}"#), None);
+		assert!(!receiver.requests.is_empty());
+
+		let error = br#"{"jsonrpc":"2.0","error":{"code":-32000,"message":"no"},"id":8}"#;
+		assert_eq!(receiver.wrap_result(error), None);
+		assert!(receiver.requests.is_empty());
+
+		let receiver = RawReceiver::new((), requests(&["7"]));
+		assert_eq!(receiver.wrap_result(br#"{"jsonrpc":"2.0","result":1,"id":9}"#), None);
+		assert_eq!(receiver.wrap_result(br#"[{"jsonrpc":"2.0","result":1,"id":7}]"#), None);
+		assert!(!receiver.requests.is_empty());
+	}
+}
@@ -1,30 +1,127 @@
-use crate::{Error, HandleSubscription, Result};
-use jsonrpsee::core::client::Subscription;
+use super::{buffer::NotificationBuffer, upstream::Upstream};
+use crate::{
+	stats::{ActiveSubscription, ProviderStats},
+	HandleSubscription, MaybeSend, Result,
+};
+use futures::{Stream, StreamExt};
 use serde::de::DeserializeOwned;
+use std::{
+	marker::PhantomData,
+	pin::Pin,
+	sync::Arc,
+	task::{Context, Poll},
+	time::Duration,
+};
 
-#[derive(Debug)]
+/// Default number of notifications buffered per subscription.
+pub const DEFAULT_BUFFER_CAPACITY: usize = 4096;
+
+/// What to do with new notifications when the consumer of a subscription falls behind and its
+/// buffer is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LagPolicy {
+	/// Yield [`crate::Error::SubscriptionLagged`] once the buffered notifications are consumed,
+	/// then end the subscription.
+	#[default]
+	Error,
+	/// Drop the oldest buffered notification and yield [`crate::Error::SubscriptionLagged`] with
+	/// the number of dropped notifications before the next one.
+	DropOldest,
+	/// Keep the new notifications in a backlog until the consumer catches up. The backlog holds
+	/// as many notifications as the buffer, and at least [`DEFAULT_BUFFER_CAPACITY`]. Once it is
+	/// full as well, [`crate::Error::SubscriptionLagged`] is yielded after the kept notifications
+	/// and the subscription ends. Handles sharing the subscription are not held back.
+	Block,
+}
+
+/// Buffering of the subscriptions opened by a provider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriptionConfig {
+	/// Maximum number of notifications waiting to be consumed.
+	pub buffer_capacity: usize,
+	pub lag_policy: LagPolicy,
+	/// Longest time a subscription may go without notification. Once elapsed, the connection is
+	/// considered stale: [`super::WsProvider::is_connected`] returns `false` and every
+	/// subscription of the provider ends with [`crate::Error::ConnectionClosed`].
+	///
+	/// Only set it when notifications are expected at a steady pace, for instance a few block
+	/// times for new heads. Disabled by default.
+	pub inactivity_timeout: Option<Duration>,
+}
+
+impl Default for SubscriptionConfig {
+	fn default() -> Self {
+		Self {
+			buffer_capacity: DEFAULT_BUFFER_CAPACITY,
+			lag_policy: LagPolicy::default(),
+			inactivity_timeout: None,
+		}
+	}
+}
+
+/// Active subscription, usable as a [`Stream`] of notifications.
+///
+/// When the connection is lost, the stream yields [`crate::Error::ConnectionClosed`] after the
+/// buffered notifications and ends.
+/// Identical subscriptions of a provider may share one subscription on the node, see
+/// [`super::WsConfig::share_subscriptions`]. Each handle has its own buffer and lag policy.
+/// Dropping the last handle unsubscribes from the node, [`HandleSubscription::unsubscribe`] can
+/// be used to wait until the node acknowledged it.
 pub struct SubscriptionWrapper<Notification> {
-	inner: Subscription<Notification>,
+	buffer: Arc<NotificationBuffer>,
+	upstream: Option<Arc<Upstream>>,
+	active: Option<ActiveSubscription>,
+	_marker: PhantomData<fn() -> Notification>,
 }
 
-#[maybe_async::async_impl(?Send)]
-impl<Notification: DeserializeOwned> HandleSubscription<Notification>
-	for SubscriptionWrapper<Notification>
-{
-	async fn next(&mut self) -> Option<Result<Notification>> {
-		self.inner
-			.next()
-			.await
-			.map(|result| result.map_err(|e| Error::Client(Box::new(e))))
+impl<Notification> SubscriptionWrapper<Notification> {
+	/// Attaches a new handle to a subscription opened on the node.
+	pub(crate) fn attach(upstream: Arc<Upstream>, config: SubscriptionConfig) -> Self {
+		let buffer = Arc::new(NotificationBuffer::new(config.buffer_capacity, config.lag_policy));
+		upstream.attach(buffer.clone());
+
+		Self { buffer, upstream: Some(upstream), active: None, _marker: PhantomData }
 	}
 
-	async fn unsubscribe(self) -> Result<()> {
-		self.inner.unsubscribe().await.map_err(|e| Error::Client(Box::new(e)))
+	/// Counts the handle as an active subscription in `stats` until dropped.
+	pub(crate) fn counted_in(mut self, stats: &Arc<ProviderStats>) -> Self {
+		self.active = Some(stats.subscription_opened());
+		self
 	}
 }
 
-impl<Notification> From<Subscription<Notification>> for SubscriptionWrapper<Notification> {
-	fn from(inner: Subscription<Notification>) -> Self {
-		Self { inner }
+impl<Notification: DeserializeOwned> Stream for SubscriptionWrapper<Notification> {
+	type Item = Result<Notification>;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		self.buffer.poll_next(cx).map(|notification| {
+			notification.map(|notification| Ok(serde_json::from_value(notification?)?))
+		})
+	}
+}
+
+impl<Notification> Drop for SubscriptionWrapper<Notification> {
+	fn drop(&mut self) {
+		self.buffer.close_consumer();
+	}
+}
+
+#[cfg_attr(feature = "send", maybe_async::async_impl)]
+#[cfg_attr(not(feature = "send"), maybe_async::async_impl(?Send))]
+impl<Notification: DeserializeOwned + MaybeSend> HandleSubscription<Notification>
+	for SubscriptionWrapper<Notification>
+{
+	async fn next(&mut self) -> Option<Result<Notification>> {
+		StreamExt::next(self).await
+	}
+
+	/// Only unsubscribes from the node when no other handle shares the subscription.
+	async fn unsubscribe(mut self) -> Result<()> {
+		self.buffer.close_consumer();
+		self.active.take();
+		match self.upstream.take().and_then(Arc::into_inner) {
+			Some(upstream) => upstream.unsubscribe().await,
+			None => Ok(()),
+		}
 	}
 }
@@ -0,0 +1,384 @@
+//! Subscriptions opened on the node, fanned out to one or more local handles.
+use super::{
+	buffer::{Failure, NotificationBuffer, Sink},
+	health::ConnectionHealth,
+};
+use crate::{
+	limits::{Limit, NotificationRate},
+	Error, Result,
+};
+use futures::{future::BoxFuture, Stream, StreamExt};
+use jsonrpsee::core::client::{Client, Error as ClientError, Subscription};
+use serde_json::Value;
+use std::{
+	collections::HashMap,
+	sync::{Arc, Mutex, Weak},
+	time::{Duration, Instant},
+};
+use tokio::sync::oneshot;
+
+/// Request to unsubscribe from the node, answered once the node acknowledged it.
+type UnsubscribeRequest = oneshot::Sender<Result<()>>;
+
+/// Notifications of a subscription opened on the node.
+pub(crate) trait Source:
+	Stream<Item = core::result::Result<Value, ClientError>> + Send + Unpin + 'static
+{
+	/// Unsubscribes and waits for the answer of the node.
+	fn unsubscribe(self) -> BoxFuture<'static, Result<()>>;
+}
+
+impl Source for Subscription<Value> {
+	fn unsubscribe(self) -> BoxFuture<'static, Result<()>> {
+		Box::pin(async move { Subscription::unsubscribe(self).await.map_err(Error::from) })
+	}
+}
+
+/// A subscription opened on the node.
+///
+/// Every local handle keeps it alive through an `Arc`. Once the last one is dropped, the
+/// forwarding task stops and the node subscription is dropped, which unsubscribes.
+pub(crate) struct Upstream {
+	sinks: Arc<Sinks>,
+	unsubscribe: Option<oneshot::Sender<UnsubscribeRequest>>,
+}
+
+/// What the forwarding task of an [`Upstream`] watches besides the notifications.
+pub(crate) struct Watch {
+	/// Method the subscription was opened with.
+	pub(crate) sub: String,
+	pub(crate) client: Weak<Client>,
+	pub(crate) health: Arc<ConnectionHealth>,
+	/// Longest time without notification before the connection is marked as stale.
+	pub(crate) inactivity_timeout: Option<Duration>,
+	/// Most notifications within a second before the subscription is ended.
+	pub(crate) max_notifications_per_second: Option<u32>,
+}
+
+impl Upstream {
+	/// Spawns the task moving notifications from the transport into the handle buffers.
+	pub(crate) fn spawn(inner: impl Source, watch: Watch) -> Self {
+		let sinks = Arc::new(Sinks::default());
+		let (unsubscribe, unsubscribe_requests) = oneshot::channel();
+		tokio::spawn(forward_notifications(inner, sinks.clone(), unsubscribe_requests, watch));
+
+		Self { sinks, unsubscribe: Some(unsubscribe) }
+	}
+
+	/// Adds a handle buffer, which receives the notifications from now on.
+	pub(crate) fn attach(&self, buffer: Arc<NotificationBuffer>) {
+		self.sinks.attach(buffer)
+	}
+
+	pub(crate) fn is_closed(&self) -> bool {
+		self.sinks.state.lock().unwrap().closed
+	}
+
+	/// Unsubscribes from the node and waits for its answer.
+	pub(crate) async fn unsubscribe(mut self) -> Result<()> {
+		let (send_back, response) = oneshot::channel();
+		let Some(unsubscribe) = self.unsubscribe.take() else { return Ok(()) };
+		if unsubscribe.send(send_back).is_err() {
+			// The notifications already ended, the node dropped the subscription.
+			return Ok(());
+		}
+		response.await.unwrap_or(Ok(()))
+	}
+}
+
+/// Handles attached to an upstream subscription.
+#[derive(Default)]
+struct Sinks {
+	state: Mutex<SinksState>,
+}
+
+#[derive(Default)]
+struct SinksState {
+	sinks: Vec<Sink>,
+	closed: bool,
+	failure: Option<Failure>,
+}
+
+impl Sinks {
+	fn attach(&self, buffer: Arc<NotificationBuffer>) {
+		let mut state = self.state.lock().unwrap();
+		if state.closed {
+			match &state.failure {
+				Some(failure) => buffer.close_failed(failure.clone()),
+				None => buffer.close(),
+			}
+			return;
+		}
+		state.sinks.push(Sink::new(buffer));
+	}
+
+	/// Hands a notification to every handle, detaching the ones which take no more.
+	fn push(&self, notification: Value) {
+		self.state.lock().unwrap().sinks.retain(|sink| sink.push(notification.clone()));
+	}
+
+	fn close(&self, failure: Option<Failure>) {
+		let mut state = self.state.lock().unwrap();
+		state.closed = true;
+		for sink in state.sinks.drain(..) {
+			sink.close(failure.clone());
+		}
+		state.failure = failure;
+	}
+}
+
+/// Why the notifications of an upstream subscription stopped.
+enum End {
+	/// A handle asked to unsubscribe, the node's answer is sent back to it.
+	Unsubscribe(Option<UnsubscribeRequest>),
+	/// Every handle was dropped, or the node ended the subscription.
+	Closed,
+	/// The connection was lost or found stale, the node broke a limit, or the client failed to
+	/// hand a notification over.
+	Failed(Failure),
+}
+
+async fn forward_notifications<S: Source>(
+	mut inner: S,
+	sinks: Arc<Sinks>,
+	mut unsubscribe_requests: oneshot::Receiver<UnsubscribeRequest>,
+	watch: Watch,
+) {
+	let mut rate = watch.max_notifications_per_second.map(NotificationRate::new);
+	let end = loop {
+		tokio::select! {
+			biased;
+
+			request = &mut unsubscribe_requests => break End::Unsubscribe(request.ok()),
+			_ = watch.health.stale() => break End::Failed(Failure::Disconnected),
+			notification = next_notification(&mut inner, watch.inactivity_timeout) => {
+				let notification = match notification {
+					Ok(Some(Ok(notification))) => notification,
+					Ok(Some(Err(e))) => break End::Failed(Failure::Notification(Arc::new(e.into()))),
+					Ok(None) if watch.is_connected() => break End::Closed,
+					Ok(None) => break End::Failed(Failure::Disconnected),
+					Err(timeout) => {
+						watch.health.mark_stale(format!(
+							"No notification received for {timeout:?} on subscription `{}`",
+							watch.sub
+						));
+						break End::Failed(Failure::Disconnected);
+					},
+				};
+				if rate.as_mut().is_some_and(|rate| !rate.record(Instant::now())) {
+					break End::Failed(Failure::LimitExceeded(Limit::NotificationRate));
+				}
+				sinks.push(notification);
+			},
+		}
+	};
+	sinks.close(match &end {
+		End::Failed(failure) => Some(failure.clone()),
+		End::Unsubscribe(_) | End::Closed => None,
+	});
+
+	// Otherwise dropping `inner` unsubscribes without waiting for the node.
+	if let End::Unsubscribe(Some(send_back)) = end {
+		let _ = send_back.send(inner.unsubscribe().await);
+	}
+}
+
+/// Waits for the next notification, failing with the timeout once it elapsed.
+async fn next_notification<S: Source>(
+	inner: &mut S,
+	inactivity_timeout: Option<Duration>,
+) -> core::result::Result<Option<core::result::Result<Value, ClientError>>, Duration> {
+	match inactivity_timeout {
+		Some(timeout) => tokio::time::timeout(timeout, inner.next()).await.map_err(|_| timeout),
+		None => Ok(inner.next().await),
+	}
+}
+
+impl Watch {
+	fn is_connected(&self) -> bool {
+		self.client.upgrade().is_some_and(|client| client.is_connected())
+	}
+}
+
+/// Identifies subscriptions that can share the same upstream subscription.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct SubscriptionKey {
+	sub: String,
+	params: Option<String>,
+	unsub: String,
+}
+
+impl SubscriptionKey {
+	pub(crate) fn new(sub: &str, params: Option<String>, unsub: &str) -> Self {
+		Self { sub: sub.to_string(), params, unsub: unsub.to_string() }
+	}
+}
+
+/// Upstream subscriptions of a provider, by method and parameters.
+#[derive(Default)]
+pub(crate) struct SharedSubscriptions {
+	slots: Mutex<HashMap<SubscriptionKey, Arc<Slot>>>,
+}
+
+/// Upstream subscription of a key, locked while it is being opened.
+pub(crate) type Slot = tokio::sync::Mutex<Weak<Upstream>>;
+
+impl SharedSubscriptions {
+	/// The slot of `key`. Opening a subscription only holds back the identical ones.
+	pub(crate) fn slot(&self, key: SubscriptionKey) -> Arc<Slot> {
+		let mut slots = self.slots.lock().unwrap();
+		// A slot is forgotten once nobody opens its subscription and every handle is dropped.
+		slots.retain(|_, slot| {
+			Arc::strong_count(slot) > 1
+				|| slot.try_lock().map_or(true, |upstream| upstream.strong_count() > 0)
+		});
+		slots.entry(key).or_default().clone()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::ws::{subscription::DEFAULT_BUFFER_CAPACITY, LagPolicy};
+	use futures::channel::mpsc;
+	use serde_json::json;
+
+	type Notifications = mpsc::UnboundedReceiver<core::result::Result<Value, ClientError>>;
+
+	impl Source for Notifications {
+		fn unsubscribe(self) -> BoxFuture<'static, Result<()>> {
+			Box::pin(async { Ok(()) })
+		}
+	}
+
+	/// Without a client, the end of the notifications is reported as a lost connection.
+	fn watch() -> Watch {
+		Watch {
+			sub: "mock_subscribe".into(),
+			client: Weak::new(),
+			health: Default::default(),
+			inactivity_timeout: None,
+			max_notifications_per_second: None,
+		}
+	}
+
+	fn notifications(count: u64) -> Vec<core::result::Result<Value, ClientError>> {
+		(0..count).map(|i| Ok(json!(i))).collect()
+	}
+
+	/// What a handle reads, with errors as their debug output.
+	async fn read(buffer: Arc<NotificationBuffer>) -> Vec<core::result::Result<u64, String>> {
+		futures::stream::poll_fn(|cx| buffer.poll_next(cx))
+			.map(|notification| match notification {
+				Ok(notification) => Ok(notification.as_u64().unwrap()),
+				Err(e) => Err(format!("{e:?}")),
+			})
+			.collect()
+			.await
+	}
+
+	/// Forwards every notification to a handle which only starts reading once they all ended.
+	async fn forward(
+		notifications: Vec<core::result::Result<Value, ClientError>>,
+		capacity: usize,
+		lag_policy: LagPolicy,
+	) -> Vec<core::result::Result<u64, String>> {
+		let (sender, receiver) = mpsc::unbounded();
+		for notification in notifications {
+			sender.unbounded_send(notification).unwrap();
+		}
+		drop(sender);
+
+		let sinks = Arc::new(Sinks::default());
+		let buffer = Arc::new(NotificationBuffer::new(capacity, lag_policy));
+		sinks.attach(buffer.clone());
+		let (_unsubscribe, unsubscribe_requests) = oneshot::channel();
+		forward_notifications(receiver, sinks, unsubscribe_requests, watch()).await;
+		read(buffer).await
+	}
+
+	fn closed() -> core::result::Result<u64, String> {
+		Err("ConnectionClosed".into())
+	}
+
+	#[tokio::test]
+	async fn failed_notification_ends_the_handles_with_its_error() {
+		let invalid = serde_json::from_str::<Value>("{").unwrap_err();
+		let notifications = vec![Ok(json!(0)), Err(ClientError::ParseError(invalid)), Ok(json!(1))];
+
+		let read = forward(notifications, 16, LagPolicy::Error).await;
+		assert_eq!(read.len(), 2);
+		assert_eq!(read[0], Ok(0));
+		assert!(read[1].as_ref().is_err_and(|e| e.contains("ParseError")), "{:?}", read[1]);
+	}
+
+	#[tokio::test]
+	async fn drop_oldest_reports_lagged_notifications() {
+		assert_eq!(
+			forward(notifications(50), 4, LagPolicy::DropOldest).await,
+			[Err("SubscriptionLagged(46)".into()), Ok(46), Ok(47), Ok(48), Ok(49), closed()]
+		);
+	}
+
+	#[tokio::test]
+	async fn error_policy_ends_a_lagging_handle() {
+		assert_eq!(
+			forward(notifications(50), 4, LagPolicy::Error).await,
+			[Ok(0), Ok(1), Ok(2), Ok(3), Err("SubscriptionLagged(1)".into())]
+		);
+	}
+
+	#[tokio::test]
+	async fn block_policy_delivers_every_notification() {
+		let (sender, receiver) = mpsc::unbounded();
+		for notification in notifications(50) {
+			sender.unbounded_send(notification).unwrap();
+		}
+		drop(sender);
+
+		let sinks = Arc::new(Sinks::default());
+		let buffer = Arc::new(NotificationBuffer::new(4, LagPolicy::Block));
+		sinks.attach(buffer.clone());
+		let (_unsubscribe, unsubscribe_requests) = oneshot::channel();
+		let forwarding =
+			tokio::spawn(forward_notifications(receiver, sinks, unsubscribe_requests, watch()));
+
+		let mut expected = (0..50).map(Ok).collect::<Vec<_>>();
+		expected.push(closed());
+		assert_eq!(read(buffer).await, expected);
+		forwarding.await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn blocked_handle_does_not_hold_back_the_others() {
+		let (sender, receiver) = mpsc::unbounded();
+		let sinks = Arc::new(Sinks::default());
+		// Full after the first notification, and not read until the end.
+		let blocked = Arc::new(NotificationBuffer::new(1, LagPolicy::Block));
+		let buffer = Arc::new(NotificationBuffer::new(16, LagPolicy::Block));
+		sinks.attach(blocked.clone());
+		sinks.attach(buffer.clone());
+		let (_unsubscribe, unsubscribe_requests) = oneshot::channel();
+		tokio::spawn(forward_notifications(receiver, sinks, unsubscribe_requests, watch()));
+
+		let mut notifications = futures::stream::poll_fn(|cx| buffer.poll_next(cx));
+		for i in 0..3 {
+			sender.unbounded_send(Ok(json!(i))).unwrap();
+			assert_eq!(notifications.next().await.unwrap().unwrap(), json!(i));
+		}
+
+		drop(sender);
+		assert_eq!(read(blocked).await, [Ok(0), Ok(1), Ok(2), closed()]);
+	}
+
+	#[tokio::test]
+	async fn blocked_handle_lags_once_its_backlog_is_full() {
+		const SENT: u64 = DEFAULT_BUFFER_CAPACITY as u64 + 16;
+
+		let read = forward(notifications(SENT), 1, LagPolicy::Block).await;
+		let (lagged, delivered) = read.split_last().unwrap();
+		assert_eq!(lagged, &Err("SubscriptionLagged(1)".into()));
+		assert!(delivered.len() >= DEFAULT_BUFFER_CAPACITY && delivered.len() < SENT as usize);
+		assert!(delivered.iter().enumerate().all(|(i, read)| read == &Ok(i as u64)));
+	}
+}
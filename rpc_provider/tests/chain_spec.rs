@@ -0,0 +1,57 @@
+//! Refresh of the checkpoint of a chain spec from a node.
+mod common;
+
+use jsonrpsee::server::{RpcModule, ServerHandle};
+use rpc_provider::chain_spec::refresh_chain_spec;
+use serde_json::{json, Value};
+use std::{fs, net::SocketAddr, path::PathBuf};
+
+fn spec(id: &str, checkpoint: Option<u64>) -> Value {
+	let mut spec = json!({ "id": id, "genesis": { "raw": { "top": { "0x00": "0x01" } } } });
+	if let Some(checkpoint) = checkpoint {
+		spec["lightSyncState"] = json!({ "finalizedBlockHeader": checkpoint });
+	}
+	spec
+}
+
+/// Serves `sync_state_genSyncSpec` like a node of the chain `id`.
+async fn start_node(id: &'static str) -> (SocketAddr, ServerHandle) {
+	let mut module = RpcModule::new(());
+	module
+		.register_method("sync_state_genSyncSpec", move |params, _| {
+			assert!(params.one::<bool>().unwrap(), "light clients need the raw genesis");
+			spec(id, Some(7))
+		})
+		.unwrap();
+	common::start_server(module).await
+}
+
+fn spec_file(name: &str) -> PathBuf {
+	let path = std::env::temp_dir().join(format!("rpc-provider-{}-{name}", std::process::id()));
+	fs::write(&path, spec("westend2", None).to_string()).unwrap();
+	path
+}
+
+fn read(path: &PathBuf) -> Value {
+	serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn checkpoint_is_refreshed_from_the_node() {
+	let (addr, _handle) = start_node("westend2").await;
+	let path = spec_file("refreshed.json");
+
+	refresh_chain_spec(&common::http_provider(addr).await, &path).await.unwrap();
+	assert_eq!(read(&path), spec("westend2", Some(7)));
+	fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn spec_of_another_chain_is_not_written() {
+	let (addr, _handle) = start_node("polkadot").await;
+	let path = spec_file("other_chain.json");
+
+	assert!(refresh_chain_spec(&common::http_provider(addr).await, &path).await.is_err());
+	assert_eq!(read(&path), spec("westend2", None));
+	fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,385 @@
+//! Local JSON-RPC node used by the integration tests.
+#![allow(dead_code)]
+
+use jsonrpsee::{
+	server::{RpcModule, Server, ServerHandle, SubscriptionMessage},
+	types::ErrorObjectOwned,
+};
+use rpc_provider::{http::HttpProvider, types::ProviderInterface, ws::WsProvider};
+use serde_json::{json, value::RawValue};
+use std::{
+	net::SocketAddr,
+	sync::{
+		atomic::{AtomicBool, AtomicUsize, Ordering},
+		Arc,
+	},
+};
+use tokio::{
+	io::{AsyncReadExt, AsyncWriteExt},
+	net::{
+		tcp::{OwnedReadHalf, OwnedWriteHalf},
+		TcpListener, TcpStream,
+	},
+	sync::Notify,
+};
+
+/// What the mock node observed from its clients.
+#[derive(Default)]
+pub struct MockState {
+	pub active_subscriptions: AtomicUsize,
+	/// Subscriptions to `mock_subscribePending`, which are never answered.
+	pub pending_subscriptions: AtomicUsize,
+	/// Notified whenever a subscription starts or ends.
+	changed: Notify,
+}
+
+impl MockState {
+	/// Waits until exactly `count` subscriptions are active, panics after five seconds.
+	pub async fn wait_for_active_subscriptions(&self, count: usize) {
+		self.wait_for(|state| state.active_subscriptions.load(Ordering::SeqCst) == count)
+			.await
+	}
+
+	/// Waits until `count` subscriptions to `mock_subscribePending` were received.
+	pub async fn wait_for_pending_subscriptions(&self, count: usize) {
+		self.wait_for(|state| state.pending_subscriptions.load(Ordering::SeqCst) == count)
+			.await
+	}
+
+	async fn wait_for(&self, condition: impl Fn(&Self) -> bool) {
+		let wait = async {
+			loop {
+				let changed = self.changed.notified();
+				tokio::pin!(changed);
+				// Registered before checking, so no change is missed.
+				changed.as_mut().enable();
+				if condition(self) {
+					return;
+				}
+				changed.await;
+			}
+		};
+		tokio::time::timeout(std::time::Duration::from_secs(5), wait)
+			.await
+			.expect("the mock node never reached the expected state");
+	}
+}
+
+/// Counts a subscription as active for as long as it is alive.
+struct ActiveSubscription(Arc<MockState>);
+
+impl ActiveSubscription {
+	fn new(state: Arc<MockState>) -> Self {
+		state.active_subscriptions.fetch_add(1, Ordering::SeqCst);
+		state.changed.notify_waiters();
+		Self(state)
+	}
+}
+
+impl Drop for ActiveSubscription {
+	fn drop(&mut self) {
+		self.0.active_subscriptions.fetch_sub(1, Ordering::SeqCst);
+		self.0.changed.notify_waiters();
+	}
+}
+
+/// `result` of `mock_unsorted`, exactly as the server writes it.
+pub const MOCK_UNSORTED: &str =
+	r#"{"specVersion":1,"specName":"mock","balance":123456789012345678901234567890,"rate":1.50}"#;
+
+/// Registers a fixed set of methods covering every JSON shape a `result` can take.
+pub fn mock_module(state: Arc<MockState>) -> RpcModule<Arc<MockState>> {
+	let mut module = RpcModule::new(state);
+	module
+		.register_method("mock_object", |_, _| json!({ "specName": "mock", "specVersion": 1 }))
+		.unwrap();
+	module.register_method("mock_array", |_, _| json!([1, "two", null])).unwrap();
+	// Unsorted keys and numbers a `serde_json::Value` can't hold as written.
+	module
+		.register_method("mock_unsorted", |_, _| {
+			Ok::<_, ErrorObjectOwned>(RawValue::from_string(MOCK_UNSORTED.into()).unwrap())
+		})
+		.unwrap();
+	module.register_method("mock_number", |_, _| 42).unwrap();
+	module.register_method("mock_string", |_, _| "0x1234").unwrap();
+	module.register_method("mock_null", |_, _| serde_json::Value::Null).unwrap();
+	module
+		.register_method("mock_echo", |params, _| params.parse::<serde_json::Value>())
+		.unwrap();
+	module
+		.register_method("mock_error", |_, _| {
+			Err::<(), _>(ErrorObjectOwned::owned(-32000, "mock failure", None::<()>))
+		})
+		.unwrap();
+	module
+		.register_async_method("mock_slow", |_, _| async {
+			tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+			42
+		})
+		.unwrap();
+	// Node-admin method, see `tests/unsafe_methods.rs`.
+	module.register_method("author_rotateKeys", |_, _| "0xabcd").unwrap();
+	module
+		.register_subscription(
+			"mock_subscribeCounter",
+			"mock_counter",
+			"mock_unsubscribeCounter",
+			|_, pending, state| async move {
+				let sink = pending.accept().await?;
+				let _active = ActiveSubscription::new((*state).clone());
+				for i in 0u64.. {
+					sink.send(SubscriptionMessage::from_json(&i)?).await?;
+					tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+				}
+				Ok(())
+			},
+		)
+		.unwrap();
+	module
+		.register_subscription(
+			"mock_subscribeBurst",
+			"mock_burst",
+			"mock_unsubscribeBurst",
+			|params, pending, state| async move {
+				let count = params.one::<u64>()?;
+				let sink = pending.accept().await?;
+				let _active = ActiveSubscription::new((*state).clone());
+				for i in 0..count {
+					sink.send(SubscriptionMessage::from_json(&i)?).await?;
+				}
+				sink.closed().await;
+				Ok(())
+			},
+		)
+		.unwrap();
+	module
+		.register_subscription(
+			"mock_subscribePending",
+			"mock_pending",
+			"mock_unsubscribePending",
+			|_, pending, state| async move {
+				state.pending_subscriptions.fetch_add(1, Ordering::SeqCst);
+				state.changed.notify_waiters();
+				// Neither accepted nor rejected.
+				let _pending = pending;
+				std::future::pending::<()>().await;
+				Ok(())
+			},
+		)
+		.unwrap();
+	module
+}
+
+/// Starts a mock node answering both HTTP and WebSocket requests.
+pub async fn start_mock_server() -> (SocketAddr, ServerHandle) {
+	let (addr, handle, _) = start_mock_server_with_state().await;
+	(addr, handle)
+}
+
+/// Starts a mock node and returns what it observes from its clients.
+pub async fn start_mock_server_with_state() -> (SocketAddr, ServerHandle, Arc<MockState>) {
+	let state = Arc::new(MockState::default());
+	let (addr, handle) = start_server(mock_module(state.clone())).await;
+	(addr, handle, state)
+}
+
+/// Starts a node answering both HTTP and WebSocket requests with the methods of `module`.
+pub async fn start_server<Context: Send + Sync + 'static>(
+	module: RpcModule<Context>,
+) -> (SocketAddr, ServerHandle) {
+	let server = Server::builder().build("127.0.0.1:0").await.unwrap();
+	let addr = server.local_addr().unwrap();
+	(addr, server.start(module))
+}
+
+/// Connected HTTP provider of the node at `addr`.
+pub async fn http_provider(addr: SocketAddr) -> HttpProvider {
+	let mut provider = HttpProvider::new(&format!("http://{addr}")).unwrap();
+	provider.connect().await.unwrap();
+	provider
+}
+
+/// Connected WebSocket provider of the node at `addr`.
+pub async fn ws_provider(addr: SocketAddr) -> WsProvider {
+	let mut provider = WsProvider::new(&format!("ws://{addr}")).unwrap();
+	provider.connect().await.unwrap();
+	provider
+}
+
+/// Starts a TCP proxy to `target` which stops forwarding any byte, without closing the
+/// connections, once the returned flag is set. Simulates a half-open connection.
+pub async fn start_stalling_proxy(target: SocketAddr) -> (SocketAddr, Arc<AtomicBool>) {
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let addr = listener.local_addr().unwrap();
+	let stalled = Arc::new(AtomicBool::new(false));
+	let flag = stalled.clone();
+	tokio::spawn(async move {
+		while let Ok((client, _)) = listener.accept().await {
+			let server = TcpStream::connect(target).await.unwrap();
+			let (client_read, client_write) = client.into_split();
+			let (server_read, server_write) = server.into_split();
+			tokio::spawn(forward_until_stalled(client_read, server_write, flag.clone()));
+			tokio::spawn(forward_until_stalled(server_read, client_write, flag.clone()));
+		}
+	});
+	(addr, stalled)
+}
+
+async fn forward_until_stalled(
+	mut from: OwnedReadHalf,
+	mut to: OwnedWriteHalf,
+	stalled: Arc<AtomicBool>,
+) {
+	let mut buf = [0u8; 4096];
+	loop {
+		let Ok(read) = from.read(&mut buf).await else { return };
+		if read == 0 {
+			return;
+		}
+		if stalled.load(Ordering::SeqCst) {
+			// Keep both halves open, the peers never learn about the stall.
+			std::future::pending::<()>().await;
+		}
+		if to.write_all(&buf[..read]).await.is_err() {
+			return;
+		}
+	}
+}
+
+/// Targets requested from a mock proxy: `host:port` for a SOCKS5 proxy, the method and target of
+/// the request line for an HTTP proxy.
+pub type Tunnels = Arc<std::sync::Mutex<Vec<String>>>;
+
+/// Starts an HTTP proxy answering `CONNECT` requests, and forwarding the requests sent in
+/// absolute form. With `credentials`, clients must authenticate with basic authentication.
+pub async fn start_http_proxy(credentials: Option<(&str, &str)>) -> (SocketAddr, Tunnels) {
+	use base64::{engine::general_purpose::STANDARD, Engine};
+
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let addr = listener.local_addr().unwrap();
+	let authorization = credentials.map(|(username, password)| {
+		format!("proxy-authorization: basic {}", STANDARD.encode(format!("{username}:{password}")))
+			.to_lowercase()
+	});
+	let tunnels = Tunnels::default();
+	let requested = tunnels.clone();
+	tokio::spawn(async move {
+		while let Ok((mut client, _)) = listener.accept().await {
+			let authorization = authorization.clone();
+			let requested = requested.clone();
+			tokio::spawn(async move {
+				let mut head = Vec::new();
+				while !head.ends_with(b"\r\n\r\n") {
+					let Ok(byte) = client.read_u8().await else { return };
+					head.push(byte);
+				}
+				let head = String::from_utf8(head).unwrap();
+				let mut request_line = head.split(' ');
+				let (method, target) = (request_line.next().unwrap(), request_line.next().unwrap());
+				let authorized = authorization.map_or(true, |expected| {
+					head.to_lowercase().lines().any(|line| line == expected)
+				});
+				if !authorized {
+					let response = "HTTP/1.1 407 Proxy Authentication Required\r\n\r\n";
+					let _ = client.write_all(response.as_bytes()).await;
+					return;
+				}
+				requested.lock().unwrap().push(format!("{method} {target}"));
+				let mut server = match target.strip_prefix("http://") {
+					// Forwarded as is, `jsonrpsee` accepts the absolute form.
+					Some(url) => {
+						let mut server =
+							TcpStream::connect(url.trim_end_matches('/')).await.unwrap();
+						server.write_all(head.as_bytes()).await.unwrap();
+						server
+					},
+					None => {
+						let server = TcpStream::connect(target).await.unwrap();
+						client
+							.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+							.await
+							.unwrap();
+						server
+					},
+				};
+				let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+			});
+		}
+	});
+	(addr, tunnels)
+}
+
+/// Starts a SOCKS5 proxy. With `credentials`, clients must authenticate with a username and
+/// password.
+pub async fn start_socks5_proxy(credentials: Option<(&str, &str)>) -> (SocketAddr, Tunnels) {
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let addr = listener.local_addr().unwrap();
+	let credentials =
+		credentials.map(|(username, password)| (username.to_string(), password.to_string()));
+	let tunnels = Tunnels::default();
+	let requested = tunnels.clone();
+	tokio::spawn(async move {
+		while let Ok((mut client, _)) = listener.accept().await {
+			let credentials = credentials.clone();
+			let requested = requested.clone();
+			tokio::spawn(async move {
+				let Some(target) = socks5_handshake(&mut client, credentials).await else { return };
+				requested.lock().unwrap().push(target.clone());
+				let mut server = TcpStream::connect(target).await.unwrap();
+				client.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).await.unwrap();
+				let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+			});
+		}
+	});
+	(addr, tunnels)
+}
+
+/// Runs the server side of a SOCKS5 handshake, returns the requested target.
+async fn socks5_handshake(
+	client: &mut TcpStream,
+	credentials: Option<(String, String)>,
+) -> Option<String> {
+	let mut header = [0u8; 2];
+	client.read_exact(&mut header).await.ok()?;
+	let mut methods = vec![0u8; header[1] as usize];
+	client.read_exact(&mut methods).await.ok()?;
+	let method = if credentials.is_some() { 2 } else { 0 };
+	if !methods.contains(&method) {
+		client.write_all(&[5, 0xff]).await.ok()?;
+		return None;
+	}
+	client.write_all(&[5, method]).await.ok()?;
+
+	if let Some((username, password)) = credentials {
+		let mut version = [0u8; 1];
+		client.read_exact(&mut version).await.ok()?;
+		let received_username = read_socks5_string(client).await?;
+		let received_password = read_socks5_string(client).await?;
+		let valid = received_username == username && received_password == password;
+		client.write_all(&[1, if valid { 0 } else { 1 }]).await.ok()?;
+		if !valid {
+			return None;
+		}
+	}
+
+	let mut request = [0u8; 4];
+	client.read_exact(&mut request).await.ok()?;
+	let host = match request[3] {
+		1 => {
+			let mut ip = [0u8; 4];
+			client.read_exact(&mut ip).await.ok()?;
+			std::net::Ipv4Addr::from(ip).to_string()
+		},
+		3 => read_socks5_string(client).await?,
+		_ => return None,
+	};
+	let port = client.read_u16().await.ok()?;
+	Some(format!("{host}:{port}"))
+}
+
+async fn read_socks5_string(client: &mut TcpStream) -> Option<String> {
+	let len = client.read_u8().await.ok()?;
+	let mut bytes = vec![0u8; len as usize];
+	client.read_exact(&mut bytes).await.ok()?;
+	String::from_utf8(bytes).ok()
+}
@@ -0,0 +1,213 @@
+//! Negotiation and decoding of compressed responses against local servers.
+use futures::io::{BufReader, BufWriter};
+use hyper::{
+	header::{ACCEPT_ENCODING, CONTENT_ENCODING},
+	service::{make_service_fn, service_fn},
+	Body, Response, Server,
+};
+use rpc_provider::{
+	compression::ContentEncoding,
+	http::{HttpConfig, HttpProvider},
+	limits::{Limit, Limits},
+	no_params,
+	types::ProviderInterface,
+	ws::{WsConfig, WsProvider},
+	Error, Request,
+};
+use serde_json::{json, Value};
+use soketto::{
+	extension::deflate::Deflate,
+	handshake::{server::Response as Handshake, Server as WsServer},
+	Mode,
+};
+use std::{
+	convert::Infallible,
+	io::Write,
+	net::SocketAddr,
+	sync::{Arc, Mutex},
+};
+use tokio::net::TcpListener;
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+/// Highly compressible result, such as metadata.
+fn large_result() -> String {
+	format!("0x{}", "00".repeat(64 * 1024))
+}
+
+fn response_to(request: &[u8]) -> String {
+	let request: Value = serde_json::from_slice(request).unwrap();
+	json!({ "jsonrpc": "2.0", "id": request["id"], "result": large_result() }).to_string()
+}
+
+fn encode(encoding: &str, data: &[u8]) -> Vec<u8> {
+	match encoding {
+		"gzip" => {
+			let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+			encoder.write_all(data).unwrap();
+			encoder.finish().unwrap()
+		},
+		"deflate" => {
+			let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), Default::default());
+			encoder.write_all(data).unwrap();
+			encoder.finish().unwrap()
+		},
+		"br" => {
+			let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+			encoder.write_all(data).unwrap();
+			encoder.into_inner()
+		},
+		_ => unreachable!(),
+	}
+}
+
+/// Starts an HTTP node answering with `encoding` when the client accepts it. Returns the
+/// `Accept-Encoding` headers it received.
+async fn start_http_server(encoding: &'static str) -> (SocketAddr, Arc<Mutex<Vec<String>>>) {
+	let accepted = Arc::new(Mutex::new(Vec::new()));
+	let received = accepted.clone();
+	let make_service = make_service_fn(move |_| {
+		let received = received.clone();
+		async move {
+			Ok::<_, Infallible>(service_fn(move |request: hyper::Request<Body>| {
+				let received = received.clone();
+				async move {
+					let accept = request
+						.headers()
+						.get(ACCEPT_ENCODING)
+						.map(|value| value.to_str().unwrap().to_string())
+						.unwrap_or_default();
+					received.lock().unwrap().push(accept.clone());
+					let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+					let response = response_to(&body);
+					let response = if accept.contains(encoding) {
+						Response::builder()
+							.header(CONTENT_ENCODING, encoding)
+							.body(Body::from(encode(encoding, response.as_bytes())))
+					} else {
+						Response::builder().body(Body::from(response))
+					};
+					Ok::<_, Infallible>(response.unwrap())
+				}
+			}))
+		}
+	});
+	let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+	let addr = server.local_addr();
+	tokio::spawn(server);
+	(addr, accepted)
+}
+
+/// Starts a WebSocket node, supporting `permessage-deflate` if `deflate` is set.
+async fn start_ws_server(deflate: bool) -> SocketAddr {
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let addr = listener.local_addr().unwrap();
+	tokio::spawn(async move {
+		while let Ok((socket, _)) = listener.accept().await {
+			tokio::spawn(async move {
+				let socket = BufReader::new(BufWriter::new(socket.compat()));
+				let mut server = WsServer::new(socket);
+				if deflate {
+					server.add_extension(Box::new(Deflate::new(Mode::Server)));
+				}
+				let key = server.receive_request().await.unwrap().key();
+				server.send_response(&Handshake::Accept { key, protocol: None }).await.unwrap();
+				let (mut sender, mut receiver) = server.into_builder().finish();
+				let mut request = Vec::new();
+				while receiver.receive_data(&mut request).await.is_ok() {
+					sender.send_text_owned(response_to(&request)).await.unwrap();
+					sender.flush().await.unwrap();
+					request.clear();
+				}
+			});
+		}
+	});
+	addr
+}
+
+#[tokio::test]
+async fn http_responses_are_decoded() {
+	for (encoding, name) in [
+		(ContentEncoding::Gzip, "gzip"),
+		(ContentEncoding::Deflate, "deflate"),
+		(ContentEncoding::Brotli, "br"),
+	] {
+		let (addr, accepted) = start_http_server(name).await;
+		let config = HttpConfig { compression: vec![encoding], ..Default::default() };
+		let mut provider =
+			HttpProvider::new_with_config(&format!("http://{addr}"), config).unwrap();
+		provider.connect().await.unwrap();
+
+		let result: String = provider.request("state_getMetadata", no_params!()).await.unwrap();
+		assert_eq!(result, large_result());
+		assert!(accepted.lock().unwrap()[0].starts_with(name));
+		let stats = provider.compression_stats();
+		assert!(stats.decoded_bytes() > result.len() as u64);
+		assert!(stats.ratio().unwrap() > 10.0, "{name}: {:?}", stats.ratio());
+	}
+}
+
+#[tokio::test]
+async fn http_compression_is_opt_in() {
+	let (addr, accepted) = start_http_server("gzip").await;
+	let mut provider = HttpProvider::new(&format!("http://{addr}")).unwrap();
+	provider.connect().await.unwrap();
+
+	let result: String = provider.request("state_getMetadata", no_params!()).await.unwrap();
+	assert_eq!(result, large_result());
+	assert_eq!(*accepted.lock().unwrap(), vec![String::new()]);
+	assert_eq!(provider.compression_stats().ratio(), None);
+}
+
+#[tokio::test]
+async fn http_decoded_responses_are_limited_to_the_message_size() {
+	let (addr, _) = start_http_server("gzip").await;
+	let config = HttpConfig {
+		compression: vec![ContentEncoding::Gzip],
+		// The compressed response fits, not the decoded one.
+		limits: Limits { max_message_size: 16 * 1024, ..Default::default() },
+		..Default::default()
+	};
+	let mut provider = HttpProvider::new_with_config(&format!("http://{addr}"), config).unwrap();
+	provider.connect().await.unwrap();
+
+	match provider.request::<String>("state_getMetadata", no_params!()).await {
+		Err(Error::LimitExceeded(limit)) => assert_eq!(limit, Limit::MessageSize),
+		other => panic!("Expected the message size to be exceeded, got {other:?}"),
+	}
+}
+
+#[tokio::test]
+async fn ws_permessage_deflate_is_negotiated() {
+	let addr = start_ws_server(true).await;
+	let config = WsConfig { compression: true, ..Default::default() };
+	let mut provider = WsProvider::new_with_config(&format!("ws://{addr}"), config).unwrap();
+	provider.connect().await.unwrap();
+	assert!(provider.compression_negotiated());
+
+	let result: String = provider.request("state_getMetadata", no_params!()).await.unwrap();
+	assert_eq!(result, large_result());
+	let stats = provider.compression_stats();
+	assert!(stats.ratio().unwrap() > 10.0, "{:?}", stats.ratio());
+}
+
+#[tokio::test]
+async fn ws_falls_back_to_uncompressed_messages() {
+	let addr = start_ws_server(false).await;
+	let config = WsConfig { compression: true, ..Default::default() };
+	let mut provider = WsProvider::new_with_config(&format!("ws://{addr}"), config).unwrap();
+	provider.connect().await.unwrap();
+	assert!(!provider.compression_negotiated());
+
+	let result: String = provider.request("state_getMetadata", no_params!()).await.unwrap();
+	assert_eq!(result, large_result());
+	let ratio = provider.compression_stats().ratio().unwrap();
+	assert!(ratio < 1.0, "{ratio}");
+
+	// Not offered unless enabled.
+	let addr = start_ws_server(true).await;
+	let mut provider = WsProvider::new(&format!("ws://{addr}")).unwrap();
+	provider.connect().await.unwrap();
+	assert!(!provider.compression_negotiated());
+	let result: String = provider.request("state_getMetadata", no_params!()).await.unwrap();
+	assert_eq!(result, large_result());
+}
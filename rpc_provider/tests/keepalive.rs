@@ -0,0 +1,147 @@
+//! Dead connections are detected by the keepalive and the subscription watchdog.
+mod common;
+
+use futures::FutureExt;
+use rpc_provider::{
+	no_params, rpc_params,
+	types::ProviderInterface,
+	ws::{KeepaliveConfig, SubscriptionConfig, WsConfig, WsProvider},
+	Error, HandleSubscription, Request, Subscribe,
+};
+use std::{net::SocketAddr, sync::atomic::Ordering, time::Duration};
+
+async fn connect(addr: SocketAddr, config: WsConfig) -> WsProvider {
+	let mut provider = WsProvider::new_with_config(&format!("ws://{addr}"), config).unwrap();
+	provider.connect().await.unwrap();
+	provider
+}
+
+#[tokio::test]
+async fn keepalive_detects_a_stalled_connection() {
+	let (server, _handle) = common::start_mock_server().await;
+	let (proxy, stalled) = common::start_stalling_proxy(server).await;
+	let keepalive = KeepaliveConfig {
+		ping_interval: Duration::from_millis(50),
+		inactive_limit: Duration::from_millis(100),
+		max_failures: 1,
+	};
+	let provider =
+		connect(proxy, WsConfig { keepalive: Some(keepalive), ..Default::default() }).await;
+
+	let mut subscription = provider
+		.subscribe::<u64>("mock_subscribeCounter", no_params!(), "mock_unsubscribeCounter")
+		.await
+		.unwrap();
+	subscription.next().await.unwrap().unwrap();
+	assert!(provider.is_connected());
+
+	stalled.store(true, Ordering::SeqCst);
+	tokio::time::timeout(Duration::from_secs(2), provider.on_disconnect())
+		.await
+		.unwrap();
+	assert!(!provider.is_connected());
+	assert!(provider.disconnect_reason().await.to_string().contains("inactive"));
+
+	// Buffered notifications come first, then the loss of the connection.
+	loop {
+		match subscription.next().await {
+			Some(Ok(_)) => continue,
+			Some(Err(Error::ConnectionClosed)) => break,
+			other => panic!("Expected the connection loss, got {other:?}"),
+		}
+	}
+	assert!(subscription.next().await.is_none());
+}
+
+#[tokio::test]
+async fn inactive_subscription_marks_the_connection_stale() {
+	let (addr, _handle) = common::start_mock_server().await;
+	let subscription = SubscriptionConfig {
+		inactivity_timeout: Some(Duration::from_millis(100)),
+		..Default::default()
+	};
+	let provider = connect(addr, WsConfig { subscription, ..Default::default() }).await;
+
+	// A single notification is sent, then nothing.
+	let mut silent = provider
+		.subscribe::<u64>("mock_subscribeBurst", rpc_params!(1), "mock_unsubscribeBurst")
+		.await
+		.unwrap();
+	let mut other = provider
+		.subscribe::<u64>("mock_subscribeCounter", no_params!(), "mock_unsubscribeCounter")
+		.await
+		.unwrap();
+	assert_eq!(silent.next().await.unwrap().unwrap(), 0);
+
+	tokio::time::timeout(Duration::from_secs(2), provider.on_disconnect())
+		.await
+		.unwrap();
+	assert!(!provider.is_connected());
+	let reason = provider.disconnect_reason().await.to_string();
+	assert!(reason.contains("mock_subscribeBurst"), "{reason}");
+
+	assert!(matches!(silent.next().await, Some(Err(Error::ConnectionClosed))));
+	while let Some(notification) = other.next().await {
+		if let Err(e) = notification {
+			assert!(matches!(e, Error::ConnectionClosed));
+		}
+	}
+	assert!(matches!(
+		provider
+			.subscribe::<u64>("mock_subscribeCounter", no_params!(), "mock_unsubscribeCounter")
+			.await,
+		Err(Error::ConnectionClosed)
+	));
+}
+
+#[tokio::test]
+async fn requests_fail_once_the_connection_is_stale() {
+	let (server, _handle) = common::start_mock_server().await;
+	let (proxy, stalled) = common::start_stalling_proxy(server).await;
+	let subscription = SubscriptionConfig {
+		inactivity_timeout: Some(Duration::from_millis(100)),
+		..Default::default()
+	};
+	let provider = connect(proxy, WsConfig { subscription, ..Default::default() }).await;
+	let mut watched = provider
+		.subscribe::<u64>("mock_subscribeCounter", no_params!(), "mock_unsubscribeCounter")
+		.await
+		.unwrap();
+	watched.next().await.unwrap().unwrap();
+
+	// Never answered, fails once the subscription watchdog finds the connection stale.
+	stalled.store(true, Ordering::SeqCst);
+	let in_flight = provider.request::<Vec<u64>>("mock_echo", rpc_params!(1));
+	let in_flight = tokio::time::timeout(Duration::from_secs(2), in_flight).await.unwrap();
+	assert!(matches!(in_flight, Err(Error::ConnectionClosed)), "{in_flight:?}");
+
+	assert!(matches!(
+		provider.request::<Vec<u64>>("mock_echo", rpc_params!(1)).now_or_never(),
+		Some(Err(Error::ConnectionClosed))
+	));
+	assert!(matches!(
+		provider.request_raw("mock_echo", rpc_params!(1)).now_or_never(),
+		Some(Err(Error::ConnectionClosed))
+	));
+}
+
+#[tokio::test]
+async fn reconnecting_clears_a_stale_connection() {
+	let (addr, _handle) = common::start_mock_server().await;
+	let subscription = SubscriptionConfig {
+		inactivity_timeout: Some(Duration::from_millis(100)),
+		..Default::default()
+	};
+	let mut provider = connect(addr, WsConfig { subscription, ..Default::default() }).await;
+	let _silent = provider
+		.subscribe::<u64>("mock_subscribeBurst", rpc_params!(0), "mock_unsubscribeBurst")
+		.await
+		.unwrap();
+	tokio::time::timeout(Duration::from_secs(2), provider.on_disconnect())
+		.await
+		.unwrap();
+
+	provider.connect().await.unwrap();
+
+	assert!(provider.is_connected());
+}
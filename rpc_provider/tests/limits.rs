@@ -0,0 +1,196 @@
+//! Limits on what a hostile node may send.
+mod common;
+
+use futures::io::{BufReader, BufWriter};
+use hyper::{
+	service::{make_service_fn, service_fn},
+	Body, Response, Server,
+};
+use rpc_provider::{
+	http::{HttpConfig, HttpProvider},
+	limits::{Limit, Limits},
+	no_params, rpc_params,
+	types::ProviderInterface,
+	ws::{WsConfig, WsProvider},
+	Error, HandleSubscription, Request, Subscribe,
+};
+use serde_json::Value;
+use soketto::{
+	extension::deflate::Deflate,
+	handshake::{server::Response as Handshake, Server as WsServer},
+	Mode,
+};
+use std::{convert::Infallible, net::SocketAddr};
+use tokio::net::TcpListener;
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+/// What the hostile node answers with.
+#[derive(Clone, Copy)]
+enum Answer {
+	/// A string result of this many bytes.
+	Large(usize),
+	/// A result nested this deep.
+	Nested(usize),
+}
+
+fn response_to(request: &[u8], answer: Answer) -> String {
+	let request: Value = serde_json::from_slice(request).unwrap();
+	let result = match answer {
+		Answer::Large(size) => format!("\"{}\"", "0".repeat(size)),
+		Answer::Nested(depth) => format!("{}{}", "[".repeat(depth), "]".repeat(depth)),
+	};
+	format!(r#"{{"jsonrpc":"2.0","id":{},"result":{result}}}"#, request["id"])
+}
+
+async fn start_http_server(answer: Answer) -> SocketAddr {
+	let make_service = make_service_fn(move |_| async move {
+		Ok::<_, Infallible>(service_fn(move |request: hyper::Request<Body>| async move {
+			let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+			Ok::<_, Infallible>(Response::new(Body::from(response_to(&body, answer))))
+		}))
+	});
+	let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+	let addr = server.local_addr();
+	tokio::spawn(server);
+	addr
+}
+
+/// Starts a WebSocket node, supporting `permessage-deflate`.
+async fn start_ws_server(answer: Answer) -> SocketAddr {
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let addr = listener.local_addr().unwrap();
+	tokio::spawn(async move {
+		while let Ok((socket, _)) = listener.accept().await {
+			tokio::spawn(async move {
+				let socket = BufReader::new(BufWriter::new(socket.compat()));
+				let mut server = WsServer::new(socket);
+				server.add_extension(Box::new(Deflate::new(Mode::Server)));
+				let key = server.receive_request().await.unwrap().key();
+				server.send_response(&Handshake::Accept { key, protocol: None }).await.unwrap();
+				let (mut sender, mut receiver) = server.into_builder().finish();
+				let mut request = Vec::new();
+				while receiver.receive_data(&mut request).await.is_ok() {
+					let _ = sender.send_text_owned(response_to(&request, answer)).await;
+					let _ = sender.flush().await;
+					request.clear();
+				}
+			});
+		}
+	});
+	addr
+}
+
+async fn http_request(answer: Answer, limits: Limits) -> rpc_provider::Result<Value> {
+	let addr = start_http_server(answer).await;
+	let config = HttpConfig { limits, ..Default::default() };
+	let mut provider = HttpProvider::new_with_config(&format!("http://{addr}"), config)?;
+	provider.connect().await?;
+	provider.request("state_getMetadata", no_params!()).await
+}
+
+async fn ws_request(
+	answer: Answer,
+	limits: Limits,
+	compression: bool,
+) -> rpc_provider::Result<Value> {
+	let addr = start_ws_server(answer).await;
+	let config = WsConfig { limits, compression, ..Default::default() };
+	let mut provider = WsProvider::new_with_config(&format!("ws://{addr}"), config)?;
+	provider.connect().await?;
+	provider.request("state_getMetadata", no_params!()).await
+}
+
+fn assert_exceeded(result: rpc_provider::Result<Value>, expected: Limit) {
+	match result {
+		Err(Error::LimitExceeded(limit)) => assert_eq!(limit, expected),
+		other => panic!("Expected {expected:?} to be exceeded, got {other:?}"),
+	}
+}
+
+#[tokio::test]
+async fn large_messages_are_refused() {
+	let limits = Limits { max_message_size: 4096, ..Default::default() };
+	assert!(http_request(Answer::Large(1024), limits).await.is_ok());
+	assert_exceeded(http_request(Answer::Large(8192), limits).await, Limit::MessageSize);
+	for compression in [false, true] {
+		assert!(ws_request(Answer::Large(1024), limits, compression).await.is_ok());
+		assert_exceeded(
+			ws_request(Answer::Large(8192), limits, compression).await,
+			Limit::MessageSize,
+		);
+	}
+}
+
+#[tokio::test]
+async fn deeply_nested_messages_are_refused() {
+	let limits = Limits { max_nesting_depth: 16, ..Default::default() };
+	// The envelope of the response adds a level.
+	assert!(http_request(Answer::Nested(15), limits).await.is_ok());
+	assert!(ws_request(Answer::Nested(15), limits, false).await.is_ok());
+	// Deeper than `serde_json` accepts on its own as well.
+	for depth in [17, 100, 10_000] {
+		assert_exceeded(http_request(Answer::Nested(depth), limits).await, Limit::NestingDepth);
+		for compression in [false, true] {
+			assert_exceeded(
+				ws_request(Answer::Nested(depth), limits, compression).await,
+				Limit::NestingDepth,
+			);
+		}
+	}
+}
+
+#[tokio::test]
+async fn notification_floods_end_the_subscription() {
+	let (addr, _handle) = common::start_mock_server().await;
+	let limits = Limits { max_notifications_per_second: Some(10), ..Default::default() };
+	let config = WsConfig { limits, ..Default::default() };
+	let mut provider = WsProvider::new_with_config(&format!("ws://{addr}"), config).unwrap();
+	provider.connect().await.unwrap();
+
+	let mut subscription = provider
+		.subscribe::<u64>("mock_subscribeBurst", rpc_params!(100), "mock_unsubscribeBurst")
+		.await
+		.unwrap();
+	let mut received = 0;
+	let error = loop {
+		match subscription.next().await {
+			Some(Ok(_)) => received += 1,
+			Some(Err(error)) => break error,
+			None => panic!("The subscription ended without error"),
+		}
+	};
+	assert!(matches!(error, Error::LimitExceeded(Limit::NotificationRate)), "{error:?}");
+	assert_eq!(received, 10);
+	assert!(subscription.next().await.is_none());
+}
+
+#[tokio::test]
+async fn pending_responses_are_bounded() {
+	let (addr, _handle) = common::start_mock_server().await;
+	let limits = Limits { max_pending_responses: 1, ..Default::default() };
+
+	let config = WsConfig { limits, ..Default::default() };
+	let mut ws = WsProvider::new_with_config(&format!("ws://{addr}"), config).unwrap();
+	ws.connect().await.unwrap();
+	let config = HttpConfig { limits, ..Default::default() };
+	let mut http = HttpProvider::new_with_config(&format!("http://{addr}"), config).unwrap();
+	http.connect().await.unwrap();
+
+	let (first, second) = tokio::join!(
+		ws.request::<u64>("mock_slow", no_params!()),
+		ws.request::<u64>("mock_slow", no_params!())
+	);
+	assert_eq!(first.unwrap(), 42);
+	assert!(matches!(second, Err(Error::LimitExceeded(Limit::PendingResponses))), "{second:?}");
+
+	let (first, second) = tokio::join!(
+		http.request::<u64>("mock_slow", no_params!()),
+		http.request::<u64>("mock_slow", no_params!())
+	);
+	assert_eq!(first.unwrap(), 42);
+	assert!(matches!(second, Err(Error::LimitExceeded(Limit::PendingResponses))), "{second:?}");
+
+	// Released once answered.
+	assert_eq!(ws.request::<u64>("mock_slow", no_params!()).await.unwrap(), 42);
+	assert_eq!(http.request::<u64>("mock_slow", no_params!()).await.unwrap(), 42);
+}
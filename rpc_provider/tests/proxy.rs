@@ -0,0 +1,94 @@
+//! Connections through HTTP and SOCKS5 proxies.
+mod common;
+
+use rpc_provider::{
+	http::{HttpConfig, HttpProvider},
+	no_params,
+	proxy::{Proxy, ProxyConfig},
+	types::ProviderInterface,
+	ws::{WsConfig, WsProvider},
+	Error, Request,
+};
+
+async fn ws_request(url: &str, proxy: &str) -> rpc_provider::Result<u64> {
+	let config = WsConfig { proxy: Proxy::Custom(ProxyConfig::new(proxy)?), ..Default::default() };
+	let mut provider = WsProvider::new_with_config(url, config)?;
+	provider.connect().await?;
+	provider.request("mock_number", no_params!()).await
+}
+
+// `HttpConfig` only has other fields with a TLS feature.
+#[allow(clippy::needless_update)]
+async fn http_request(url: &str, proxy: &str) -> rpc_provider::Result<u64> {
+	let config =
+		HttpConfig { proxy: Proxy::Custom(ProxyConfig::new(proxy)?), ..Default::default() };
+	let mut provider = HttpProvider::new_with_config(url, config)?;
+	provider.connect().await?;
+	provider.request("mock_number", no_params!()).await
+}
+
+#[tokio::test]
+async fn requests_go_through_http_proxy() {
+	let (node, _handle) = common::start_mock_server().await;
+	let (proxy, tunnels) = common::start_http_proxy(None).await;
+	let proxy = format!("http://{proxy}");
+
+	// WebSocket connections are tunneled, plain `http` requests are forwarded by the proxy.
+	assert_eq!(ws_request(&format!("ws://{node}"), &proxy).await.unwrap(), 42);
+	assert_eq!(http_request(&format!("http://{node}"), &proxy).await.unwrap(), 42);
+	assert_eq!(
+		*tunnels.lock().unwrap(),
+		vec![format!("CONNECT {node}"), format!("POST http://{node}/")]
+	);
+}
+
+#[tokio::test]
+async fn http_proxy_credentials_are_sent() {
+	let (node, _handle) = common::start_mock_server().await;
+	let (proxy, tunnels) = common::start_http_proxy(Some(("user", "p@ss"))).await;
+
+	let anonymous = format!("http://{proxy}");
+	let error = ws_request(&format!("ws://{node}"), &anonymous).await.unwrap_err();
+	assert!(matches!(error, Error::Proxy(_)), "{error:?}");
+	assert!(http_request(&format!("http://{node}"), &anonymous).await.is_err());
+	assert!(tunnels.lock().unwrap().is_empty());
+
+	let authenticated = format!("http://user:p%40ss@{proxy}");
+	assert_eq!(ws_request(&format!("ws://{node}"), &authenticated).await.unwrap(), 42);
+	assert_eq!(http_request(&format!("http://{node}"), &authenticated).await.unwrap(), 42);
+}
+
+#[tokio::test]
+async fn requests_are_tunneled_through_socks5_proxy() {
+	let (node, _handle) = common::start_mock_server().await;
+	let (proxy, tunnels) = common::start_socks5_proxy(Some(("user", "secret"))).await;
+
+	// `socks5h` leaves the name resolution to the proxy.
+	let url = format!("ws://localhost:{}", node.port());
+	let remote_dns = format!("socks5h://user:secret@{proxy}");
+	assert_eq!(ws_request(&url, &remote_dns).await.unwrap(), 42);
+	let local_dns = format!("socks5://user:secret@{proxy}");
+	assert_eq!(http_request(&format!("http://{node}"), &local_dns).await.unwrap(), 42);
+	assert_eq!(
+		*tunnels.lock().unwrap(),
+		vec![format!("localhost:{}", node.port()), node.to_string()]
+	);
+
+	let error = ws_request(&url, &format!("socks5h://user:wrong@{proxy}")).await.unwrap_err();
+	assert!(matches!(error, Error::Proxy(_)), "{error:?}");
+}
+
+#[tokio::test]
+async fn explicit_credentials_override_the_url() {
+	let (node, _handle) = common::start_mock_server().await;
+	let (proxy, _tunnels) = common::start_socks5_proxy(Some(("user", "secret"))).await;
+	let proxy = ProxyConfig::new(&format!("socks5://other@{proxy}"))
+		.unwrap()
+		.with_credentials("user", "secret");
+
+	let config = WsConfig { proxy: Proxy::Custom(proxy), ..Default::default() };
+	let mut provider = WsProvider::new_with_config(&format!("ws://{node}"), config).unwrap();
+	provider.connect().await.unwrap();
+	let number: u64 = provider.request("mock_number", no_params!()).await.unwrap();
+	assert_eq!(number, 42);
+}
@@ -0,0 +1,70 @@
+//! Every provider must honour the same `Request::request_raw` contract.
+mod common;
+
+use rpc_provider::{
+	coalesce::{CoalescedMethods, Coalescing},
+	http::HttpProvider,
+	no_params, rpc_params,
+	types::ProviderInterface,
+	ws::WsProvider,
+	Error, Request,
+};
+
+async fn assert_request_raw_contract<P: Request>(provider: &P) {
+	let object = provider.request_raw("mock_object", no_params!()).await.unwrap();
+	assert_eq!(object.get(), r#"{"specName":"mock","specVersion":1}"#);
+
+	let unsorted = provider.request_raw("mock_unsorted", no_params!()).await.unwrap();
+	assert_eq!(unsorted.get(), common::MOCK_UNSORTED);
+
+	let array = provider.request_raw("mock_array", no_params!()).await.unwrap();
+	assert_eq!(array.get(), r#"[1,"two",null]"#);
+
+	let number = provider.request_raw("mock_number", no_params!()).await.unwrap();
+	assert_eq!(number.get(), "42");
+
+	let string = provider.request_raw("mock_string", no_params!()).await.unwrap();
+	assert_eq!(string.get(), r#""0x1234""#);
+
+	let null = provider.request_raw("mock_null", no_params!()).await.unwrap();
+	assert_eq!(null.get(), "null");
+
+	let echo = provider.request_raw("mock_echo", rpc_params!(1, "a")).await.unwrap();
+	assert_eq!(echo.get(), r#"[1,"a"]"#);
+
+	match provider.request_raw("mock_error", no_params!()).await {
+		Err(Error::JsonRpcError(error)) => {
+			assert_eq!(error.code(), -32000);
+			assert_eq!(error.message(), "mock failure");
+		},
+		other => panic!("Expected a JSON-RPC error, got {other:?}"),
+	}
+}
+
+#[tokio::test]
+async fn ws_provider_request_raw_returns_result_json() {
+	let (addr, _handle) = common::start_mock_server().await;
+	let mut provider = WsProvider::new(&format!("ws://{addr}")).unwrap();
+	provider.connect().await.unwrap();
+
+	assert_request_raw_contract(&provider).await;
+}
+
+#[tokio::test]
+async fn http_provider_request_raw_returns_result_json() {
+	let (addr, _handle) = common::start_mock_server().await;
+	let mut provider = HttpProvider::new(&format!("http://{addr}")).unwrap();
+	provider.connect().await.unwrap();
+
+	assert_request_raw_contract(&provider).await;
+}
+
+#[tokio::test]
+async fn coalescing_provider_request_raw_returns_result_json() {
+	let (addr, _handle) = common::start_mock_server().await;
+	let provider = WsProvider::new(&format!("ws://{addr}")).unwrap();
+	let mut provider = Coalescing::with_methods(provider, CoalescedMethods::All);
+	provider.connect().await.unwrap();
+
+	assert_request_raw_contract(&provider).await;
+}
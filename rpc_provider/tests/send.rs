@@ -0,0 +1,81 @@
+//! Provider futures must be `Send` so they can run on a multi-threaded runtime.
+#![cfg(feature = "send")]
+mod common;
+
+use rpc_provider::{
+	http::HttpProvider, light_client::ScProvider, no_params, rpc_params, types::ProviderInterface,
+	ws::WsProvider, HandleSubscription, Request, Subscribe,
+};
+use smoldot_light::platform::DefaultPlatform;
+use std::sync::Arc;
+
+fn assert_send<T: Send>(_: &T) {}
+
+/// Only type-checked, requests to a light client are tested in `light_client::background`.
+#[allow(dead_code)]
+fn sc_provider_futures_are_send(provider: &ScProvider<Arc<DefaultPlatform>, ()>) {
+	assert_send(&provider.request::<String>("system_name", no_params!()));
+	assert_send(&provider.request_raw("system_name", no_params!()));
+}
+
+async fn spawn_requests<P>(provider: P)
+where
+	P: Request + Clone + Send + Sync + 'static,
+{
+	let handles = (0..8)
+		.map(|i| {
+			let provider = provider.clone();
+			tokio::spawn(
+				async move { provider.request::<Vec<u32>>("mock_echo", rpc_params!(i)).await },
+			)
+		})
+		.collect::<Vec<_>>();
+
+	for (i, handle) in handles.into_iter().enumerate() {
+		assert_eq!(handle.await.unwrap().unwrap(), vec![i as u32]);
+	}
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn ws_provider_works_with_tokio_spawn() {
+	let (addr, _handle) = common::start_mock_server().await;
+	let mut provider = WsProvider::new(&format!("ws://{addr}")).unwrap();
+	let provider = tokio::spawn(async move {
+		provider.connect().await.unwrap();
+		provider
+	})
+	.await
+	.unwrap();
+
+	spawn_requests(provider).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn http_provider_works_with_tokio_spawn() {
+	let (addr, _handle) = common::start_mock_server().await;
+	let mut provider = HttpProvider::new(&format!("http://{addr}")).unwrap();
+	provider.connect().await.unwrap();
+
+	spawn_requests(provider).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn ws_subscription_works_with_tokio_spawn() {
+	let (addr, _handle) = common::start_mock_server().await;
+	let mut provider = WsProvider::new(&format!("ws://{addr}")).unwrap();
+	provider.connect().await.unwrap();
+
+	let received = tokio::spawn(async move {
+		let mut subscription = provider
+			.subscribe::<u64>("mock_subscribeCounter", no_params!(), "mock_unsubscribeCounter")
+			.await
+			.unwrap();
+		let first = subscription.next().await.unwrap().unwrap();
+		subscription.unsubscribe().await.unwrap();
+		first
+	})
+	.await
+	.unwrap();
+
+	assert_eq!(received, 0);
+}
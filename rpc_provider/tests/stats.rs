@@ -0,0 +1,68 @@
+//! Stats recorded by the providers.
+mod common;
+
+use rpc_provider::{
+	compression::ContentEncoding,
+	http::{HttpConfig, HttpProvider},
+	no_params, rpc_params,
+	stats::ErrorClass,
+	types::ProviderInterface,
+	ws::{WsConfig, WsProvider},
+	HandleSubscription, Request, Subscribe,
+};
+
+#[tokio::test]
+async fn ws_provider_records_stats() {
+	let (addr, _handle) = common::start_mock_server().await;
+	for compression in [false, true] {
+		let config = WsConfig { compression, ..Default::default() };
+		let mut provider = WsProvider::new_with_config(&format!("ws://{addr}"), config).unwrap();
+		provider.connect().await.unwrap();
+		provider.connect().await.unwrap();
+		let stats = provider.clone().stats();
+
+		let _: u64 = provider.request("mock_number", no_params!()).await.unwrap();
+		assert!(provider.request::<u64>("mock_error", no_params!()).await.is_err());
+		assert!(provider.request::<u64>("author_rotateKeys", no_params!()).await.is_err());
+		let mut subscription = provider
+			.subscribe::<u64>("mock_subscribeCounter", no_params!(), "mock_unsubscribeCounter")
+			.await
+			.unwrap();
+		subscription.next().await.unwrap().unwrap();
+		assert_eq!(stats.active_subscriptions(), 1);
+		subscription.unsubscribe().await.unwrap();
+		assert_eq!(stats.active_subscriptions(), 0);
+
+		let number = stats.method("mock_number").unwrap();
+		assert_eq!((number.requests, number.errors, number.latency.count()), (1, 0, 1));
+		assert_eq!(stats.method("mock_error").unwrap().errors, 1);
+		assert_eq!(stats.method("mock_subscribeCounter").unwrap().requests, 1);
+		assert_eq!(stats.errors(ErrorClass::Rpc), 1);
+		assert_eq!(stats.errors(ErrorClass::Refused), 1);
+		assert_eq!(stats.reconnects(), 1);
+		// Requests, the subscription and its notification, the unsubscription.
+		assert!(stats.sent_bytes() > 100, "{}", stats.sent_bytes());
+		assert!(stats.received_bytes() > 100, "{}", stats.received_bytes());
+	}
+}
+
+#[tokio::test]
+async fn http_provider_records_stats() {
+	let (addr, _handle) = common::start_mock_server().await;
+	let request = r#"{"jsonrpc":"2.0","id":0,"method":"mock_echo","params":["abc"]}"#;
+	let response = r#"{"jsonrpc":"2.0","id":0,"result":["abc"]}"#;
+
+	for compression in [vec![], vec![ContentEncoding::Gzip]] {
+		let config = HttpConfig { compression, ..Default::default() };
+		let mut provider =
+			HttpProvider::new_with_config(&format!("http://{addr}"), config).unwrap();
+		provider.connect().await.unwrap();
+		let _: serde_json::Value = provider.request("mock_echo", rpc_params!("abc")).await.unwrap();
+
+		let stats = provider.stats();
+		assert_eq!(stats.method("mock_echo").unwrap().requests, 1);
+		assert_eq!(stats.sent_bytes(), request.len() as u64);
+		assert_eq!(stats.received_bytes(), response.len() as u64);
+		assert_eq!(stats.errors(ErrorClass::Connection), 0);
+	}
+}
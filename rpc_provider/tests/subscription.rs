@@ -0,0 +1,191 @@
+//! Subscriptions are streams that honour the configured buffering.
+mod common;
+
+use futures::{FutureExt, StreamExt};
+use rpc_provider::{
+	no_params, rpc_params,
+	types::ProviderInterface,
+	ws::{LagPolicy, SubscriptionConfig, WsConfig, WsProvider},
+	Error, HandleSubscription, Subscribe,
+};
+use std::{net::SocketAddr, sync::atomic::Ordering};
+
+async fn connect(addr: SocketAddr, buffer_capacity: usize, lag_policy: LagPolicy) -> WsProvider {
+	let config = WsConfig {
+		subscription: SubscriptionConfig { buffer_capacity, lag_policy, ..Default::default() },
+		..Default::default()
+	};
+	connect_with_config(addr, config).await
+}
+
+async fn connect_with_config(addr: SocketAddr, config: WsConfig) -> WsProvider {
+	let mut provider = WsProvider::new_with_config(&format!("ws://{addr}"), config).unwrap();
+	provider.connect().await.unwrap();
+	provider
+}
+
+/// Collects the notifications, with lag notices as `Err(n)`.
+async fn collect<S>(subscription: S, count: usize) -> Vec<Result<u64, u64>>
+where
+	S: futures::Stream<Item = rpc_provider::Result<u64>>,
+{
+	subscription
+		.take(count)
+		.map(|notification| match notification {
+			Ok(n) => Ok(n),
+			Err(Error::SubscriptionLagged(lagged)) => Err(lagged),
+			Err(e) => panic!("Unexpected error {e:?}"),
+		})
+		.collect()
+		.await
+}
+
+#[tokio::test]
+async fn subscription_is_a_stream() {
+	let (addr, _handle) = common::start_mock_server().await;
+	let provider = connect(addr, 16, LagPolicy::Error).await;
+
+	let subscription = provider
+		.subscribe::<u64>("mock_subscribeCounter", no_params!(), "mock_unsubscribeCounter")
+		.await
+		.unwrap();
+
+	assert_eq!(collect(subscription, 3).await, vec![Ok(0), Ok(1), Ok(2)]);
+}
+
+#[tokio::test]
+async fn dropping_a_subscription_unsubscribes() {
+	let (addr, _handle, state) = common::start_mock_server_with_state().await;
+	let provider = connect(addr, 16, LagPolicy::Error).await;
+
+	let mut subscription = provider
+		.subscribe::<u64>("mock_subscribeCounter", no_params!(), "mock_unsubscribeCounter")
+		.await
+		.unwrap();
+	HandleSubscription::next(&mut subscription).await.unwrap().unwrap();
+	assert_eq!(state.active_subscriptions.load(Ordering::SeqCst), 1);
+
+	drop(subscription);
+	state.wait_for_active_subscriptions(0).await;
+}
+
+#[tokio::test]
+async fn unsubscribe_waits_for_the_node() {
+	let (addr, _handle, state) = common::start_mock_server_with_state().await;
+	let provider = connect(addr, 16, LagPolicy::Error).await;
+
+	let mut subscription = provider
+		.subscribe::<u64>("mock_subscribeBurst", rpc_params!(1), "mock_unsubscribeBurst")
+		.await
+		.unwrap();
+	HandleSubscription::next(&mut subscription).await.unwrap().unwrap();
+
+	subscription.unsubscribe().await.unwrap();
+	state.wait_for_active_subscriptions(0).await;
+}
+
+#[tokio::test]
+async fn identical_subscriptions_share_one_upstream_subscription() {
+	let (addr, _handle, state) = common::start_mock_server_with_state().await;
+	let provider = connect(addr, 16, LagPolicy::Error).await;
+	let active = || state.active_subscriptions.load(Ordering::SeqCst);
+
+	let mut first = provider
+		.subscribe::<u64>("mock_subscribeCounter", no_params!(), "mock_unsubscribeCounter")
+		.await
+		.unwrap();
+	let mut second = provider
+		.clone()
+		.subscribe::<u64>("mock_subscribeCounter", no_params!(), "mock_unsubscribeCounter")
+		.await
+		.unwrap();
+
+	let first_notification = HandleSubscription::next(&mut first).await.unwrap().unwrap();
+	let second_notification = HandleSubscription::next(&mut second).await.unwrap().unwrap();
+	assert!(second_notification <= first_notification + 1);
+	assert_eq!(active(), 1);
+
+	// The upstream subscription outlives the first handle: the second keeps receiving.
+	drop(first);
+	let next = HandleSubscription::next(&mut second).await.unwrap().unwrap();
+	assert_eq!(collect(&mut second, 3).await, vec![Ok(next + 1), Ok(next + 2), Ok(next + 3)]);
+	assert_eq!(active(), 1);
+
+	drop(second);
+	state.wait_for_active_subscriptions(0).await;
+}
+
+#[tokio::test]
+async fn subscriptions_with_different_params_are_not_shared() {
+	let (addr, _handle, state) = common::start_mock_server_with_state().await;
+	let provider = connect(addr, 16, LagPolicy::Error).await;
+
+	let _one = provider
+		.subscribe::<u64>("mock_subscribeBurst", rpc_params!(1), "mock_unsubscribeBurst")
+		.await
+		.unwrap();
+	let _two = provider
+		.subscribe::<u64>("mock_subscribeBurst", rpc_params!(2), "mock_unsubscribeBurst")
+		.await
+		.unwrap();
+
+	state.wait_for_active_subscriptions(2).await;
+}
+
+#[tokio::test]
+async fn late_handles_only_receive_later_notifications() {
+	let (addr, _handle) = common::start_mock_server().await;
+	let provider = connect(addr, 16, LagPolicy::Error).await;
+
+	let mut first = provider
+		.subscribe::<u64>("mock_subscribeBurst", rpc_params!(3), "mock_unsubscribeBurst")
+		.await
+		.unwrap();
+	assert_eq!(collect(&mut first, 3).await, vec![Ok(0), Ok(1), Ok(2)]);
+
+	let mut late = provider
+		.subscribe::<u64>("mock_subscribeBurst", rpc_params!(3), "mock_unsubscribeBurst")
+		.await
+		.unwrap();
+	assert!(HandleSubscription::next(&mut late).now_or_never().is_none());
+}
+
+#[tokio::test]
+async fn subscribing_only_waits_for_identical_subscriptions() {
+	let (addr, _handle, state) = common::start_mock_server_with_state().await;
+	let provider = connect(addr, 16, LagPolicy::Error).await;
+
+	let pending =
+		provider.subscribe::<u64>("mock_subscribePending", no_params!(), "mock_unsubscribePending");
+	let other = async {
+		state.wait_for_pending_subscriptions(1).await;
+		let mut subscription = provider
+			.subscribe::<u64>("mock_subscribeBurst", rpc_params!(1), "mock_unsubscribeBurst")
+			.await
+			.unwrap();
+		HandleSubscription::next(&mut subscription).await.unwrap().unwrap()
+	};
+
+	tokio::select! {
+		_ = pending => panic!("The node never answers this subscription"),
+		notification = other => assert_eq!(notification, 0),
+	}
+}
+
+#[tokio::test]
+async fn sharing_can_be_disabled() {
+	let (addr, _handle, state) = common::start_mock_server_with_state().await;
+	let config = WsConfig { share_subscriptions: false, ..Default::default() };
+	let provider = connect_with_config(addr, config).await;
+
+	let _first = provider
+		.subscribe::<u64>("mock_subscribeCounter", no_params!(), "mock_unsubscribeCounter")
+		.await
+		.unwrap();
+	let _second = provider
+		.subscribe::<u64>("mock_subscribeCounter", no_params!(), "mock_unsubscribeCounter")
+		.await
+		.unwrap();
+
+	state.wait_for_active_subscriptions(2).await;
+}
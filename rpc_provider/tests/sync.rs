@@ -0,0 +1,141 @@
+//! Waiting for a node to be synced, against a node scripted like a warp-syncing smoldot.
+mod common;
+
+use futures::StreamExt;
+use jsonrpsee::server::{RpcModule, ServerHandle, SubscriptionMessage};
+use rpc_provider::{
+	sync::{sync_status_stream, wait_until_synced, SyncConfig, SyncStatus},
+	Error,
+};
+use serde_json::json;
+use std::{
+	net::SocketAddr,
+	sync::atomic::{AtomicU64, Ordering},
+	time::Duration,
+};
+
+/// Syncs for `syncing_polls` calls of `system_health`, the best block advancing at each of them.
+/// Subscribers to the new heads are sent the headers numbered `heads`.
+async fn start_syncing_node(syncing_polls: u64, heads: &[u64]) -> (SocketAddr, ServerHandle) {
+	let heads = heads.to_vec();
+	let mut module = RpcModule::new(AtomicU64::new(0));
+	module
+		.register_method("system_health", move |_, polls| {
+			let polls = polls.fetch_add(1, Ordering::SeqCst) + 1;
+			json!({ "peers": 4, "isSyncing": polls <= syncing_polls, "shouldHavePeers": true })
+		})
+		.unwrap();
+	module
+		.register_method("chain_getHeader", |params, polls| {
+			// Asked with the hash of the finalized block, without parameters for the best one.
+			let finalized = params.one::<String>().is_ok();
+			let number = polls.load(Ordering::SeqCst) * 10 + if finalized { 0 } else { 2 };
+			json!({ "number": format!("{number:#x}"), "parentHash": "0x00" })
+		})
+		.unwrap();
+	module.register_method("chain_getFinalizedHead", |_, _| "0xf1").unwrap();
+	module
+		.register_subscription(
+			"chain_subscribeNewHeads",
+			"chain_newHead",
+			"chain_unsubscribeNewHeads",
+			move |_, pending, _| {
+				let heads = heads.clone();
+				async move {
+					let sink = pending.accept().await?;
+					for number in heads {
+						let header =
+							json!({ "number": format!("{number:#x}"), "parentHash": "0x00" });
+						sink.send(SubscriptionMessage::from_json(&header)?).await?;
+					}
+					sink.closed().await;
+					Ok(())
+				}
+			},
+		)
+		.unwrap();
+
+	common::start_server(module).await
+}
+
+fn config(timeout: Duration) -> SyncConfig {
+	SyncConfig { poll_interval: Duration::from_millis(10), timeout }
+}
+
+#[tokio::test]
+async fn wait_until_synced_returns_once_syncing_is_over() {
+	let (addr, _handle) = start_syncing_node(3, &[]).await;
+	let provider = common::http_provider(addr).await;
+
+	let status = wait_until_synced(&provider, config(Duration::from_secs(5))).await.unwrap();
+	assert_eq!(
+		status,
+		SyncStatus {
+			peers: 4,
+			is_syncing: false,
+			should_have_peers: true,
+			best_block: 42,
+			finalized_block: 40
+		}
+	);
+}
+
+#[tokio::test]
+async fn wait_until_synced_times_out_with_the_last_status() {
+	let (addr, _handle) = start_syncing_node(u64::MAX, &[]).await;
+	let provider = common::http_provider(addr).await;
+
+	match wait_until_synced(&provider, config(Duration::from_millis(100))).await {
+		Err(Error::SyncTimeout(status)) => {
+			assert!(status.is_syncing);
+			assert!(status.best_block > 0);
+		},
+		other => panic!("Expected a timeout, got {other:?}"),
+	}
+}
+
+#[tokio::test]
+async fn sync_status_stream_follows_the_progress() {
+	let (addr, _handle) = start_syncing_node(2, &[]).await;
+	let provider = common::ws_provider(addr).await;
+
+	let statuses = sync_status_stream(provider, Duration::from_millis(10))
+		.take(3)
+		.map(|status| status.unwrap())
+		.collect::<Vec<_>>()
+		.await;
+	assert_eq!(
+		statuses
+			.iter()
+			.map(|status| (status.is_syncing, status.best_block))
+			.collect::<Vec<_>>(),
+		[(true, 12), (true, 22), (false, 32)]
+	);
+	assert!(statuses[2].is_synced());
+}
+
+#[tokio::test]
+async fn sync_status_stream_follows_the_new_heads() {
+	let (addr, _handle) = start_syncing_node(u64::MAX, &[13, 13, 14]).await;
+	let provider = common::ws_provider(addr).await;
+
+	// Only the first tick polls the node, the next statuses come from the new heads.
+	let statuses = sync_status_stream(provider, Duration::from_secs(3600))
+		.take(3)
+		.map(|status| status.unwrap())
+		.collect::<Vec<_>>()
+		.await;
+	assert_eq!(statuses.iter().map(|status| status.best_block).collect::<Vec<_>>(), [12, 13, 14]);
+	assert!(statuses.iter().all(|status| status.finalized_block == 10 && status.is_syncing));
+}
+
+#[test]
+fn sync_status_stream_is_created_outside_of_a_runtime() {
+	let runtime = tokio::runtime::Runtime::new().unwrap();
+	let (addr, _handle) = runtime.block_on(start_syncing_node(u64::MAX, &[]));
+	let provider = runtime.block_on(common::ws_provider(addr));
+
+	let mut statuses = sync_status_stream(provider, Duration::from_millis(10));
+	let status = runtime.block_on(statuses.next()).unwrap().unwrap();
+	assert_eq!(status.best_block, 12);
+}
@@ -0,0 +1,209 @@
+//! `wss` and `https` connections with custom TLS configurations.
+#![cfg(feature = "__tls")]
+mod common;
+
+use rcgen::{
+	BasicConstraints, Certificate, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa,
+};
+use rpc_provider::{
+	http::{HttpConfig, HttpProvider},
+	no_params,
+	proxy::{Proxy, ProxyConfig},
+	tls::{ClientCertificate, TlsConfig},
+	types::ProviderInterface,
+	ws::{WsConfig, WsProvider},
+	Request,
+};
+use std::{io::BufReader, net::SocketAddr, sync::Arc};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::{
+	rustls::{server::WebPkiClientVerifier, RootCertStore, ServerConfig},
+	TlsAcceptor,
+};
+
+/// Certificates of a test PKI, PEM encoded.
+struct Pki {
+	ca: String,
+	server_certificate: String,
+	server_key: String,
+	client: ClientCertificate,
+}
+
+fn certificate(
+	names: Vec<String>,
+	usage: ExtendedKeyUsagePurpose,
+	ca: &Certificate,
+) -> (String, String) {
+	let mut params = CertificateParams::new(names);
+	params.extended_key_usages = vec![usage];
+	let certificate = Certificate::from_params(params).unwrap();
+	(certificate.serialize_pem_with_signer(ca).unwrap(), certificate.serialize_private_key_pem())
+}
+
+fn generate_pki() -> Pki {
+	let mut params = CertificateParams::new(vec![]);
+	params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+	params.distinguished_name.push(DnType::CommonName, "rpc_provider test CA");
+	let ca = Certificate::from_params(params).unwrap();
+
+	let (server_certificate, server_key) =
+		certificate(vec!["localhost".into()], ExtendedKeyUsagePurpose::ServerAuth, &ca);
+	let (certificate_chain_pem, private_key_pem) =
+		certificate(vec!["client".into()], ExtendedKeyUsagePurpose::ClientAuth, &ca);
+	Pki {
+		ca: ca.serialize_pem().unwrap(),
+		server_certificate,
+		server_key,
+		client: ClientCertificate {
+			certificate_chain_pem: certificate_chain_pem.into_bytes(),
+			private_key_pem: private_key_pem.into_bytes(),
+		},
+	}
+}
+
+/// Starts a TLS server forwarding to the mock node. With `require_client_certificate`, clients
+/// must present a certificate signed by the test CA.
+async fn start_tls_proxy(pki: &Pki, require_client_certificate: bool) -> SocketAddr {
+	let (target, handle) = common::start_mock_server().await;
+	let chain = rustls_pemfile::certs(&mut BufReader::new(pki.server_certificate.as_bytes()))
+		.collect::<Result<Vec<_>, _>>()
+		.unwrap();
+	let key = rustls_pemfile::private_key(&mut BufReader::new(pki.server_key.as_bytes()))
+		.unwrap()
+		.unwrap();
+	let builder = ServerConfig::builder();
+	let config = if require_client_certificate {
+		let mut roots = RootCertStore::empty();
+		for ca in rustls_pemfile::certs(&mut BufReader::new(pki.ca.as_bytes())) {
+			roots.add(ca.unwrap()).unwrap();
+		}
+		let verifier = WebPkiClientVerifier::builder(Arc::new(roots)).build().unwrap();
+		builder.with_client_cert_verifier(verifier).with_single_cert(chain, key)
+	} else {
+		builder.with_no_client_auth().with_single_cert(chain, key)
+	}
+	.unwrap();
+
+	let acceptor = TlsAcceptor::from(Arc::new(config));
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let addr = listener.local_addr().unwrap();
+	tokio::spawn(async move {
+		let _handle = handle;
+		while let Ok((client, _)) = listener.accept().await {
+			let acceptor = acceptor.clone();
+			tokio::spawn(async move {
+				let Ok(mut client) = acceptor.accept(client).await else { return };
+				let mut server = TcpStream::connect(target).await.unwrap();
+				let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+			});
+		}
+	});
+	addr
+}
+
+async fn ws_request(addr: SocketAddr, tls: TlsConfig) -> rpc_provider::Result<u64> {
+	ws_request_through(addr, tls, Proxy::None).await
+}
+
+async fn ws_request_through(
+	addr: SocketAddr,
+	tls: TlsConfig,
+	proxy: Proxy,
+) -> rpc_provider::Result<u64> {
+	let config = WsConfig { tls: Some(tls), proxy, ..Default::default() };
+	let mut provider =
+		WsProvider::new_with_config(&format!("wss://localhost:{}", addr.port()), config)?;
+	provider.connect().await?;
+	provider.request("mock_number", no_params!()).await
+}
+
+async fn http_request(addr: SocketAddr, tls: TlsConfig) -> rpc_provider::Result<u64> {
+	http_request_through(addr, tls, Proxy::None).await
+}
+
+async fn http_request_through(
+	addr: SocketAddr,
+	tls: TlsConfig,
+	proxy: Proxy,
+) -> rpc_provider::Result<u64> {
+	let config = HttpConfig { tls: Some(tls), proxy, ..Default::default() };
+	let mut provider =
+		HttpProvider::new_with_config(&format!("https://localhost:{}", addr.port()), config)?;
+	provider.connect().await?;
+	provider.request("mock_number", no_params!()).await
+}
+
+#[tokio::test]
+async fn custom_root_certificates_are_trusted() {
+	let pki = generate_pki();
+	let addr = start_tls_proxy(&pki, false).await;
+	let tls = TlsConfig::with_root_certificates(pki.ca.clone());
+
+	assert_eq!(ws_request(addr, tls.clone()).await.unwrap(), 42);
+	assert_eq!(http_request(addr, tls).await.unwrap(), 42);
+}
+
+#[tokio::test]
+async fn unknown_certificates_are_rejected() {
+	let pki = generate_pki();
+	let addr = start_tls_proxy(&pki, false).await;
+	let other_ca = generate_pki().ca;
+	let tls = TlsConfig::with_root_certificates(other_ca);
+
+	assert!(ws_request(addr, tls.clone()).await.is_err());
+	assert!(http_request(addr, tls).await.is_err());
+}
+
+#[tokio::test]
+async fn insecure_mode_accepts_any_certificate() {
+	let pki = generate_pki();
+	let addr = start_tls_proxy(&pki, false).await;
+
+	assert_eq!(ws_request(addr, TlsConfig::insecure()).await.unwrap(), 42);
+	assert_eq!(http_request(addr, TlsConfig::insecure()).await.unwrap(), 42);
+}
+
+#[tokio::test]
+async fn client_certificates_are_presented() {
+	let pki = generate_pki();
+	let addr = start_tls_proxy(&pki, true).await;
+	let without_certificate = TlsConfig::with_root_certificates(pki.ca.clone());
+	let with_certificate =
+		TlsConfig { client_certificate: Some(pki.client.clone()), ..without_certificate.clone() };
+
+	assert!(ws_request(addr, without_certificate.clone()).await.is_err());
+	assert!(http_request(addr, without_certificate).await.is_err());
+	assert_eq!(ws_request(addr, with_certificate.clone()).await.unwrap(), 42);
+	assert_eq!(http_request(addr, with_certificate).await.unwrap(), 42);
+}
+
+#[tokio::test]
+async fn tls_is_tunneled_through_proxy() {
+	let pki = generate_pki();
+	let addr = start_tls_proxy(&pki, false).await;
+	let (proxy, tunnels) = common::start_http_proxy(None).await;
+	let proxy = Proxy::Custom(ProxyConfig::new(&format!("http://{proxy}")).unwrap());
+	let tls = TlsConfig::with_root_certificates(pki.ca.clone());
+
+	assert_eq!(ws_request_through(addr, tls.clone(), proxy.clone()).await.unwrap(), 42);
+	assert_eq!(http_request_through(addr, tls, proxy).await.unwrap(), 42);
+	let tunnel = format!("CONNECT localhost:{}", addr.port());
+	assert_eq!(*tunnels.lock().unwrap(), vec![tunnel.clone(), tunnel]);
+}
+
+#[test]
+fn invalid_pem_is_reported() {
+	let tls = TlsConfig {
+		client_certificate: Some(ClientCertificate {
+			certificate_chain_pem: b"not a certificate".to_vec(),
+			private_key_pem: Vec::new(),
+		}),
+		..TlsConfig::insecure()
+	};
+
+	let error = tokio::runtime::Runtime::new()
+		.unwrap()
+		.block_on(ws_request("127.0.0.1:1".parse().unwrap(), tls))
+		.unwrap_err();
+	assert!(matches!(error, rpc_provider::Error::Tls(_)), "{error:?}");
+}
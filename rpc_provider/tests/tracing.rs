@@ -0,0 +1,181 @@
+//! Spans and logs of the providers.
+mod common;
+
+use rpc_provider::{
+	http::{HttpConfig, HttpProvider},
+	no_params, rpc_params,
+	trace::TraceConfig,
+	types::ProviderInterface,
+	ws::{WsConfig, WsProvider},
+	Request, Subscribe,
+};
+use std::{
+	collections::HashMap,
+	fmt::Debug,
+	sync::{Arc, Mutex},
+};
+use tracing::{
+	field::{Field, Visit},
+	span::{Attributes, Id, Record},
+	Event, Subscriber,
+};
+use tracing_subscriber::{layer::Context, prelude::*, registry::LookupSpan, Layer};
+
+type Fields = HashMap<String, String>;
+
+/// Spans and events of the providers, spans named `rpc_*` only.
+#[derive(Clone, Default)]
+struct Captured {
+	spans: Arc<Mutex<Vec<(Id, String, Fields)>>>,
+	/// Events, with the name of their span.
+	events: Arc<Mutex<Vec<(String, Fields)>>>,
+}
+
+impl Captured {
+	fn spans(&self, name: &str) -> Vec<Fields> {
+		let spans = self.spans.lock().unwrap();
+		spans.iter().filter(|(_, n, _)| n == name).map(|(_, _, f)| f.clone()).collect()
+	}
+
+	fn events(&self, span: &str) -> Vec<Fields> {
+		let events = self.events.lock().unwrap();
+		events.iter().filter(|(s, _)| s == span).map(|(_, f)| f.clone()).collect()
+	}
+}
+
+struct FieldVisitor<'a>(&'a mut Fields);
+
+impl Visit for FieldVisitor<'_> {
+	fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+		self.0.insert(field.name().to_string(), format!("{value:?}"));
+	}
+
+	fn record_str(&mut self, field: &Field, value: &str) {
+		self.0.insert(field.name().to_string(), value.to_string());
+	}
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Captured {
+	fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _: Context<'_, S>) {
+		let name = attrs.metadata().name();
+		if name.starts_with("rpc_") {
+			let mut fields = Fields::new();
+			attrs.record(&mut FieldVisitor(&mut fields));
+			self.spans.lock().unwrap().push((id.clone(), name.to_string(), fields));
+		}
+	}
+
+	fn on_record(&self, id: &Id, values: &Record<'_>, _: Context<'_, S>) {
+		let mut spans = self.spans.lock().unwrap();
+		if let Some((_, _, fields)) = spans.iter_mut().find(|(span, _, _)| span == id) {
+			values.record(&mut FieldVisitor(fields));
+		}
+	}
+
+	fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+		let Some(span) = ctx.event_span(event) else { return };
+		if span.name().starts_with("rpc_") {
+			let mut fields = Fields::new();
+			event.record(&mut FieldVisitor(&mut fields));
+			self.events.lock().unwrap().push((span.name().to_string(), fields));
+		}
+	}
+}
+
+fn capture() -> (Captured, tracing::subscriber::DefaultGuard) {
+	let captured = Captured::default();
+	let guard = tracing_subscriber::registry().with(captured.clone()).set_default();
+	(captured, guard)
+}
+
+#[tokio::test]
+async fn requests_are_traced() {
+	let (addr, _handle) = common::start_mock_server().await;
+	let (captured, _guard) = capture();
+
+	let mut ws = WsProvider::new(&format!("ws://{addr}")).unwrap();
+	ws.connect().await.unwrap();
+	let _: serde_json::Value = ws.request("mock_echo", rpc_params!(1, "a")).await.unwrap();
+	let mut http = HttpProvider::new(&format!("http://{addr}")).unwrap();
+	http.connect().await.unwrap();
+	let _: serde_json::Value = http.request("mock_echo", rpc_params!(1, "a")).await.unwrap();
+
+	let connects = captured.spans("rpc_connect");
+	assert_eq!(connects.len(), 2);
+	let requests = captured.spans("rpc_request");
+	assert_eq!(requests.len(), 2);
+	for (span, transport) in connects.iter().chain(&requests).zip(["ws", "http", "ws", "http"]) {
+		assert_eq!(span["transport"], transport);
+		assert_eq!(span["endpoint"], format!("{transport}://{addr}/"));
+		assert_eq!(span["outcome"], "ok");
+		assert!(span.contains_key("latency_ms"));
+	}
+	for span in &requests {
+		assert_eq!(span["method"], "mock_echo");
+		assert_eq!(span["id"], "0");
+	}
+
+	let events = captured.events("rpc_request");
+	let params: Vec<_> = events.iter().filter_map(|event| event.get("params")).collect();
+	assert_eq!(params, vec![r#"[1,"a"]"#, r#"[1,"a"]"#]);
+	let responses: Vec<_> = events.iter().filter_map(|event| event.get("response")).collect();
+	assert_eq!(responses.len(), 2);
+	assert!(responses.iter().all(|response| response.contains("\"a\"")), "{responses:?}");
+}
+
+#[tokio::test]
+async fn failures_and_reconnects_are_traced() {
+	let (addr, _handle) = common::start_mock_server().await;
+	let (captured, _guard) = capture();
+
+	let mut ws = WsProvider::new(&format!("ws://{addr}")).unwrap();
+	ws.connect().await.unwrap();
+	ws.connect().await.unwrap();
+	assert!(ws.request::<u64>("mock_error", no_params!()).await.is_err());
+	assert!(ws.request::<u64>("mock_number", no_params!()).await.is_ok());
+	let _subscription = ws
+		.subscribe::<u64>("mock_subscribeCounter", no_params!(), "mock_unsubscribeCounter")
+		.await
+		.unwrap();
+
+	let reconnects: Vec<_> =
+		captured.spans("rpc_connect").iter().map(|s| s["reconnect"].clone()).collect();
+	assert_eq!(reconnects, vec!["false", "true"]);
+	let requests = captured.spans("rpc_request");
+	assert_eq!(requests[0]["outcome"], "error");
+	assert_eq!((&*requests[1]["id"], &*requests[1]["outcome"]), ("1", "ok"));
+	assert!(captured.events("rpc_request").iter().any(|event| event.contains_key("error")));
+
+	let subscriptions = captured.spans("rpc_subscribe");
+	assert_eq!(subscriptions[0]["method"], "mock_subscribeCounter");
+	assert_eq!(subscriptions[0]["outcome"], "ok");
+}
+
+#[tokio::test]
+async fn payloads_are_truncated_and_redacted() {
+	let (addr, _handle) = common::start_mock_server().await;
+	let (captured, _guard) = capture();
+
+	let trace = TraceConfig { max_payload_len: Some(3), ..Default::default() };
+	let config = WsConfig { trace, ..Default::default() };
+	let mut ws = WsProvider::new_with_config(&format!("ws://{addr}"), config).unwrap();
+	ws.connect().await.unwrap();
+	let _: serde_json::Value = ws.request("mock_echo", rpc_params!(1, "a")).await.unwrap();
+
+	let trace = TraceConfig { redacted_methods: vec!["mock_echo".into()], ..Default::default() };
+	let config = HttpConfig { trace, ..Default::default() };
+	let mut http = HttpProvider::new_with_config(&format!("http://{addr}"), config).unwrap();
+	http.connect().await.unwrap();
+	let _: serde_json::Value = http.request("mock_echo", rpc_params!(1, "a")).await.unwrap();
+
+	let events = captured.events("rpc_request");
+	let payloads: Vec<_> = events
+		.iter()
+		.filter_map(|event| event.get("params").or_else(|| event.get("response")))
+		.collect();
+	assert_eq!(payloads.len(), 4);
+	assert_eq!(payloads[0], "[1,... (7 bytes)");
+	assert!(payloads[1].ends_with(" bytes)"), "{}", payloads[1]);
+	assert_eq!(payloads[2], "<redacted>");
+	assert_eq!(payloads[3], "<redacted>");
+}
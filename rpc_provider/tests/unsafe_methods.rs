@@ -0,0 +1,53 @@
+//! Node-admin methods are only sent by providers built to allow them.
+mod common;
+
+use rpc_provider::{
+	http::{HttpConfig, HttpProvider},
+	no_params,
+	types::ProviderInterface,
+	ws::{WsConfig, WsProvider},
+	Error, Request,
+};
+use std::net::SocketAddr;
+
+async fn ws_provider(addr: SocketAddr, config: WsConfig) -> WsProvider {
+	let mut provider = WsProvider::new_with_config(&format!("ws://{addr}"), config).unwrap();
+	provider.connect().await.unwrap();
+	provider
+}
+
+async fn http_provider(addr: SocketAddr, config: HttpConfig) -> HttpProvider {
+	let mut provider = HttpProvider::new_with_config(&format!("http://{addr}"), config).unwrap();
+	provider.connect().await.unwrap();
+	provider
+}
+
+async fn assert_refused<P: Request>(provider: &P) {
+	match provider.request::<String>("author_rotateKeys", no_params!()).await {
+		Err(Error::UnsafeMethod(method)) => assert_eq!(method, "author_rotateKeys"),
+		other => panic!("Expected the method to be refused, got {other:?}"),
+	}
+	let error = provider.request_raw("author_rotateKeys", no_params!()).await.unwrap_err();
+	assert!(matches!(error, Error::UnsafeMethod(_)), "{error:?}");
+	assert_eq!(provider.request::<u64>("mock_number", no_params!()).await.unwrap(), 42);
+}
+
+#[tokio::test]
+async fn unsafe_methods_are_refused_by_default() {
+	let (addr, _handle) = common::start_mock_server().await;
+	assert_refused(&ws_provider(addr, WsConfig::default()).await).await;
+	assert_refused(&http_provider(addr, HttpConfig::default()).await).await;
+}
+
+#[tokio::test]
+async fn unsafe_methods_are_sent_when_allowed() {
+	let (addr, _handle) = common::start_mock_server().await;
+	let provider = ws_provider(addr, WsConfig { allow_unsafe: true, ..Default::default() }).await;
+	let keys: String = provider.request("author_rotateKeys", no_params!()).await.unwrap();
+	assert_eq!(keys, "0xabcd");
+
+	let provider =
+		http_provider(addr, HttpConfig { allow_unsafe: true, ..Default::default() }).await;
+	let keys: String = provider.request("author_rotateKeys", no_params!()).await.unwrap();
+	assert_eq!(keys, "0xabcd");
+}
@@ -0,0 +1,298 @@
+//! Genesis block of a raw chain spec, computed without a node.
+//!
+//! The storage is the Patricia-Merkle trie of Substrate: radix 16, the partial key of a node in
+//! its header instead of extension nodes, children referenced by their BLAKE2b-256 hash or inline
+//! when encoded in less than 32 bytes.
+use super::{decode_hex, ChainSpecError, GenesisStorage, RawGenesis};
+use blake2::{
+	digest::{consts::U32, Digest},
+	Blake2b,
+};
+use std::collections::BTreeMap;
+
+/// Key in the top trie of the root of a default child trie, followed by its storage key.
+const CHILD_STORAGE_DEFAULT_PREFIX: &[u8] = b":child_storage:default:";
+
+/// Values this large are hashed in the nodes of a [`StateVersion::V1`] trie.
+const VALUE_NODE_THRESHOLD: usize = 33;
+
+/// Layout of the storage trie, the `state_version` of the runtime at genesis.
+///
+/// Chains launched before Substrate introduced `V1`, such as Polkadot, Kusama and their system
+/// parachains, have a `V0` genesis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateVersion {
+	/// Values are stored in the nodes.
+	V0,
+	/// Values of 33 bytes or more are stored as their hash in the nodes.
+	V1,
+}
+
+impl RawGenesis {
+	/// Root of the genesis storage, with the roots of the child tries in the top trie.
+	pub fn state_root(&self, version: StateVersion) -> Result<[u8; 32], ChainSpecError> {
+		let mut top = decode_storage(&self.top)?;
+		for (storage_key, child) in &self.children_default {
+			let child = decode_storage(child)?;
+			// Empty child tries are not kept in the storage.
+			if child.is_empty() {
+				continue
+			}
+			let key = [CHILD_STORAGE_DEFAULT_PREFIX, &decode(storage_key, storage_key)?].concat();
+			top.insert(key, trie_root(&child, version).to_vec());
+		}
+		Ok(trie_root(&top, version))
+	}
+
+	/// Hash of the genesis block: number 0, zero parent hash, no extrinsic and no digest.
+	pub fn block_hash(&self, version: StateVersion) -> Result<[u8; 32], ChainSpecError> {
+		let mut header = Vec::with_capacity(32 + 1 + 32 + 32 + 1);
+		header.extend([0; 32]);
+		header.push(0);
+		header.extend(self.state_root(version)?);
+		header.extend(trie_root(&BTreeMap::new(), version));
+		header.push(0);
+		Ok(blake2_256(&header))
+	}
+}
+
+/// BLAKE2b with a 256 bits output, the hash of Substrate chains.
+fn blake2_256(data: &[u8]) -> [u8; 32] {
+	Blake2b::<U32>::digest(data).into()
+}
+
+fn decode(hex: &str, key: &str) -> Result<Vec<u8>, ChainSpecError> {
+	decode_hex(hex).ok_or_else(|| ChainSpecError::InvalidHex(key.into()))
+}
+
+fn decode_storage(storage: &GenesisStorage) -> Result<BTreeMap<Vec<u8>, Vec<u8>>, ChainSpecError> {
+	storage
+		.iter()
+		.map(|(key, value)| Ok((decode(key, key)?, decode(value, key)?)))
+		.collect()
+}
+
+/// Hash of the root node of the trie holding `storage`.
+fn trie_root(storage: &BTreeMap<Vec<u8>, Vec<u8>>, version: StateVersion) -> [u8; 32] {
+	// Keys sorted by bytes are sorted by nibbles as well.
+	let entries = storage
+		.iter()
+		.map(|(key, value)| {
+			(key.iter().flat_map(|byte| [byte >> 4, byte & 0xf]).collect(), &value[..])
+		})
+		.collect::<Vec<(Vec<u8>, &[u8])>>();
+	if entries.is_empty() {
+		return blake2_256(&[0])
+	}
+	blake2_256(&encode_node(&entries, 0, version))
+}
+
+/// Encodes the node of the `entries` sharing their first `depth` nibbles.
+fn encode_node(entries: &[(Vec<u8>, &[u8])], depth: usize, version: StateVersion) -> Vec<u8> {
+	let hashed = |value: &[u8]| version == StateVersion::V1 && value.len() >= VALUE_NODE_THRESHOLD;
+
+	let (first, last) = (&entries[0].0, &entries[entries.len() - 1].0);
+	if entries.len() == 1 {
+		let value = entries[0].1;
+		let (prefix, prefix_bits) = if hashed(value) { (0b001 << 5, 3) } else { (0b01 << 6, 2) };
+		let mut node = header(prefix, prefix_bits, first.len() - depth);
+		push_partial_key(&mut node, &first[depth..]);
+		push_value(&mut node, value, hashed(value));
+		return node
+	}
+
+	let common = first[depth..].iter().zip(&last[depth..]).take_while(|(a, b)| a == b).count();
+	let split = depth + common;
+	let (value, mut children) = match entries[0].0.len() == split {
+		true => (Some(entries[0].1), &entries[1..]),
+		false => (None, entries),
+	};
+	let (prefix, prefix_bits) = match value {
+		None => (0b10 << 6, 2),
+		Some(value) if hashed(value) => (0b0001 << 4, 4),
+		Some(_) => (0b11 << 6, 2),
+	};
+	let mut node = header(prefix, prefix_bits, common);
+	push_partial_key(&mut node, &first[depth..split]);
+	let bitmap_index = node.len();
+	node.extend([0, 0]);
+	if let Some(value) = value {
+		push_value(&mut node, value, hashed(value));
+	}
+
+	let mut bitmap = 0u16;
+	for nibble in 0..16 {
+		let end = children.partition_point(|(key, _)| key[split] == nibble);
+		if end == 0 {
+			continue
+		}
+		bitmap |= 1 << nibble;
+		let child = encode_node(&children[..end], split + 1, version);
+		if child.len() < 32 {
+			push_compact(&mut node, child.len() as u64);
+			node.extend(child);
+		} else {
+			push_compact(&mut node, 32);
+			node.extend(blake2_256(&child));
+		}
+		children = &children[end..];
+	}
+	node[bitmap_index..bitmap_index + 2].copy_from_slice(&bitmap.to_le_bytes());
+	node
+}
+
+/// Kind of the node in the first `prefix_bits` bits, then the length of its partial key.
+fn header(prefix: u8, prefix_bits: u32, nibbles: usize) -> Vec<u8> {
+	let max = 255 >> prefix_bits;
+	if nibbles < usize::from(max) {
+		return vec![prefix | nibbles as u8]
+	}
+	let mut header = vec![prefix | max];
+	let mut remaining = nibbles - usize::from(max);
+	while remaining >= 255 {
+		header.push(255);
+		remaining -= 255;
+	}
+	header.push(remaining as u8);
+	header
+}
+
+/// Packs the nibbles by two, the first one alone when odd.
+fn push_partial_key(node: &mut Vec<u8>, nibbles: &[u8]) {
+	let (odd, pairs) = nibbles.split_at(nibbles.len() % 2);
+	node.extend(odd);
+	node.extend(pairs.chunks(2).map(|pair| pair[0] << 4 | pair[1]));
+}
+
+fn push_value(node: &mut Vec<u8>, value: &[u8], hashed: bool) {
+	if hashed {
+		node.extend(blake2_256(value));
+	} else {
+		push_compact(node, value.len() as u64);
+		node.extend(value);
+	}
+}
+
+/// SCALE compact encoding.
+fn push_compact(node: &mut Vec<u8>, n: u64) {
+	match n {
+		0..=0x3f => node.push((n as u8) << 2),
+		0x40..=0x3fff => node.extend(((n as u16) << 2 | 0b01).to_le_bytes()),
+		0x4000..=0x3fff_ffff => node.extend(((n as u32) << 2 | 0b10).to_le_bytes()),
+		_ => {
+			let bytes = n.to_le_bytes();
+			let length = 8 - n.leading_zeros() as usize / 8;
+			node.push(((length - 4) as u8) << 2 | 0b11);
+			node.extend(&bytes[..length]);
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::chain_spec::{ChainSpec, Genesis};
+	use std::path::Path;
+
+	fn hex(bytes: [u8; 32]) -> String {
+		bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+	}
+
+	fn raw_genesis(name: &str) -> RawGenesis {
+		let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../chain_spec/demo").join(name);
+		match ChainSpec::load(&path).unwrap().genesis {
+			Genesis::Raw(raw) => raw,
+			genesis => panic!("{name} has a {genesis:?} genesis"),
+		}
+	}
+
+	#[test]
+	fn genesis_hashes_of_live_chains() {
+		for (name, hash) in [
+			("kusama.json", "b0a8d493285c2df73290dfb7e61f870f17b41801197a149ca93654499ea3dafe"),
+			("westend.json", "e143f23803ac50e8f6f8e62695d1ce9e4e1d68aa36c1cd2cfd15340213f3423e"),
+			("rococo.json", "6408de7737c59c238890533af25896a2c20608d8b380bb01029acb392781063e"),
+			(
+				"polkadot-asset-hub.json",
+				"68d56f15f85d3136970ec16946040bc1752654e906147f7e43e9d539d7c3de2f",
+			),
+			(
+				"kusama-statemine.json",
+				"48239ef607d7928874027a43a67689209727dfb3d3dc5e5b03a39bdc2eda771a",
+			),
+			(
+				"westend-westmint.json",
+				"67f9723393ef76214df0118c34bbbd3dbebc8ed46a10973a8c969d48fe7598c9",
+			),
+		] {
+			let genesis = raw_genesis(name);
+			assert_eq!(hex(genesis.block_hash(StateVersion::V0).unwrap()), hash, "{name}");
+		}
+	}
+
+	#[test]
+	fn v1_only_differs_with_large_values() {
+		let roots = |value_length: usize| {
+			let genesis = RawGenesis {
+				top: GenesisStorage::from([
+					("0x3a636f6465".into(), format!("0x{}", "00".repeat(value_length))),
+					("0x3a68656170706167657321".into(), "0x08".into()),
+				]),
+				..RawGenesis::default()
+			};
+			(
+				genesis.state_root(StateVersion::V0).unwrap(),
+				genesis.state_root(StateVersion::V1).unwrap(),
+			)
+		};
+		let (v0, v1) = roots(VALUE_NODE_THRESHOLD - 1);
+		assert_eq!(v0, v1);
+		let (v0, v1) = roots(VALUE_NODE_THRESHOLD);
+		assert_ne!(v0, v1);
+	}
+
+	#[test]
+	fn child_tries_are_rooted_in_the_top_trie() {
+		let mut genesis = RawGenesis::default();
+		let empty_root = genesis.state_root(StateVersion::V0).unwrap();
+		genesis.children_default.insert("0x01".into(), GenesisStorage::new());
+		assert_eq!(genesis.state_root(StateVersion::V0).unwrap(), empty_root);
+
+		let child = GenesisStorage::from([("0x02".into(), "0x03".into())]);
+		genesis.children_default.insert("0x01".into(), child.clone());
+		let mut top = RawGenesis::default();
+		let child_root = RawGenesis { top: child, ..RawGenesis::default() };
+		let child_root = child_root.state_root(StateVersion::V0).unwrap();
+		let key = [CHILD_STORAGE_DEFAULT_PREFIX, &[1]].concat();
+		top.top.insert(
+			format!("0x{}", key.iter().map(|byte| format!("{byte:02x}")).collect::<String>()),
+			format!("0x{}", hex(child_root)),
+		);
+		assert_eq!(
+			genesis.state_root(StateVersion::V0).unwrap(),
+			top.state_root(StateVersion::V0).unwrap()
+		);
+	}
+
+	#[test]
+	fn invalid_hex_is_refused() {
+		let genesis = RawGenesis {
+			top: GenesisStorage::from([("0x3a636f6465".into(), "0x0g".into())]),
+			..RawGenesis::default()
+		};
+		assert!(matches!(
+			genesis.block_hash(StateVersion::V0),
+			Err(ChainSpecError::InvalidHex(key)) if key == "0x3a636f6465"
+		));
+	}
+
+	#[test]
+	fn empty_trie() {
+		for version in [StateVersion::V0, StateVersion::V1] {
+			assert_eq!(
+				hex(trie_root(&BTreeMap::new(), version)),
+				"03170a2e7597b7b7e3d84c05391d139a62b157e78786d8c082f29dcf4c111314"
+			);
+		}
+	}
+}
@@ -0,0 +1,452 @@
+//! Checks of a [`ChainSpec`] beyond its format.
+//!
+//! A spec which parses may still be refused by nodes and light clients, or connect to no peer.
+use super::{decode_hex, ChainSpec, ChainType, Genesis, RawGenesis, StateVersion};
+use serde_json::Value;
+use std::{
+	collections::HashSet,
+	fmt,
+	net::{Ipv4Addr, Ipv6Addr},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+	/// The spec is likely usable, but not as intended.
+	Warning,
+	/// Nodes or light clients refuse the spec, or part of it.
+	Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lint {
+	/// `para_id` is set but not `relay_chain`.
+	MissingRelayChain,
+	/// `relay_chain` is set but not `para_id`.
+	MissingParaId,
+	InvalidBootNode {
+		address: String,
+		reason: String,
+	},
+	DuplicateBootNode(String),
+	/// Neither `Development`, `Local`, `Live` nor `{ "Custom": .. }`.
+	UnknownChainType(Value),
+	/// The `lightSyncState` checkpoint can't be used with the genesis of the spec.
+	InconsistentLightSyncState(String),
+}
+
+impl Lint {
+	pub fn severity(&self) -> Severity {
+		match self {
+			Self::DuplicateBootNode(_) | Self::UnknownChainType(_) => Severity::Warning,
+			Self::MissingRelayChain
+			| Self::MissingParaId
+			| Self::InvalidBootNode { .. }
+			| Self::InconsistentLightSyncState(_) => Severity::Error,
+		}
+	}
+}
+
+impl fmt::Display for Lint {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::MissingRelayChain => write!(f, "parachain without `relay_chain`"),
+			Self::MissingParaId => write!(f, "parachain without `para_id`"),
+			Self::InvalidBootNode { address, reason } =>
+				write!(f, "invalid boot node `{address}`: {reason}"),
+			Self::DuplicateBootNode(address) => write!(f, "duplicate boot node `{address}`"),
+			Self::UnknownChainType(chain_type) => write!(f, "unknown `chainType` {chain_type}"),
+			Self::InconsistentLightSyncState(reason) =>
+				write!(f, "inconsistent `lightSyncState`: {reason}"),
+		}
+	}
+}
+
+/// Checks `spec`, returning what is wrong with it from the most severe.
+pub fn lint(spec: &ChainSpec) -> Vec<Lint> {
+	let mut lints = Vec::new();
+
+	// Parachain specs written by hand sometimes follow the camel case of the other fields.
+	let relay_chain = spec.relay_chain().is_some() || spec.extra.contains_key("relayChain");
+	let para_id = spec.para_id().is_some() || spec.extra.contains_key("paraId");
+	match (relay_chain, para_id) {
+		(false, true) => lints.push(Lint::MissingRelayChain),
+		(true, false) => lints.push(Lint::MissingParaId),
+		_ => {},
+	}
+
+	let mut boot_nodes = HashSet::new();
+	for address in &spec.boot_nodes {
+		if let Err(reason) = check_multiaddr(address) {
+			lints.push(Lint::InvalidBootNode { address: address.clone(), reason });
+		}
+		if !boot_nodes.insert(address) {
+			lints.push(Lint::DuplicateBootNode(address.clone()));
+		}
+	}
+
+	if let Some(ChainType::Other(chain_type)) = &spec.chain_type {
+		lints.push(Lint::UnknownChainType(chain_type.clone()));
+	}
+
+	if let Some(light_sync_state) = spec.light_sync_state() {
+		let reason = match &spec.genesis {
+			Genesis::Raw(_) if relay_chain || para_id =>
+				Some("parachains sync from their relay chain".to_string()),
+			Genesis::Raw(genesis) =>
+				check_checkpoint(genesis, &light_sync_state.finalized_block_header).err(),
+			_ => Some("the genesis is not raw".to_string()),
+		};
+		lints.extend(reason.map(Lint::InconsistentLightSyncState));
+	}
+
+	lints.sort_by_key(|lint| std::cmp::Reverse(lint.severity()));
+	lints
+}
+
+/// Checks a multiaddress in its text form, which must end with the `/p2p/` identity of the peer.
+fn check_multiaddr(address: &str) -> Result<(), String> {
+	let Some(mut parts) = address.strip_prefix('/').map(|address| address.split('/')) else {
+		return Err("does not start with `/`".into())
+	};
+	let mut peer_id = false;
+	while let Some(protocol) = parts.next() {
+		if peer_id {
+			return Err(format!("`/{protocol}` after the peer id"))
+		}
+		let mut value = || parts.next().ok_or_else(|| format!("`/{protocol}` without value"));
+		match protocol {
+			"ip4" => value()?.parse::<Ipv4Addr>().map(drop).map_err(|e| format!("{e}"))?,
+			"ip6" => value()?.parse::<Ipv6Addr>().map(drop).map_err(|e| format!("{e}"))?,
+			"dns" | "dns4" | "dns6" | "dnsaddr" =>
+				if value()?.is_empty() {
+					return Err(format!("empty `/{protocol}`"))
+				},
+			"tcp" | "udp" => value()?.parse::<u16>().map(drop).map_err(|e| format!("{e}"))?,
+			"p2p" | "ipfs" => {
+				check_peer_id(value()?)?;
+				peer_id = true;
+			},
+			"certhash" => {
+				value()?;
+			},
+			"ws" | "wss" | "tls" | "quic" | "quic-v1" | "webrtc-direct" | "webrtc" => {},
+			_ => return Err(format!("unknown protocol `/{protocol}`")),
+		}
+	}
+	if !peer_id {
+		return Err("no `/p2p/` peer id".into())
+	}
+	Ok(())
+}
+
+/// Peer ids are base58 multihashes, `Qm..` for the former RSA keys and `12D3KooW..` for ed25519.
+fn check_peer_id(peer_id: &str) -> Result<(), String> {
+	const BASE58: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+	if !peer_id.chars().all(|c| BASE58.contains(c)) {
+		return Err(format!("peer id `{peer_id}` is not base58"))
+	}
+	match peer_id.len() {
+		46 if peer_id.starts_with("Qm") => Ok(()),
+		52 if peer_id.starts_with("12D3KooW") => Ok(()),
+		_ => Err(format!("peer id `{peer_id}` is not a multihash")),
+	}
+}
+
+/// Checks the finalized block header of a checkpoint against the genesis: the header of block 0
+/// is the genesis itself, the one of block 1 its child. Later blocks cannot be checked without
+/// the chain.
+fn check_checkpoint(genesis: &RawGenesis, header: &str) -> Result<(), String> {
+	const VERSIONS: [StateVersion; 2] = [StateVersion::V0, StateVersion::V1];
+	let invalid_genesis = |e| format!("invalid genesis: {e:?}");
+	let header = decode_header(header)?;
+	match header.number {
+		0 => {
+			let roots = VERSIONS
+				.map(|version| genesis.state_root(version))
+				.into_iter()
+				.collect::<Result<Vec<_>, _>>()
+				.map_err(invalid_genesis)?;
+			if header.parent_hash != [0; 32] || !roots.contains(&header.state_root) {
+				return Err("the finalized block 0 is not the genesis".into())
+			}
+		},
+		1 => {
+			let hashes = VERSIONS
+				.map(|version| genesis.block_hash(version))
+				.into_iter()
+				.collect::<Result<Vec<_>, _>>()
+				.map_err(invalid_genesis)?;
+			if !hashes.contains(&header.parent_hash) {
+				return Err("the finalized block 1 is not a child of the genesis".into())
+			}
+		},
+		_ => {},
+	}
+	Ok(())
+}
+
+/// The fields of a block header checked against the genesis.
+struct Header {
+	parent_hash: [u8; 32],
+	number: u64,
+	state_root: [u8; 32],
+}
+
+/// Decodes a SCALE encoded header: parent hash, compact number, state root and extrinsics root,
+/// then the digest, which must end the header.
+fn decode_header(header: &str) -> Result<Header, String> {
+	let header = decode_hex(header).ok_or("the finalized block header is not hexadecimal")?;
+	let mut input = Input(&header);
+	let parent_hash = input.hash()?;
+	let number = input.compact()?;
+	let state_root = input.hash()?;
+	let _extrinsics_root = input.hash()?;
+	for _ in 0..input.compact()? {
+		match input.bytes(1)?[0] {
+			// `Other`.
+			0 => {
+				input.vec()?;
+			},
+			// `Consensus`, `Seal` and `PreRuntime`: the id of the engine, then its data.
+			4..=6 => {
+				input.bytes(4)?;
+				input.vec()?;
+			},
+			// `RuntimeEnvironmentUpdated`.
+			8 => {},
+			kind =>
+				return Err(format!("unknown digest item `{kind}` in the finalized block header")),
+		}
+	}
+	if !input.0.is_empty() {
+		return Err("the finalized block header has trailing bytes".into())
+	}
+	Ok(Header { parent_hash, number, state_root })
+}
+
+/// SCALE encoded bytes not decoded yet.
+struct Input<'a>(&'a [u8]);
+
+impl<'a> Input<'a> {
+	fn bytes(&mut self, length: usize) -> Result<&'a [u8], String> {
+		if self.0.len() < length {
+			return Err("the finalized block header is truncated".into())
+		}
+		let (bytes, rest) = self.0.split_at(length);
+		self.0 = rest;
+		Ok(bytes)
+	}
+
+	fn hash(&mut self) -> Result<[u8; 32], String> {
+		Ok(self.bytes(32)?.try_into().expect("32 bytes were read"))
+	}
+
+	/// The two lower bits of the first byte give the length of the integer.
+	fn compact(&mut self) -> Result<u64, String> {
+		let first = self.bytes(1)?[0];
+		let value = match first & 0b11 {
+			0 => u64::from(first >> 2),
+			1 => u64::from(u16::from_le_bytes([first, self.bytes(1)?[0]]) >> 2),
+			2 => {
+				let rest = self.bytes(3)?;
+				u64::from(u32::from_le_bytes([first, rest[0], rest[1], rest[2]]) >> 2)
+			},
+			_ => {
+				let length = usize::from(first >> 2) + 4;
+				if length > 8 {
+					return Err("the finalized block header has an oversized integer".into())
+				}
+				let mut bytes = [0; 8];
+				bytes[..length].copy_from_slice(self.bytes(length)?);
+				u64::from_le_bytes(bytes)
+			},
+		};
+		Ok(value)
+	}
+
+	/// A compact length, then as many bytes.
+	fn vec(&mut self) -> Result<&'a [u8], String> {
+		let length = self.compact()?;
+		self.bytes(usize::try_from(length).map_err(|_| "the finalized block header is truncated")?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+	use std::{fs, path::Path};
+
+	const PEER: &str = "12D3KooWKer94o1REDPtAhjtYR4SdLehnSrN8PEhBnZm5NBoCrMC";
+	/// Engine id of BABE, in hexadecimal.
+	const BABE: &str = "42414245";
+
+	fn spec(fields: Value) -> ChainSpec {
+		let mut spec = json!({ "name": "Test", "id": "test", "genesis": { "raw": {
+			"top": {}, "childrenDefault": {}
+		} } });
+		for (key, value) in fields.as_object().unwrap() {
+			spec[key] = value.clone();
+		}
+		serde_json::from_value(spec).unwrap()
+	}
+
+	#[test]
+	fn demo_specs_have_no_lint() {
+		let demo = Path::new(env!("CARGO_MANIFEST_DIR")).join("../chain_spec/demo");
+		for entry in fs::read_dir(demo).unwrap() {
+			let path = entry.unwrap().path();
+			if path.extension().is_some_and(|extension| extension == "json") {
+				let spec = ChainSpec::load(&path).unwrap();
+				assert_eq!(lint(&spec), [], "{}", path.display());
+			}
+		}
+	}
+
+	#[test]
+	fn incomplete_parachain_is_reported() {
+		assert_eq!(lint(&spec(json!({ "para_id": 1000 }))), [Lint::MissingRelayChain]);
+		assert_eq!(lint(&spec(json!({ "relay_chain": "westend2" }))), [Lint::MissingParaId]);
+		assert_eq!(lint(&spec(json!({ "relayChain": "westend2", "paraId": 1000 }))), []);
+	}
+
+	#[test]
+	fn boot_nodes_are_checked() {
+		let valid = [
+			format!("/dns/example.org/tcp/30333/ws/p2p/{PEER}"),
+			format!("/ip4/10.0.0.1/tcp/30333/p2p/{PEER}"),
+			format!("/ip6/::1/tcp/443/wss/p2p/{PEER}"),
+			"/ip4/35.246.224.91/tcp/30333/p2p/QmaGKGpdm2iLiVCAnEcwrAhHxrcjMdGao4UubJxq7AF77n"
+				.into(),
+		];
+		for address in valid {
+			assert_eq!(check_multiaddr(&address), Ok(()), "{address}");
+		}
+
+		let invalid = [
+			format!("dns/example.org/tcp/30333/p2p/{PEER}"),
+			"/dns/example.org/tcp/30333/ws".into(),
+			format!("/ip4/10.0.0.256/tcp/30333/p2p/{PEER}"),
+			format!("/dns/example.org/tcp/65536/p2p/{PEER}"),
+			format!("/dns/example.org/tcp/30333/http/p2p/{PEER}"),
+			"/dns/example.org/tcp/30333/p2p/12D3KooW0OIl".into(),
+			format!("/dns/example.org/tcp/30333/p2p/{PEER}/ws"),
+			"/dns/example.org/tcp".into(),
+		];
+		for address in invalid {
+			assert!(check_multiaddr(&address).is_err(), "{address}");
+		}
+	}
+
+	#[test]
+	fn duplicate_boot_nodes_are_reported_after_errors() {
+		let address = format!("/dns/example.org/tcp/30333/p2p/{PEER}");
+		let lints = lint(&spec(json!({ "bootNodes": [address, "/dns/a", address] })));
+		assert_eq!(lints.len(), 2);
+		assert!(matches!(lints[0], Lint::InvalidBootNode { .. }));
+		assert_eq!(lints[1], Lint::DuplicateBootNode(address));
+		assert_eq!(lints[1].severity(), Severity::Warning);
+	}
+
+	#[test]
+	fn unknown_chain_type_is_reported() {
+		assert_eq!(
+			lint(&spec(json!({ "chainType": "Mainnet" }))),
+			[Lint::UnknownChainType(json!("Mainnet"))]
+		);
+		assert_eq!(lint(&spec(json!({ "chainType": { "Custom": "Staging" } }))), []);
+	}
+
+	fn light_sync_state(header: &str) -> Value {
+		json!({ "lightSyncState": {
+			"babeEpochChanges": "0x00",
+			"babeFinalizedBlockWeight": 1,
+			"finalizedBlockHeader": header,
+			"grandpaAuthoritySet": "0x00",
+		} })
+	}
+
+	/// Header of the block `number`, below 64, with the digest encoded as `digest`.
+	fn header(parent_hash: [u8; 32], number: u8, state_root: [u8; 32], digest: &str) -> String {
+		let hex =
+			|bytes: [u8; 32]| bytes.iter().map(|byte| format!("{byte:02x}")).collect::<String>();
+		format!(
+			"0x{}{:02x}{}{}{digest}",
+			hex(parent_hash),
+			number << 2,
+			hex(state_root),
+			"22".repeat(32)
+		)
+	}
+
+	#[test]
+	fn light_sync_state_is_checked_against_the_genesis() {
+		let header = header([0x11; 32], 2, [0x33; 32], "00");
+		assert_eq!(lint(&spec(light_sync_state(&header))), []);
+
+		let mut runtime = light_sync_state(&header);
+		runtime["genesis"] = json!({ "runtime": {} });
+		let mut parachain = light_sync_state(&header);
+		parachain["relay_chain"] = json!("westend2");
+		parachain["para_id"] = json!(1000);
+		let truncated = light_sync_state(&header[..100]);
+		let not_hex = light_sync_state("0xzz");
+		for fields in [runtime, parachain, truncated, not_hex] {
+			assert!(
+				matches!(lint(&spec(fields.clone()))[..], [Lint::InconsistentLightSyncState(_)]),
+				"{fields}"
+			);
+		}
+	}
+
+	#[test]
+	fn finalized_block_header_is_decoded_up_to_its_digest() {
+		// A `PreRuntime` item of BABE with 2 bytes of data, then a `Seal` with 1 byte.
+		let digest =
+			format!("08{}{}", format_args!("06{BABE}08aabb"), format_args!("05{BABE}04cc"));
+		let valid = header([0x11; 32], 2, [0x33; 32], &digest);
+		assert_eq!(lint(&spec(light_sync_state(&valid))), []);
+
+		let missing_item = header([0x11; 32], 2, [0x33; 32], "04");
+		let truncated_item = header([0x11; 32], 2, [0x33; 32], &digest[..digest.len() - 2]);
+		let unknown_item = header([0x11; 32], 2, [0x33; 32], "0407");
+		let trailing_bytes = header([0x11; 32], 2, [0x33; 32], "0000");
+		for header in [missing_item, truncated_item, unknown_item, trailing_bytes] {
+			assert!(
+				matches!(
+					lint(&spec(light_sync_state(&header)))[..],
+					[Lint::InconsistentLightSyncState(_)]
+				),
+				"{header}"
+			);
+		}
+	}
+
+	#[test]
+	fn checkpoint_conflicting_with_the_genesis_is_reported() {
+		let Genesis::Raw(genesis) = spec(json!({})).genesis else { unreachable!() };
+		let state_root = genesis.state_root(StateVersion::V1).unwrap();
+		let genesis_hash = genesis.block_hash(StateVersion::V1).unwrap();
+
+		let consistent =
+			[header([0; 32], 0, state_root, "00"), header(genesis_hash, 1, [0x33; 32], "00")];
+		for header in consistent {
+			assert_eq!(lint(&spec(light_sync_state(&header))), [], "{header}");
+		}
+
+		let conflicting = [
+			header([0; 32], 0, [0x33; 32], "00"),
+			header([0x11; 32], 0, state_root, "00"),
+			header([0x11; 32], 1, [0x33; 32], "00"),
+		];
+		for header in conflicting {
+			assert!(
+				matches!(
+					lint(&spec(light_sync_state(&header)))[..],
+					[Lint::InconsistentLightSyncState(_)]
+				),
+				"{header}"
+			);
+		}
+	}
+}
@@ -0,0 +1,83 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+mod genesis;
+mod lint;
+mod model;
+mod refresh;
+mod stream;
+
+pub use genesis::StateVersion;
+pub use lint::{lint, Lint, Severity};
+pub use model::{
+	ChainSpec, ChainType, Genesis, GenesisStorage, LightSyncState, Nullable, RawGenesis,
+};
+pub use refresh::{check_refreshed, refresh_chain_spec, write_atomically, GEN_SYNC_SPEC};
+pub use stream::{storage_entries, StorageEntries, StorageEntry};
+
+#[derive(Debug, Deserialize, Serialize)]
+#[allow(non_snake_case)]
+pub struct ChainSpecMetadata {
+	pub id: String,
+	pub name: String,
+	/// As written when a string, as JSON otherwise, such as `{"Custom":"Staging"}`. Empty when
+	/// missing, see [`Self::chain_type`].
+	#[serde(default, deserialize_with = "chain_type_text")]
+	pub chainType: String,
+	pub protocolId: Option<String>,
+	#[serde(default)]
+	pub bootNodes: Vec<String>,
+	pub relay_chain: Option<String>,
+	pub para_id: Option<u32>,
+}
+
+impl ChainSpecMetadata {
+	/// The `chainType` of the spec, `None` when missing.
+	pub fn chain_type(&self) -> Option<ChainType> {
+		if self.chainType.is_empty() {
+			return None
+		}
+		let value = serde_json::from_str(&self.chainType)
+			.unwrap_or_else(|_| Value::String(self.chainType.clone()));
+		serde_json::from_value(value).ok()
+	}
+}
+
+fn chain_type_text<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+	Ok(match Value::deserialize(deserializer)? {
+		Value::String(chain_type) => chain_type,
+		value => value.to_string(),
+	})
+}
+
+#[derive(Debug)]
+pub enum ChainSpecError {
+	Json(serde_json::Error),
+	Io(std::io::Error),
+	/// The field differs between two specs which should describe the same chain.
+	Mismatch(&'static str),
+	MissingField(&'static str),
+	/// A storage key, or the value at this key, is not hexadecimal.
+	InvalidHex(String),
+}
+
+impl From<serde_json::Error> for ChainSpecError {
+	fn from(error: serde_json::Error) -> Self {
+		Self::Json(error)
+	}
+}
+
+impl From<std::io::Error> for ChainSpecError {
+	fn from(error: std::io::Error) -> Self {
+		Self::Io(error)
+	}
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+	let hex = hex.strip_prefix("0x").unwrap_or(hex);
+	// An odd trailing digit is refused by `get`.
+	(0..hex.len())
+		.step_by(2)
+		.map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+		.collect()
+}
@@ -0,0 +1,237 @@
+//! Typed model of a chain spec.
+//!
+//! Specs are written by several versions of Substrate, which disagree on whether an unset field
+//! is omitted or written as `null`. Such fields are a [`Nullable`], and unknown fields are kept in
+//! [`ChainSpec::extra`], so that a spec is written back as it was read.
+use super::ChainSpecError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// Field which is either absent, `null` or set.
+pub type Nullable<T> = Option<Option<T>>;
+
+/// Storage of a genesis, hexadecimal keys to hexadecimal values.
+pub type GenesisStorage = BTreeMap<String, String>;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainSpec {
+	pub name: String,
+	pub id: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub chain_type: Option<ChainType>,
+	/// Multiaddresses, such as `/dns/example.org/tcp/30333/ws/p2p/12D3KooW...`.
+	#[serde(default)]
+	pub boot_nodes: Vec<String>,
+	#[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+	pub telemetry_endpoints: Nullable<Vec<(String, u8)>>,
+	#[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+	pub protocol_id: Nullable<String>,
+	/// Such as `tokenSymbol`, `tokenDecimals` and `ss58Format`.
+	#[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+	pub properties: Nullable<Map<String, Value>>,
+	/// `id` of the relay chain of a parachain.
+	#[serde(
+		default,
+		rename = "relay_chain",
+		deserialize_with = "nullable",
+		skip_serializing_if = "Option::is_none"
+	)]
+	pub relay_chain: Nullable<String>,
+	#[serde(
+		default,
+		rename = "para_id",
+		deserialize_with = "nullable",
+		skip_serializing_if = "Option::is_none"
+	)]
+	pub para_id: Nullable<u32>,
+	/// Always `null`, kept by Substrate for compatibility.
+	#[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+	pub consensus_engine: Nullable<Value>,
+	#[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+	pub light_sync_state: Nullable<LightSyncState>,
+	/// Runtime code to use from a block number on, instead of the one in storage.
+	#[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+	pub code_substitutes: Nullable<BTreeMap<String, String>>,
+	/// Blocks to pick at a fork, as their number and hash.
+	#[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+	pub fork_blocks: Nullable<Vec<(u64, String)>>,
+	/// Hashes of the blocks to reject.
+	#[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+	pub bad_blocks: Nullable<Vec<String>>,
+	pub genesis: Genesis,
+	#[serde(flatten)]
+	pub extra: Map<String, Value>,
+}
+
+impl ChainSpec {
+	pub fn load(path: &Path) -> Result<Self, ChainSpecError> {
+		Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+	}
+
+	pub fn relay_chain(&self) -> Option<&str> {
+		self.relay_chain.as_ref()?.as_deref()
+	}
+
+	pub fn para_id(&self) -> Option<u32> {
+		self.para_id.flatten()
+	}
+
+	pub fn light_sync_state(&self) -> Option<&LightSyncState> {
+		self.light_sync_state.as_ref()?.as_ref()
+	}
+}
+
+fn nullable<'de, T, D>(deserializer: D) -> Result<Nullable<T>, D::Error>
+where
+	T: Deserialize<'de>,
+	D: Deserializer<'de>,
+{
+	Option::deserialize(deserializer).map(Some)
+}
+
+/// Kind of a chain. Unknown kinds are kept as [`ChainType::Other`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChainType {
+	Development,
+	Local,
+	Live,
+	Custom(String),
+	Other(Value),
+}
+
+impl<'de> Deserialize<'de> for ChainType {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let value = Value::deserialize(deserializer)?;
+		Ok(match value.as_str() {
+			Some("Development") => Self::Development,
+			Some("Local") => Self::Local,
+			Some("Live") => Self::Live,
+			_ => match value.get("Custom").and_then(Value::as_str) {
+				Some(custom) if value.as_object().is_some_and(|fields| fields.len() == 1) =>
+					Self::Custom(custom.into()),
+				_ => Self::Other(value),
+			},
+		})
+	}
+}
+
+impl Serialize for ChainType {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match self {
+			Self::Development => serializer.serialize_str("Development"),
+			Self::Local => serializer.serialize_str("Local"),
+			Self::Live => serializer.serialize_str("Live"),
+			Self::Custom(custom) =>
+				serializer.serialize_newtype_variant("ChainType", 3, "Custom", custom),
+			Self::Other(value) => value.serialize(serializer),
+		}
+	}
+}
+
+/// Checkpoint a light client syncs from instead of the genesis, SCALE encoded in hexadecimal.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LightSyncState {
+	pub babe_epoch_changes: String,
+	pub babe_finalized_block_weight: u64,
+	pub finalized_block_header: String,
+	pub grandpa_authority_set: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Genesis {
+	/// Storage of the genesis block, which is what light clients need.
+	Raw(RawGenesis),
+	/// Genesis config of the runtime, which a node builds the storage from.
+	Runtime(Value),
+	/// Code of the runtime along with a patch of, or a full, genesis config.
+	RuntimeGenesis(Value),
+	/// Only the root of the genesis storage.
+	StateRootHash(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawGenesis {
+	pub top: GenesisStorage,
+	/// Default child tries, by their storage key without the `:child_storage:default:` prefix.
+	pub children_default: BTreeMap<String, GenesisStorage>,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+	use std::path::PathBuf;
+
+	fn demo_specs() -> impl Iterator<Item = PathBuf> {
+		let demo = Path::new(env!("CARGO_MANIFEST_DIR")).join("../chain_spec/demo");
+		let mut paths = fs::read_dir(demo)
+			.unwrap()
+			.map(|entry| entry.unwrap().path())
+			.filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+			.collect::<Vec<_>>();
+		paths.sort();
+		assert!(!paths.is_empty());
+		paths.into_iter()
+	}
+
+	#[test]
+	fn demo_specs_round_trip() {
+		for path in demo_specs() {
+			let json: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+			let spec: ChainSpec = serde_json::from_value(json.clone())
+				.unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+			assert!(spec.extra.is_empty(), "{}: {:?}", path.display(), spec.extra);
+			assert_eq!(serde_json::to_value(&spec).unwrap(), json, "{}", path.display());
+		}
+	}
+
+	#[test]
+	fn demo_fields_are_typed() {
+		let demo = Path::new(env!("CARGO_MANIFEST_DIR")).join("../chain_spec/demo");
+
+		let westmint = ChainSpec::load(&demo.join("westend-westmint.json")).unwrap();
+		assert_eq!(westmint.chain_type, Some(ChainType::Live));
+		assert_eq!((westmint.relay_chain(), westmint.para_id()), (Some("westend2"), Some(1000)));
+		assert_eq!(westmint.light_sync_state, Some(None));
+
+		let westend = ChainSpec::load(&demo.join("westend.json")).unwrap();
+		assert_eq!(westend.chain_type, None);
+		assert_eq!(westend.protocol_id, None);
+		assert_eq!(westend.relay_chain(), None);
+		assert!(westend.light_sync_state().is_some());
+		assert!(matches!(westend.genesis, Genesis::Raw(ref raw) if !raw.top.is_empty()));
+	}
+
+	#[test]
+	fn custom_and_unknown_chain_types_are_kept() {
+		for chain_type in [json!({ "Custom": "Staging" }), json!("Mainnet"), json!({ "Custom": 1 })]
+		{
+			let parsed: ChainType = serde_json::from_value(chain_type.clone()).unwrap();
+			assert_eq!(serde_json::to_value(&parsed).unwrap(), chain_type);
+		}
+		let custom: ChainType = serde_json::from_value(json!({ "Custom": "Staging" })).unwrap();
+		assert_eq!(custom, ChainType::Custom("Staging".into()));
+		let unknown: ChainType = serde_json::from_value(json!("Mainnet")).unwrap();
+		assert_eq!(unknown, ChainType::Other(json!("Mainnet")));
+	}
+
+	#[test]
+	fn unknown_fields_and_runtime_genesis_are_kept() {
+		let json = json!({
+			"name": "Local",
+			"id": "local",
+			"bootNodes": [],
+			"relayChain": "rococo",
+			"genesis": { "runtimeGenesis": { "code": "0x00", "patch": {} } },
+		});
+		let spec: ChainSpec = serde_json::from_value(json.clone()).unwrap();
+		assert_eq!(spec.extra.keys().collect::<Vec<_>>(), ["relayChain"]);
+		assert!(matches!(spec.genesis, Genesis::RuntimeGenesis(_)));
+		assert_eq!(serde_json::to_value(&spec).unwrap(), json);
+	}
+}
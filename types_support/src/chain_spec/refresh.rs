@@ -0,0 +1,113 @@
+//! Refresh of the `lightSyncState` checkpoint of a chain spec.
+//!
+//! A light client starts syncing from the checkpoint of its chain spec, the older the checkpoint
+//! the longer the warp sync. A node generates a spec with a checkpoint at its finalized block
+//! through [`GEN_SYNC_SPEC`].
+use super::ChainSpecError;
+use serde_json::Value;
+use std::{
+	fs::{self, File},
+	io::Write,
+	path::Path,
+};
+
+/// Method of a node returning its chain spec with a fresh `lightSyncState`. Its only parameter
+/// asks for the genesis in its raw form, as light clients need it.
+pub const GEN_SYNC_SPEC: &str = "sync_state_genSyncSpec";
+
+/// Checks that `fresh` is a spec of the same chain as `old`, same `id` and `genesis`, and that it
+/// carries a checkpoint.
+pub fn check_refreshed(old: &str, fresh: &str) -> Result<(), ChainSpecError> {
+	let old: Value = serde_json::from_str(old)?;
+	let fresh: Value = serde_json::from_str(fresh)?;
+
+	for field in ["id", "genesis"] {
+		match (old.get(field), fresh.get(field)) {
+			(_, None) => return Err(ChainSpecError::MissingField(field)),
+			(Some(old), Some(fresh)) if old != fresh => return Err(ChainSpecError::Mismatch(field)),
+			_ => {},
+		}
+	}
+	if !fresh.get("lightSyncState").is_some_and(Value::is_object) {
+		return Err(ChainSpecError::MissingField("lightSyncState"))
+	}
+	Ok(())
+}
+
+/// Replaces the spec at `path` by `fresh`, written as the node sent it, once checked against it,
+/// see [`check_refreshed`].
+pub fn refresh_chain_spec(path: &Path, fresh: &str) -> Result<(), ChainSpecError> {
+	let old = fs::read_to_string(path)?;
+	check_refreshed(&old, fresh)?;
+	write_atomically(path, fresh)?;
+	Ok(())
+}
+
+/// Writes next to `path` then renames, so that neither readers nor a crash ever leave a
+/// truncated file behind.
+pub fn write_atomically(path: &Path, content: &str) -> std::io::Result<()> {
+	let mut temporary = path.as_os_str().to_owned();
+	temporary.push(".tmp");
+	let mut file = File::create(&temporary)?;
+	file.write_all(content.as_bytes())?;
+	// Otherwise the rename may reach the disk before the content.
+	file.sync_all()?;
+	fs::rename(&temporary, path)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	fn spec(id: &str, genesis: u8, checkpoint: Option<u8>) -> String {
+		let mut spec = json!({ "id": id, "genesis": { "raw": { "top": { "0x00": genesis } } } });
+		if let Some(checkpoint) = checkpoint {
+			spec["lightSyncState"] = json!({ "finalizedBlockHeader": checkpoint });
+		}
+		spec.to_string()
+	}
+
+	#[test]
+	fn fresh_checkpoint_of_the_same_chain_is_accepted() {
+		assert!(
+			check_refreshed(&spec("westend2", 1, Some(1)), &spec("westend2", 1, Some(2))).is_ok()
+		);
+		assert!(check_refreshed(&spec("westend2", 1, None), &spec("westend2", 1, Some(2))).is_ok());
+	}
+
+	#[test]
+	fn spec_of_another_chain_is_refused() {
+		assert!(matches!(
+			check_refreshed(&spec("westend2", 1, None), &spec("polkadot", 1, Some(2))),
+			Err(ChainSpecError::Mismatch("id"))
+		));
+		assert!(matches!(
+			check_refreshed(&spec("westend2", 1, None), &spec("westend2", 2, Some(2))),
+			Err(ChainSpecError::Mismatch("genesis"))
+		));
+	}
+
+	#[test]
+	fn spec_without_checkpoint_is_refused() {
+		assert!(matches!(
+			check_refreshed(&spec("westend2", 1, None), &spec("westend2", 1, None)),
+			Err(ChainSpecError::MissingField("lightSyncState"))
+		));
+	}
+
+	#[test]
+	fn refreshed_spec_replaces_the_old_one() {
+		let path = std::env::temp_dir().join(format!("types-support-{}.json", std::process::id()));
+		fs::write(&path, spec("westend2", 1, None)).unwrap();
+
+		let fresh = spec("westend2", 1, Some(2));
+		refresh_chain_spec(&path, &fresh).unwrap();
+		assert_eq!(fs::read_to_string(&path).unwrap(), fresh);
+
+		assert!(refresh_chain_spec(&path, &spec("polkadot", 1, Some(3))).is_err());
+		let kept: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+		assert_eq!(kept["lightSyncState"]["finalizedBlockHeader"], 2);
+		fs::remove_file(path).unwrap();
+	}
+}
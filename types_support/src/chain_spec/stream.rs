@@ -0,0 +1,350 @@
+//! Reading of a chain spec without loading its genesis in memory.
+//!
+//! The raw genesis makes most of a spec, up to several megabytes with the runtime code. The
+//! metadata skips it while parsing, and [`storage_entries`] yields its pairs one at a time.
+use super::{ChainSpecError, ChainSpecMetadata};
+use serde::de::Error as _;
+use std::{
+	fs::File,
+	io::{BufRead, BufReader, ErrorKind, Read},
+	path::Path,
+};
+
+impl ChainSpecMetadata {
+	/// Parses the metadata of the spec read from `reader`. The other fields, `genesis` included,
+	/// are skipped without being kept.
+	pub fn from_reader(reader: impl Read) -> Result<Self, ChainSpecError> {
+		Ok(serde_json::from_reader(BufReader::new(reader))?)
+	}
+
+	pub fn load(path: &Path) -> Result<Self, ChainSpecError> {
+		Self::from_reader(File::open(path)?)
+	}
+}
+
+/// Pair of the raw genesis storage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageEntry {
+	/// Storage key of the default child trie of the pair, `None` for the top trie.
+	pub child: Option<String>,
+	pub key: String,
+	pub value: String,
+}
+
+/// Iterates over the pairs of `genesis.raw` of the spec read from `reader`, in the order of the
+/// spec. Yields nothing for a spec whose genesis is not raw, and ends after the first error.
+pub fn storage_entries<R: Read>(reader: R) -> StorageEntries<R> {
+	StorageEntries {
+		scanner: Scanner { reader: BufReader::new(reader), offset: 0 },
+		frames: vec![Frame { object: Object::Root, first: true }],
+		started: false,
+	}
+}
+
+/// See [`storage_entries`].
+pub struct StorageEntries<R> {
+	scanner: Scanner<BufReader<R>>,
+	/// Objects being read, innermost last. Empty once done.
+	frames: Vec<Frame>,
+	started: bool,
+}
+
+struct Frame {
+	object: Object,
+	first: bool,
+}
+
+enum Object {
+	Root,
+	Genesis,
+	Raw,
+	ChildrenDefault,
+	Storage(Option<String>),
+}
+
+impl<R: Read> StorageEntries<R> {
+	fn next_entry(&mut self) -> Result<Option<StorageEntry>, ChainSpecError> {
+		if !self.started {
+			self.scanner.expect(b'{')?;
+			self.started = true;
+		}
+		while let Some(frame) = self.frames.last_mut() {
+			let first = std::mem::replace(&mut frame.first, false);
+			let Some(key) = self.scanner.next_key(first)? else {
+				self.frames.pop();
+				continue
+			};
+			let frame = self.frames.last().expect("the frame was just read");
+			let object = match (&frame.object, key.as_str()) {
+				(Object::Root, "genesis") => Object::Genesis,
+				(Object::Genesis, "raw") => Object::Raw,
+				(Object::Raw, "top") => Object::Storage(None),
+				(Object::Raw, "childrenDefault") => Object::ChildrenDefault,
+				(Object::ChildrenDefault, _) => Object::Storage(Some(key)),
+				(Object::Storage(child), _) => {
+					let child = child.clone();
+					let value = self.scanner.string()?;
+					return Ok(Some(StorageEntry { child, key, value }))
+				},
+				_ => {
+					self.scanner.skip_value()?;
+					continue
+				},
+			};
+			self.scanner.expect(b'{')?;
+			self.frames.push(Frame { object, first: true });
+		}
+		Ok(None)
+	}
+}
+
+impl<R: Read> Iterator for StorageEntries<R> {
+	type Item = Result<StorageEntry, ChainSpecError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		match self.next_entry() {
+			Ok(entry) => entry.map(Ok),
+			Err(e) => {
+				self.frames.clear();
+				Some(Err(e))
+			},
+		}
+	}
+}
+
+/// Reads JSON values, or skips them, one byte at a time.
+struct Scanner<R> {
+	reader: R,
+	offset: usize,
+}
+
+impl<R: BufRead> Scanner<R> {
+	fn peek(&mut self) -> Result<Option<u8>, ChainSpecError> {
+		loop {
+			match self.reader.fill_buf() {
+				Ok(buffer) => return Ok(buffer.first().copied()),
+				Err(e) if e.kind() == ErrorKind::Interrupted => {},
+				Err(e) => return Err(e.into()),
+			}
+		}
+	}
+
+	fn next(&mut self) -> Result<u8, ChainSpecError> {
+		let byte = self.peek()?.ok_or_else(|| self.error("unexpected end of the spec"))?;
+		self.reader.consume(1);
+		self.offset += 1;
+		Ok(byte)
+	}
+
+	fn error(&self, message: &str) -> ChainSpecError {
+		ChainSpecError::Json(serde_json::Error::custom(format!(
+			"{message} at byte {}",
+			self.offset
+		)))
+	}
+
+	/// Next byte which is not whitespace.
+	fn token(&mut self) -> Result<u8, ChainSpecError> {
+		loop {
+			match self.next()? {
+				b' ' | b'\t' | b'\n' | b'\r' => {},
+				byte => return Ok(byte),
+			}
+		}
+	}
+
+	fn expect(&mut self, expected: u8) -> Result<(), ChainSpecError> {
+		match self.token()? {
+			byte if byte == expected => Ok(()),
+			_ => Err(self.error(&format!("expected `{}`", expected as char))),
+		}
+	}
+
+	/// Key of the next member of the object being read, along with its `:`. `None` at its end.
+	fn next_key(&mut self, first: bool) -> Result<Option<String>, ChainSpecError> {
+		let token = match (first, self.token()?) {
+			(_, b'}') => return Ok(None),
+			(true, token) => token,
+			(false, b',') => self.token()?,
+			(false, _) => return Err(self.error("expected `,` or `}`")),
+		};
+		if token != b'"' {
+			return Err(self.error("expected a key"))
+		}
+		let key = self.rest_of_string()?;
+		self.expect(b':')?;
+		Ok(Some(key))
+	}
+
+	fn string(&mut self) -> Result<String, ChainSpecError> {
+		match self.token()? {
+			b'"' => self.rest_of_string(),
+			_ => Err(self.error("expected a string")),
+		}
+	}
+
+	/// Reads a string once its opening quote is read.
+	fn rest_of_string(&mut self) -> Result<String, ChainSpecError> {
+		let mut bytes = vec![b'"'];
+		let mut escaped = false;
+		loop {
+			let byte = self.next()?;
+			bytes.push(byte);
+			match (escaped, byte) {
+				(false, b'"') => break,
+				(false, b'\\') => escaped = true,
+				_ => escaped = false,
+			}
+		}
+		// Quotes included, for serde to unescape it.
+		Ok(serde_json::from_slice(&bytes)?)
+	}
+
+	fn skip_string(&mut self) -> Result<(), ChainSpecError> {
+		let mut escaped = false;
+		loop {
+			match (escaped, self.next()?) {
+				(false, b'"') => return Ok(()),
+				(false, b'\\') => escaped = true,
+				_ => escaped = false,
+			}
+		}
+	}
+
+	/// Skips a value of any kind, keeping nothing of it.
+	fn skip_value(&mut self) -> Result<(), ChainSpecError> {
+		let mut depth = 0usize;
+		loop {
+			match self.token()? {
+				b'"' => self.skip_string()?,
+				b'{' | b'[' => depth += 1,
+				b'}' | b']' if depth > 0 => depth -= 1,
+				b',' | b':' if depth > 0 => continue,
+				b'-' | b'0'..=b'9' | b'a'..=b'z' => self.skip_literal()?,
+				_ => return Err(self.error("expected a value")),
+			}
+			if depth == 0 {
+				return Ok(())
+			}
+		}
+	}
+
+	/// Skips the rest of a number, `true`, `false` or `null`.
+	fn skip_literal(&mut self) -> Result<(), ChainSpecError> {
+		while let Some(b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'.' | b'+' | b'-') =
+			self.peek()?
+		{
+			self.next()?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::chain_spec::{ChainSpec, ChainType, Genesis, GenesisStorage};
+	use serde_json::{json, Value};
+	use std::fs;
+
+	fn entries(spec: &str) -> Result<Vec<StorageEntry>, ChainSpecError> {
+		storage_entries(spec.as_bytes()).collect()
+	}
+
+	fn entry(child: Option<&str>, key: &str, value: &str) -> StorageEntry {
+		StorageEntry { child: child.map(Into::into), key: key.into(), value: value.into() }
+	}
+
+	#[test]
+	fn demo_specs_are_streamed() {
+		let demo = Path::new(env!("CARGO_MANIFEST_DIR")).join("../chain_spec/demo");
+		let paths = fs::read_dir(demo).unwrap().map(|entry| entry.unwrap().path());
+		for path in
+			paths.filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+		{
+			let spec = ChainSpec::load(&path).unwrap();
+			let metadata = ChainSpecMetadata::load(&path).unwrap();
+			assert_eq!((&metadata.id, &metadata.name), (&spec.id, &spec.name));
+			assert_eq!(metadata.chain_type(), spec.chain_type);
+			assert_eq!(metadata.relay_chain.as_deref(), spec.relay_chain());
+			assert_eq!(metadata.protocolId, spec.protocol_id.flatten());
+
+			let Genesis::Raw(raw) = spec.genesis else { panic!("{}", path.display()) };
+			let mut top = GenesisStorage::new();
+			for entry in storage_entries(File::open(&path).unwrap()) {
+				let entry = entry.unwrap();
+				assert_eq!(entry.child, None, "{}", path.display());
+				top.insert(entry.key, entry.value);
+			}
+			assert_eq!(top, raw.top, "{}", path.display());
+		}
+	}
+
+	#[test]
+	fn other_fields_are_skipped_in_any_order() {
+		let spec = json!({
+			"properties": { "tokenSymbol": "WND", "nested": [1, -2.5e3, true, null, { "a": [] }] },
+			"genesis": {
+				"raw": {
+					"childrenDefault": { "0x01": { "0x02": "0x03" } },
+					"top": { "0x3a636f6465": "0x00", "0x04": "0x05" },
+				},
+			},
+			"para_id": 1000,
+			"name": "Escaped \"name\" \\",
+		});
+		assert_eq!(
+			entries(&spec.to_string()).unwrap(),
+			[
+				entry(Some("0x01"), "0x02", "0x03"),
+				entry(None, "0x04", "0x05"),
+				entry(None, "0x3a636f6465", "0x00"),
+			]
+		);
+		assert_eq!(entries(&serde_json::to_string_pretty(&spec).unwrap()).unwrap().len(), 3);
+	}
+
+	#[test]
+	fn chain_type_is_kept_as_text() {
+		let metadata = |chain_type: Option<Value>| {
+			let mut spec = json!({ "id": "test", "name": "Test" });
+			if let Some(chain_type) = chain_type {
+				spec["chainType"] = chain_type;
+			}
+			ChainSpecMetadata::from_reader(spec.to_string().as_bytes()).unwrap()
+		};
+
+		let live = metadata(Some(json!("Live")));
+		assert_eq!((live.chainType.as_str(), live.chain_type()), ("Live", Some(ChainType::Live)));
+		let custom = metadata(Some(json!({ "Custom": "Staging" })));
+		assert_eq!(custom.chainType, r#"{"Custom":"Staging"}"#);
+		assert_eq!(custom.chain_type(), Some(ChainType::Custom("Staging".into())));
+		let missing = metadata(None);
+		assert_eq!((missing.chainType.as_str(), missing.chain_type()), ("", None));
+	}
+
+	#[test]
+	fn spec_without_raw_genesis_has_no_entry() {
+		let spec =
+			json!({ "id": "local", "genesis": { "runtime": { "system": { "code": "0x00" } } } });
+		assert_eq!(entries(&spec.to_string()).unwrap(), []);
+	}
+
+	#[test]
+	fn malformed_specs_end_with_an_error() {
+		let truncated = r#"{"genesis": {"raw": {"top": {"0x00": "0x01", "0x02""#;
+		let mut iter = storage_entries(truncated.as_bytes());
+		assert_eq!(iter.next().unwrap().unwrap(), entry(None, "0x00", "0x01"));
+		assert!(matches!(iter.next(), Some(Err(ChainSpecError::Json(_)))));
+		assert!(iter.next().is_none());
+
+		for spec in [
+			r#"{"genesis": {"raw": {"top": {"0x00": 1}}}}"#,
+			r#"{"genesis": {"raw": {"top": {"0x00": "0x01" "0x02": "0x03"}}}}"#,
+			r#"["genesis"]"#,
+			r#"{"id": }"#,
+		] {
+			assert!(entries(spec).is_err(), "{spec}");
+		}
+	}
+}
@@ -243,4 +243,157 @@ impl KusamaRpcMethod {
 			KusamaRpcMethod::UnsubscribeNewHead => "unsubscribe_newHead".to_string(),
 		}
 	}
+
+	/// The method named `method`, `None` for methods of other chains.
+	pub fn parse(method: &str) -> Option<Self> {
+		let method = match method {
+			"account_nextIndex" => KusamaRpcMethod::AccountNextIndex,
+			"author_hasKey" => KusamaRpcMethod::AuthorHasKey,
+			"author_hasSessionKeys" => KusamaRpcMethod::AuthorHasSessionKeys,
+			"author_insertKey" => KusamaRpcMethod::AuthorInsertKey,
+			"author_pendingExtrinsics" => KusamaRpcMethod::AuthorPendingExtrinsics,
+			"author_removeExtrinsic" => KusamaRpcMethod::AuthorRemoveExtrinsic,
+			"author_rotateKeys" => KusamaRpcMethod::AuthorRotateKeys,
+			"author_submitAndWatchExtrinsic" => KusamaRpcMethod::AuthorSubmitAndWatchExtrinsic,
+			"author_submitExtrinsic" => KusamaRpcMethod::AuthorSubmitExtrinsic,
+			"author_unwatchExtrinsic" => KusamaRpcMethod::AuthorUnwatchExtrinsic,
+			"babe_epochAuthorship" => KusamaRpcMethod::BabeEpochAuthorship,
+			"beefy_getFinalizedHead" => KusamaRpcMethod::BeefyGetFinalizedHead,
+			"beefy_subscribeJustifications" => KusamaRpcMethod::BeefySubscribeJustifications,
+			"beefy_unsubscribeJustifications" => KusamaRpcMethod::BeefyUnsubscribeJustifications,
+			"chainHead_unstable_body" => KusamaRpcMethod::ChainHeadUnstableBody,
+			"chainHead_unstable_call" => KusamaRpcMethod::ChainHeadUnstableCall,
+			"chainHead_unstable_follow" => KusamaRpcMethod::ChainHeadUnstableFollow,
+			"chainHead_unstable_genesisHash" => KusamaRpcMethod::ChainHeadUnstableGenesisHash,
+			"chainHead_unstable_header" => KusamaRpcMethod::ChainHeadUnstableHeader,
+			"chainHead_unstable_storage" => KusamaRpcMethod::ChainHeadUnstableStorage,
+			"chainHead_unstable_unfollow" => KusamaRpcMethod::ChainHeadUnstableUnfollow,
+			"chainHead_unstable_unpin" => KusamaRpcMethod::ChainHeadUnstableUnpin,
+			"chain_getBlock" => KusamaRpcMethod::ChainGetBlock,
+			"chain_getBlockHash" => KusamaRpcMethod::ChainGetBlockHash,
+			"chain_getFinalisedHead" => KusamaRpcMethod::ChainGetFinalisedHead,
+			"chain_getFinalizedHead" => KusamaRpcMethod::ChainGetFinalizedHead,
+			"chain_getHead" => KusamaRpcMethod::ChainGetHead,
+			"chain_getHeader" => KusamaRpcMethod::ChainGetHeader,
+			"chain_getRuntimeVersion" => KusamaRpcMethod::ChainGetRuntimeVersion,
+			"chain_subscribeAllHeads" => KusamaRpcMethod::ChainSubscribeAllHeads,
+			"chain_subscribeFinalisedHeads" => KusamaRpcMethod::ChainSubscribeFinalisedHeads,
+			"chain_subscribeFinalizedHeads" => KusamaRpcMethod::ChainSubscribeFinalizedHeads,
+			"chain_subscribeNewHead" => KusamaRpcMethod::ChainSubscribeNewHead,
+			"chain_subscribeNewHeads" => KusamaRpcMethod::ChainSubscribeNewHeads,
+			"chain_subscribeRuntimeVersion" => KusamaRpcMethod::ChainSubscribeRuntimeVersion,
+			"chain_unsubscribeAllHeads" => KusamaRpcMethod::ChainUnsubscribeAllHeads,
+			"chain_unsubscribeFinalisedHeads" => KusamaRpcMethod::ChainUnsubscribeFinalisedHeads,
+			"chain_unsubscribeFinalizedHeads" => KusamaRpcMethod::ChainUnsubscribeFinalizedHeads,
+			"chain_unsubscribeNewHead" => KusamaRpcMethod::ChainUnsubscribeNewHead,
+			"chain_unsubscribeNewHeads" => KusamaRpcMethod::ChainUnsubscribeNewHeads,
+			"chain_unsubscribeRuntimeVersion" => KusamaRpcMethod::ChainUnsubscribeRuntimeVersion,
+			"childstate_getKeys" => KusamaRpcMethod::ChildstateGetKeys,
+			"childstate_getKeysPaged" => KusamaRpcMethod::ChildstateGetKeysPaged,
+			"childstate_getKeysPagedAt" => KusamaRpcMethod::ChildstateGetKeysPagedAt,
+			"childstate_getStorage" => KusamaRpcMethod::ChildstateGetStorage,
+			"childstate_getStorageEntries" => KusamaRpcMethod::ChildstateGetStorageEntries,
+			"childstate_getStorageHash" => KusamaRpcMethod::ChildstateGetStorageHash,
+			"childstate_getStorageSize" => KusamaRpcMethod::ChildstateGetStorageSize,
+			"grandpa_proveFinality" => KusamaRpcMethod::GrandpaProveFinality,
+			"grandpa_roundState" => KusamaRpcMethod::GrandpaRoundState,
+			"grandpa_subscribeJustifications" => KusamaRpcMethod::GrandpaSubscribeJustifications,
+			"grandpa_unsubscribeJustifications" =>
+				KusamaRpcMethod::GrandpaUnsubscribeJustifications,
+			"mmr_generateProof" => KusamaRpcMethod::MmrGenerateProof,
+			"mmr_root" => KusamaRpcMethod::MmrRoot,
+			"mmr_verifyProof" => KusamaRpcMethod::MmrVerifyProof,
+			"mmr_verifyProofStateless" => KusamaRpcMethod::MmrVerifyProofStateless,
+			"offchain_localStorageGet" => KusamaRpcMethod::OffchainLocalStorageGet,
+			"offchain_localStorageSet" => KusamaRpcMethod::OffchainLocalStorageSet,
+			"payment_queryFeeDetails" => KusamaRpcMethod::PaymentQueryFeeDetails,
+			"payment_queryInfo" => KusamaRpcMethod::PaymentQueryInfo,
+			"state_call" => KusamaRpcMethod::StateCall,
+			"state_callAt" => KusamaRpcMethod::StateCallAt,
+			"state_getChildReadProof" => KusamaRpcMethod::StateGetChildReadProof,
+			"state_getKeys" => KusamaRpcMethod::StateGetKeys,
+			"state_getKeysPaged" => KusamaRpcMethod::StateGetKeysPaged,
+			"state_getKeysPagedAt" => KusamaRpcMethod::StateGetKeysPagedAt,
+			"state_getMetadata" => KusamaRpcMethod::StateGetMetadata,
+			"state_getPairs" => KusamaRpcMethod::StateGetPairs,
+			"state_getReadProof" => KusamaRpcMethod::StateGetReadProof,
+			"state_getRuntimeVersion" => KusamaRpcMethod::StateGetRuntimeVersion,
+			"state_getStorage" => KusamaRpcMethod::StateGetStorage,
+			"state_getStorageAt" => KusamaRpcMethod::StateGetStorageAt,
+			"state_getStorageHash" => KusamaRpcMethod::StateGetStorageHash,
+			"state_getStorageHashAt" => KusamaRpcMethod::StateGetStorageHashAt,
+			"state_getStorageSize" => KusamaRpcMethod::StateGetStorageSize,
+			"state_getStorageSizeAt" => KusamaRpcMethod::StateGetStorageSizeAt,
+			"state_queryStorage" => KusamaRpcMethod::StateQueryStorage,
+			"state_queryStorageAt" => KusamaRpcMethod::StateQueryStorageAt,
+			"state_subscribeRuntimeVersion" => KusamaRpcMethod::StateSubscribeRuntimeVersion,
+			"state_subscribeStorage" => KusamaRpcMethod::StateSubscribeStorage,
+			"state_traceBlock" => KusamaRpcMethod::StateTraceBlock,
+			"state_trieMigrationStatus" => KusamaRpcMethod::StateTrieMigrationStatus,
+			"state_unsubscribeRuntimeVersion" => KusamaRpcMethod::StateUnsubscribeRuntimeVersion,
+			"state_unsubscribeStorage" => KusamaRpcMethod::StateUnsubscribeStorage,
+			"subscribe_newHead" => KusamaRpcMethod::SubscribeNewHead,
+			"sync_state_genSyncSpec" => KusamaRpcMethod::SyncStateGenSyncSpec,
+			"system_accountNextIndex" => KusamaRpcMethod::SystemAccountNextIndex,
+			"system_addLogFilter" => KusamaRpcMethod::SystemAddLogFilter,
+			"system_addReservedPeer" => KusamaRpcMethod::SystemAddReservedPeer,
+			"system_chain" => KusamaRpcMethod::SystemChain,
+			"system_chainType" => KusamaRpcMethod::SystemChainType,
+			"system_dryRun" => KusamaRpcMethod::SystemDryRun,
+			"system_dryRunAt" => KusamaRpcMethod::SystemDryRunAt,
+			"system_health" => KusamaRpcMethod::SystemHealth,
+			"system_localListenAddresses" => KusamaRpcMethod::SystemLocalListenAddresses,
+			"system_localPeerId" => KusamaRpcMethod::SystemLocalPeerId,
+			"system_name" => KusamaRpcMethod::SystemName,
+			"system_nodeRoles" => KusamaRpcMethod::SystemNodeRoles,
+			"system_peers" => KusamaRpcMethod::SystemPeers,
+			"system_properties" => KusamaRpcMethod::SystemProperties,
+			"system_removeReservedPeer" => KusamaRpcMethod::SystemRemoveReservedPeer,
+			"system_reservedPeers" => KusamaRpcMethod::SystemReservedPeers,
+			"system_resetLogFilter" => KusamaRpcMethod::SystemResetLogFilter,
+			"system_syncState" => KusamaRpcMethod::SystemSyncState,
+			"system_unstable_networkState" => KusamaRpcMethod::SystemUnstableNetworkState,
+			"system_version" => KusamaRpcMethod::SystemVersion,
+			"transaction_unstable_submitAndWatch" =>
+				KusamaRpcMethod::TransactionUnstableSubmitAndWatch,
+			"transaction_unstable_unwatch" => KusamaRpcMethod::TransactionUnstableUnwatch,
+			"unsubscribe_newHead" => KusamaRpcMethod::UnsubscribeNewHead,
+			_ => return None,
+		};
+		Some(method)
+	}
+
+	/// Whether Substrate nodes only serve the method with `--rpc-methods unsafe`: it exposes or
+	/// changes the node itself, such as its keystore, peers or logging, or it may scan the whole
+	/// state, such as `state_getPairs` and `state_queryStorage`.
+	pub fn is_unsafe(&self) -> bool {
+		matches!(
+			self,
+			KusamaRpcMethod::AuthorHasKey
+				| KusamaRpcMethod::AuthorHasSessionKeys
+				| KusamaRpcMethod::AuthorInsertKey
+				| KusamaRpcMethod::AuthorRemoveExtrinsic
+				| KusamaRpcMethod::AuthorRotateKeys
+				| KusamaRpcMethod::OffchainLocalStorageGet
+				| KusamaRpcMethod::OffchainLocalStorageSet
+				| KusamaRpcMethod::StateGetPairs
+				| KusamaRpcMethod::StateQueryStorage
+				| KusamaRpcMethod::StateTraceBlock
+				| KusamaRpcMethod::StateTrieMigrationStatus
+				| KusamaRpcMethod::SystemAddLogFilter
+				| KusamaRpcMethod::SystemAddReservedPeer
+				| KusamaRpcMethod::SystemDryRun
+				| KusamaRpcMethod::SystemDryRunAt
+				| KusamaRpcMethod::SystemNodeRoles
+				| KusamaRpcMethod::SystemPeers
+				| KusamaRpcMethod::SystemRemoveReservedPeer
+				| KusamaRpcMethod::SystemReservedPeers
+				| KusamaRpcMethod::SystemResetLogFilter
+				| KusamaRpcMethod::SystemUnstableNetworkState
+		)
+	}
+
+	pub fn is_safe(&self) -> bool {
+		!self.is_unsafe()
+	}
 }
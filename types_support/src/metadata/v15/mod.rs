@@ -1,2 +1,67 @@
 pub mod kusama_rpc;
 pub mod polkadot_rpc;
+
+use kusama_rpc::KusamaRpcMethod;
+use polkadot_rpc::PolkadotRpcMethod;
+
+/// Unsafe methods served by Substrate nodes which neither chain lists, see
+/// [`PolkadotRpcMethod::is_unsafe`].
+pub const UNLISTED_UNSAFE_RPC_METHODS: &[&str] =
+	&["dev_getBlockStats", "offchain_localStorageClear", "statement_dump", "statement_remove"];
+
+/// Whether `method` is unsafe on either chain, see [`PolkadotRpcMethod::is_unsafe`], or one of
+/// the [`UNLISTED_UNSAFE_RPC_METHODS`]. Other methods are considered safe.
+pub fn is_unsafe_rpc_method(method: &str) -> bool {
+	PolkadotRpcMethod::parse(method).is_some_and(|method| method.is_unsafe())
+		|| KusamaRpcMethod::parse(method).is_some_and(|method| method.is_unsafe())
+		|| UNLISTED_UNSAFE_RPC_METHODS.contains(&method)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn node_admin_methods_are_unsafe() {
+		assert!(PolkadotRpcMethod::AuthorInsertKey.is_unsafe());
+		assert!(PolkadotRpcMethod::AuthorRotateKeys.is_unsafe());
+		assert!(PolkadotRpcMethod::SystemAddReservedPeer.is_unsafe());
+		assert!(PolkadotRpcMethod::OffchainLocalStorageSet.is_unsafe());
+		assert!(KusamaRpcMethod::SystemAddLogFilter.is_unsafe());
+		assert!(PolkadotRpcMethod::ChainGetBlockHash.is_safe());
+		assert!(KusamaRpcMethod::AuthorSubmitExtrinsic.is_safe());
+		assert!(!is_unsafe_rpc_method("system_health"));
+	}
+
+	#[test]
+	fn expensive_chain_queries_are_unsafe() {
+		// Served with `--rpc-methods unsafe` only, as they may scan the whole state.
+		assert!(PolkadotRpcMethod::StateGetPairs.is_unsafe());
+		assert!(KusamaRpcMethod::StateQueryStorage.is_unsafe());
+		assert!(is_unsafe_rpc_method("state_getPairs"));
+		assert!(is_unsafe_rpc_method("state_queryStorage"));
+		assert!(!is_unsafe_rpc_method("state_queryStorageAt"));
+	}
+
+	#[test]
+	fn unlisted_node_admin_methods_are_unsafe() {
+		for method in UNLISTED_UNSAFE_RPC_METHODS {
+			assert!(PolkadotRpcMethod::parse(method).is_none(), "{method} is listed");
+			assert!(KusamaRpcMethod::parse(method).is_none(), "{method} is listed");
+			assert!(is_unsafe_rpc_method(method));
+		}
+		assert!(!is_unsafe_rpc_method("mock_echo"));
+	}
+
+	#[test]
+	fn names_are_parsed_back() {
+		for method in [PolkadotRpcMethod::StateGetStorage, PolkadotRpcMethod::SystemDryRunAt] {
+			let name = method.as_string();
+			assert_eq!(
+				PolkadotRpcMethod::parse(&name).map(|method| method.as_string()),
+				Some(name)
+			);
+		}
+		assert!(KusamaRpcMethod::parse("mock_echo").is_none());
+	}
+}
@@ -244,4 +244,157 @@ impl PolkadotRpcMethod {
 			PolkadotRpcMethod::UnsubscribeNewHead => "unsubscribe_newHead".to_string(),
 		}
 	}
+
+	/// The method named `method`, `None` for methods of other chains.
+	pub fn parse(method: &str) -> Option<Self> {
+		let method = match method {
+			"account_nextIndex" => PolkadotRpcMethod::AccountNextIndex,
+			"author_hasKey" => PolkadotRpcMethod::AuthorHasKey,
+			"author_hasSessionKeys" => PolkadotRpcMethod::AuthorHasSessionKeys,
+			"author_insertKey" => PolkadotRpcMethod::AuthorInsertKey,
+			"author_pendingExtrinsics" => PolkadotRpcMethod::AuthorPendingExtrinsics,
+			"author_removeExtrinsic" => PolkadotRpcMethod::AuthorRemoveExtrinsic,
+			"author_rotateKeys" => PolkadotRpcMethod::AuthorRotateKeys,
+			"author_submitAndWatchExtrinsic" => PolkadotRpcMethod::AuthorSubmitAndWatchExtrinsic,
+			"author_submitExtrinsic" => PolkadotRpcMethod::AuthorSubmitExtrinsic,
+			"author_unwatchExtrinsic" => PolkadotRpcMethod::AuthorUnwatchExtrinsic,
+			"babe_epochAuthorship" => PolkadotRpcMethod::BabeEpochAuthorship,
+			"beefy_getFinalizedHead" => PolkadotRpcMethod::BeefyGetFinalizedHead,
+			"beefy_subscribeJustifications" => PolkadotRpcMethod::BeefySubscribeJustifications,
+			"beefy_unsubscribeJustifications" => PolkadotRpcMethod::BeefyUnsubscribeJustifications,
+			"chainHead_unstable_body" => PolkadotRpcMethod::ChainHeadUnstableBody,
+			"chainHead_unstable_call" => PolkadotRpcMethod::ChainHeadUnstableCall,
+			"chainHead_unstable_follow" => PolkadotRpcMethod::ChainHeadUnstableFollow,
+			"chainHead_unstable_genesisHash" => PolkadotRpcMethod::ChainHeadUnstableGenesisHash,
+			"chainHead_unstable_header" => PolkadotRpcMethod::ChainHeadUnstableHeader,
+			"chainHead_unstable_storage" => PolkadotRpcMethod::ChainHeadUnstableStorage,
+			"chainHead_unstable_unfollow" => PolkadotRpcMethod::ChainHeadUnstableUnfollow,
+			"chainHead_unstable_unpin" => PolkadotRpcMethod::ChainHeadUnstableUnpin,
+			"chain_getBlock" => PolkadotRpcMethod::ChainGetBlock,
+			"chain_getBlockHash" => PolkadotRpcMethod::ChainGetBlockHash,
+			"chain_getFinalisedHead" => PolkadotRpcMethod::ChainGetFinalisedHead,
+			"chain_getFinalizedHead" => PolkadotRpcMethod::ChainGetFinalizedHead,
+			"chain_getHead" => PolkadotRpcMethod::ChainGetHead,
+			"chain_getHeader" => PolkadotRpcMethod::ChainGetHeader,
+			"chain_getRuntimeVersion" => PolkadotRpcMethod::ChainGetRuntimeVersion,
+			"chain_subscribeAllHeads" => PolkadotRpcMethod::ChainSubscribeAllHeads,
+			"chain_subscribeFinalisedHeads" => PolkadotRpcMethod::ChainSubscribeFinalisedHeads,
+			"chain_subscribeFinalizedHeads" => PolkadotRpcMethod::ChainSubscribeFinalizedHeads,
+			"chain_subscribeNewHead" => PolkadotRpcMethod::ChainSubscribeNewHead,
+			"chain_subscribeNewHeads" => PolkadotRpcMethod::ChainSubscribeNewHeads,
+			"chain_subscribeRuntimeVersion" => PolkadotRpcMethod::ChainSubscribeRuntimeVersion,
+			"chain_unsubscribeAllHeads" => PolkadotRpcMethod::ChainUnsubscribeAllHeads,
+			"chain_unsubscribeFinalisedHeads" => PolkadotRpcMethod::ChainUnsubscribeFinalisedHeads,
+			"chain_unsubscribeFinalizedHeads" => PolkadotRpcMethod::ChainUnsubscribeFinalizedHeads,
+			"chain_unsubscribeNewHead" => PolkadotRpcMethod::ChainUnsubscribeNewHead,
+			"chain_unsubscribeNewHeads" => PolkadotRpcMethod::ChainUnsubscribeNewHeads,
+			"chain_unsubscribeRuntimeVersion" => PolkadotRpcMethod::ChainUnsubscribeRuntimeVersion,
+			"childstate_getKeys" => PolkadotRpcMethod::ChildstateGetKeys,
+			"childstate_getKeysPaged" => PolkadotRpcMethod::ChildstateGetKeysPaged,
+			"childstate_getKeysPagedAt" => PolkadotRpcMethod::ChildstateGetKeysPagedAt,
+			"childstate_getStorage" => PolkadotRpcMethod::ChildstateGetStorage,
+			"childstate_getStorageEntries" => PolkadotRpcMethod::ChildstateGetStorageEntries,
+			"childstate_getStorageHash" => PolkadotRpcMethod::ChildstateGetStorageHash,
+			"childstate_getStorageSize" => PolkadotRpcMethod::ChildstateGetStorageSize,
+			"grandpa_proveFinality" => PolkadotRpcMethod::GrandpaProveFinality,
+			"grandpa_roundState" => PolkadotRpcMethod::GrandpaRoundState,
+			"grandpa_subscribeJustifications" => PolkadotRpcMethod::GrandpaSubscribeJustifications,
+			"grandpa_unsubscribeJustifications" =>
+				PolkadotRpcMethod::GrandpaUnsubscribeJustifications,
+			"mmr_generateProof" => PolkadotRpcMethod::MmrGenerateProof,
+			"mmr_root" => PolkadotRpcMethod::MmrRoot,
+			"mmr_verifyProof" => PolkadotRpcMethod::MmrVerifyProof,
+			"mmr_verifyProofStateless" => PolkadotRpcMethod::MmrVerifyProofStateless,
+			"offchain_localStorageGet" => PolkadotRpcMethod::OffchainLocalStorageGet,
+			"offchain_localStorageSet" => PolkadotRpcMethod::OffchainLocalStorageSet,
+			"payment_queryFeeDetails" => PolkadotRpcMethod::PaymentQueryFeeDetails,
+			"payment_queryInfo" => PolkadotRpcMethod::PaymentQueryInfo,
+			"state_call" => PolkadotRpcMethod::StateCall,
+			"state_callAt" => PolkadotRpcMethod::StateCallAt,
+			"state_getChildReadProof" => PolkadotRpcMethod::StateGetChildReadProof,
+			"state_getKeys" => PolkadotRpcMethod::StateGetKeys,
+			"state_getKeysPaged" => PolkadotRpcMethod::StateGetKeysPaged,
+			"state_getKeysPagedAt" => PolkadotRpcMethod::StateGetKeysPagedAt,
+			"state_getMetadata" => PolkadotRpcMethod::StateGetMetadata,
+			"state_getPairs" => PolkadotRpcMethod::StateGetPairs,
+			"state_getReadProof" => PolkadotRpcMethod::StateGetReadProof,
+			"state_getRuntimeVersion" => PolkadotRpcMethod::StateGetRuntimeVersion,
+			"state_getStorage" => PolkadotRpcMethod::StateGetStorage,
+			"state_getStorageAt" => PolkadotRpcMethod::StateGetStorageAt,
+			"state_getStorageHash" => PolkadotRpcMethod::StateGetStorageHash,
+			"state_getStorageHashAt" => PolkadotRpcMethod::StateGetStorageHashAt,
+			"state_getStorageSize" => PolkadotRpcMethod::StateGetStorageSize,
+			"state_getStorageSizeAt" => PolkadotRpcMethod::StateGetStorageSizeAt,
+			"state_queryStorage" => PolkadotRpcMethod::StateQueryStorage,
+			"state_queryStorageAt" => PolkadotRpcMethod::StateQueryStorageAt,
+			"state_subscribeRuntimeVersion" => PolkadotRpcMethod::StateSubscribeRuntimeVersion,
+			"state_subscribeStorage" => PolkadotRpcMethod::StateSubscribeStorage,
+			"state_traceBlock" => PolkadotRpcMethod::StateTraceBlock,
+			"state_trieMigrationStatus" => PolkadotRpcMethod::StateTrieMigrationStatus,
+			"state_unsubscribeRuntimeVersion" => PolkadotRpcMethod::StateUnsubscribeRuntimeVersion,
+			"state_unsubscribeStorage" => PolkadotRpcMethod::StateUnsubscribeStorage,
+			"subscribe_newHead" => PolkadotRpcMethod::SubscribeNewHead,
+			"sync_state_genSyncSpec" => PolkadotRpcMethod::SyncStateGenSyncSpec,
+			"system_accountNextIndex" => PolkadotRpcMethod::SystemAccountNextIndex,
+			"system_addLogFilter" => PolkadotRpcMethod::SystemAddLogFilter,
+			"system_addReservedPeer" => PolkadotRpcMethod::SystemAddReservedPeer,
+			"system_chain" => PolkadotRpcMethod::SystemChain,
+			"system_chainType" => PolkadotRpcMethod::SystemChainType,
+			"system_dryRun" => PolkadotRpcMethod::SystemDryRun,
+			"system_dryRunAt" => PolkadotRpcMethod::SystemDryRunAt,
+			"system_health" => PolkadotRpcMethod::SystemHealth,
+			"system_localListenAddresses" => PolkadotRpcMethod::SystemLocalListenAddresses,
+			"system_localPeerId" => PolkadotRpcMethod::SystemLocalPeerId,
+			"system_name" => PolkadotRpcMethod::SystemName,
+			"system_nodeRoles" => PolkadotRpcMethod::SystemNodeRoles,
+			"system_peers" => PolkadotRpcMethod::SystemPeers,
+			"system_properties" => PolkadotRpcMethod::SystemProperties,
+			"system_removeReservedPeer" => PolkadotRpcMethod::SystemRemoveReservedPeer,
+			"system_reservedPeers" => PolkadotRpcMethod::SystemReservedPeers,
+			"system_resetLogFilter" => PolkadotRpcMethod::SystemResetLogFilter,
+			"system_syncState" => PolkadotRpcMethod::SystemSyncState,
+			"system_unstable_networkState" => PolkadotRpcMethod::SystemUnstableNetworkState,
+			"system_version" => PolkadotRpcMethod::SystemVersion,
+			"transaction_unstable_submitAndWatch" =>
+				PolkadotRpcMethod::TransactionUnstableSubmitAndWatch,
+			"transaction_unstable_unwatch" => PolkadotRpcMethod::TransactionUnstableUnwatch,
+			"unsubscribe_newHead" => PolkadotRpcMethod::UnsubscribeNewHead,
+			_ => return None,
+		};
+		Some(method)
+	}
+
+	/// Whether Substrate nodes only serve the method with `--rpc-methods unsafe`: it exposes or
+	/// changes the node itself, such as its keystore, peers or logging, or it may scan the whole
+	/// state, such as `state_getPairs` and `state_queryStorage`.
+	pub fn is_unsafe(&self) -> bool {
+		matches!(
+			self,
+			PolkadotRpcMethod::AuthorHasKey
+				| PolkadotRpcMethod::AuthorHasSessionKeys
+				| PolkadotRpcMethod::AuthorInsertKey
+				| PolkadotRpcMethod::AuthorRemoveExtrinsic
+				| PolkadotRpcMethod::AuthorRotateKeys
+				| PolkadotRpcMethod::OffchainLocalStorageGet
+				| PolkadotRpcMethod::OffchainLocalStorageSet
+				| PolkadotRpcMethod::StateGetPairs
+				| PolkadotRpcMethod::StateQueryStorage
+				| PolkadotRpcMethod::StateTraceBlock
+				| PolkadotRpcMethod::StateTrieMigrationStatus
+				| PolkadotRpcMethod::SystemAddLogFilter
+				| PolkadotRpcMethod::SystemAddReservedPeer
+				| PolkadotRpcMethod::SystemDryRun
+				| PolkadotRpcMethod::SystemDryRunAt
+				| PolkadotRpcMethod::SystemNodeRoles
+				| PolkadotRpcMethod::SystemPeers
+				| PolkadotRpcMethod::SystemRemoveReservedPeer
+				| PolkadotRpcMethod::SystemReservedPeers
+				| PolkadotRpcMethod::SystemResetLogFilter
+				| PolkadotRpcMethod::SystemUnstableNetworkState
+		)
+	}
+
+	pub fn is_safe(&self) -> bool {
+		!self.is_unsafe()
+	}
 }